@@ -1,8 +1,13 @@
 use cfg_if::cfg_if;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use humansize::{format_size, BINARY};
-use rc_zip::parse::{Archive, EntryKind, Method, Version};
-use rc_zip_sync::{ReadZip, ReadZipStreaming};
+use rc_zip::parse::{Archive, EntryKind, Method, SortKey, Version};
+use rc_zip_sync::{
+    has_symlink_parent, recover_from_local_headers, ArchiveHandle, EntryHandle, HasCursor, ReadZip,
+    ReadZipStreaming, ZipWriter,
+};
+#[cfg(not(windows))]
+use rc_zip_sync::{rc_zip::encoding::Encoding, read_symlink_target, LinkTarget};
 
 use std::{
     borrow::Cow,
@@ -10,7 +15,7 @@ use std::{
     fmt,
     fs::File,
     io::{self, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -58,6 +63,14 @@ enum Commands {
 
         #[arg(short, long)]
         verbose: bool,
+
+        /// List entries in this order, rather than in central directory order
+        #[arg(long, value_enum)]
+        sort: Option<SortBy>,
+
+        /// Reverse the order of the listing
+        #[arg(long)]
+        reverse: bool,
     },
     Unzip {
         zipfile: PathBuf,
@@ -71,6 +84,37 @@ enum Commands {
         #[arg(long)]
         dir: Option<String>,
     },
+    Cat {
+        zipfile: PathBuf,
+        name: String,
+    },
+    /// Rebuild an archive whose central directory is missing or damaged,
+    /// from the local headers of its entries
+    Repair {
+        zipfile: PathBuf,
+        output: PathBuf,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SortBy {
+    Name,
+    Size,
+    Date,
+    Method,
+    Offset,
+}
+
+impl From<SortBy> for SortKey {
+    fn from(sort: SortBy) -> Self {
+        match sort {
+            SortBy::Name => SortKey::Name,
+            SortBy::Size => SortKey::Size,
+            SortBy::Date => SortKey::Modified,
+            SortBy::Method => SortKey::Method,
+            SortBy::Offset => SortKey::Offset,
+        }
+    }
 }
 
 fn main() {
@@ -90,29 +134,29 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
         let mut methods = HashSet::<Method>::new();
         let mut compressed_size: u64 = 0;
         let mut uncompressed_size: u64 = 0;
-        let mut num_dirs = 0;
-        let mut num_symlinks = 0;
         let mut num_files = 0;
 
         for entry in archive.entries() {
             reader_versions.insert(entry.reader_version);
-            match entry.kind() {
-                EntryKind::Symlink => {
-                    num_symlinks += 1;
-                }
-                EntryKind::Directory => {
-                    num_dirs += 1;
-                }
-                EntryKind::File => {
-                    methods.insert(entry.method);
-                    num_files += 1;
-                    compressed_size += entry.compressed_size;
-                    uncompressed_size += entry.uncompressed_size;
-                }
-            }
         }
+        for entry in archive.files() {
+            methods.insert(entry.method);
+            num_files += 1;
+            compressed_size += entry.compressed_size;
+            uncompressed_size += entry.uncompressed_size;
+        }
+        let num_dirs = archive.directories().count();
+        let num_symlinks = archive.symlinks().count();
         println!("Versions: {:?}", reader_versions);
         println!("Encoding: {}, Methods: {:?}", archive.encoding(), methods);
+        for method in archive.disabled_methods() {
+            let name = format!("{:?}", method).to_lowercase();
+            if method.is_supported() {
+                println!("Warning: contains {name} entries but {name} support is not compiled in.");
+            } else {
+                println!("Warning: contains {name} entries, which are not supported.");
+            }
+        }
         println!(
             "{} ({:.2}% compression) ({} files, {} dirs, {} symlinks)",
             format_size(uncompressed_size, BINARY),
@@ -129,12 +173,17 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             let reader = file.read_zip()?;
             info(&reader);
         }
-        Commands::Ls { zipfile, verbose } => {
+        Commands::Ls {
+            zipfile,
+            verbose,
+            sort,
+            reverse,
+        } => {
             let zipfile = File::open(zipfile)?;
             let reader = zipfile.read_zip()?;
             info(&reader);
 
-            for entry in reader.entries() {
+            for entry in listing_order(&reader, sort, reverse) {
                 print!(
                     "{mode:>9} {size:>12} {name}",
                     mode = entry.mode,
@@ -163,7 +212,7 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                         print!("\t{target}", target = target);
                     }
 
-                    print!("\t{:?}", entry.method);
+                    print!("\t{}", entry.method);
                     if !entry.comment.is_empty() {
                         print!("\t{comment}", comment = entry.comment);
                     }
@@ -171,6 +220,19 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                 println!();
             }
         }
+        Commands::Cat { zipfile, name } => {
+            let zipfile = File::open(zipfile)?;
+            let reader = zipfile.read_zip()?;
+            let entry = reader
+                .by_name(&name)
+                .ok_or_else(|| format!("no entry named {name:?} in archive"))?;
+            if let EntryKind::Directory = entry.kind() {
+                return Err(format!("{name:?} is a directory").into());
+            }
+
+            let mut stdout = io::stdout().lock();
+            io::copy(&mut entry.reader(), &mut stdout)?;
+        }
         Commands::Unzip { zipfile, dir } => {
             let zipfile = File::open(zipfile)?;
             let dir = PathBuf::from(dir.unwrap_or_else(|| ".".into()));
@@ -203,14 +265,16 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     None => continue,
                 };
 
-                pbar.set_message(entry_name.to_string());
+                pbar.set_message(entry_name.display().to_string());
                 match entry.kind() {
+                    // don't write through symlinks extracted earlier
+                    _ if has_symlink_parent(&dir, &entry_name) => {}
                     EntryKind::Symlink => {
                         num_symlinks += 1;
 
                         cfg_if! {
                             if #[cfg(windows)] {
-                                let path = dir.join(entry_name);
+                                let path = dir.join(&entry_name);
                                 std::fs::create_dir_all(
                                     path.parent()
                                         .expect("all full entry paths should have parent paths"),
@@ -219,7 +283,7 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                                 let mut entry_reader = entry.reader();
                                 std::io::copy(&mut entry_reader, &mut entry_writer)?;
                             } else {
-                                let path = dir.join(entry_name);
+                                let path = dir.join(&entry_name);
                                 std::fs::create_dir_all(
                                     path.parent()
                                         .expect("all full entry paths should have parent paths"),
@@ -230,11 +294,10 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                                     }
                                 }
 
-                                let mut src = String::new();
-                                entry.reader().read_to_string(&mut src)?;
+                                let src = entry.read_symlink_target()?;
 
                                 // validate pointing path before creating a symbolic link
-                                if src.contains("..") {
+                                if src.is_escaping(&entry_name) {
                                     continue;
                                 }
                                 std::os::unix::fs::symlink(src, &path)?;
@@ -243,7 +306,7 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     }
                     EntryKind::Directory => {
                         num_dirs += 1;
-                        let path = dir.join(entry_name);
+                        let path = dir.join(&entry_name);
                         std::fs::create_dir_all(
                             path.parent()
                                 .expect("all full entry paths should have parent paths"),
@@ -251,17 +314,16 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     }
                     EntryKind::File => {
                         num_files += 1;
-                        let path = dir.join(entry_name);
+                        let path = dir.join(&entry_name);
                         std::fs::create_dir_all(
                             path.parent()
                                 .expect("all full entry paths should have parent paths"),
                         )?;
                         let mut entry_writer = File::create(path)?;
-                        let entry_reader = entry.reader();
                         let before_entry_bytes = done_bytes;
                         let mut progress_reader =
-                            ProgressReader::new(entry_reader, entry.uncompressed_size, |prog| {
-                                pbar.set_position(before_entry_bytes + prog.done);
+                            entry.reader_with_progress(|_compressed, uncompressed| {
+                                pbar.set_position(before_entry_bytes + uncompressed);
                             });
 
                         let copied_bytes = std::io::copy(&mut progress_reader, &mut entry_writer)?;
@@ -312,14 +374,16 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     None => continue,
                 };
 
-                pbar.set_message(entry_name.to_string());
+                pbar.set_message(entry_name.display().to_string());
                 match entry_reader.entry().kind() {
+                    // don't write through symlinks extracted earlier
+                    _ if has_symlink_parent(&dir, &entry_name) => {}
                     EntryKind::Symlink => {
                         num_symlinks += 1;
 
                         cfg_if! {
                             if #[cfg(windows)] {
-                                let path = dir.join(entry_name);
+                                let path = dir.join(&entry_name);
                                 std::fs::create_dir_all(
                                     path.parent()
                                         .expect("all full entry paths should have parent paths"),
@@ -327,7 +391,7 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                                 let mut entry_writer = File::create(path)?;
                                 std::io::copy(&mut entry_reader, &mut entry_writer)?;
                             } else {
-                                let path = dir.join(entry_name);
+                                let path = dir.join(&entry_name);
                                 std::fs::create_dir_all(
                                     path.parent()
                                         .expect("all full entry paths should have parent paths"),
@@ -338,11 +402,12 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                                     }
                                 }
 
-                                let mut src = String::new();
-                                entry_reader.read_to_string(&mut src)?;
+                                // there's no archive encoding to go by when
+                                // streaming, targets are most likely UTF-8
+                                let src = read_symlink_target(&mut entry_reader, Encoding::Utf8)?;
 
                                 // validate pointing path before creating a symbolic link
-                                if src.contains("..") {
+                                if src.is_escaping(&entry_name) {
                                     continue;
                                 }
                                 std::os::unix::fs::symlink(src, &path)?;
@@ -351,7 +416,7 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     }
                     EntryKind::Directory => {
                         num_dirs += 1;
-                        let path = dir.join(entry_name);
+                        let path = dir.join(&entry_name);
                         std::fs::create_dir_all(
                             path.parent()
                                 .expect("all full entry paths should have parent paths"),
@@ -359,7 +424,7 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     }
                     EntryKind::File => {
                         num_files += 1;
-                        let path = dir.join(entry_name);
+                        let path = dir.join(&entry_name);
                         std::fs::create_dir_all(
                             path.parent()
                                 .expect("all full entry paths should have parent paths"),
@@ -402,11 +467,51 @@ fn do_main(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
             let bps = (uncompressed_size as f64 / seconds) as u64;
             println!("Overall extraction speed: {} / s", format_size(bps, BINARY));
         }
+        Commands::Repair { zipfile, output } => {
+            let num_entries = repair(&zipfile, &output)?;
+            println!("Recovered {} entries to {}", num_entries, output.display());
+        }
     }
 
     Ok(())
 }
 
+/// The entries of `archive` in the order `ls` lists them: central directory
+/// order, unless `sort` says otherwise.
+fn listing_order<'a, F>(
+    archive: &'a ArchiveHandle<'_, F>,
+    sort: Option<SortBy>,
+    reverse: bool,
+) -> Vec<EntryHandle<'a, F>>
+where
+    F: HasCursor,
+{
+    let mut entries = match sort {
+        Some(sort) => archive.entries_sorted_by(sort.into()),
+        None => archive.entries().collect(),
+    };
+    if reverse {
+        entries.reverse();
+    }
+    entries
+}
+
+/// Copies the entries found by scanning the local headers of `zipfile` to a
+/// new archive at `output`, as-is, and returns how many there were.
+fn repair(zipfile: &Path, output: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let zipfile = File::open(zipfile)?;
+    let size = zipfile.metadata()?.len();
+    let archive = recover_from_local_headers(&zipfile, size)?;
+
+    let mut writer = ZipWriter::new(io::BufWriter::new(File::create(output)?));
+    for entry in archive.entries() {
+        writer.copy_raw(&entry)?;
+    }
+    let num_entries = writer.entries().len();
+    writer.finish()?;
+    Ok(num_entries)
+}
+
 trait Truncate {
     fn truncate_path(&self, limit: usize) -> String;
 }
@@ -492,3 +597,90 @@ where
         self.inner
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc_zip::fsm::EntryOptions;
+    use std::io::Write;
+
+    #[test]
+    fn ls_sort() {
+        let mut writer = ZipWriter::new(Vec::new());
+        for (name, contents, method) in [
+            ("b.txt", "a bit longer", Method::Store),
+            ("c.txt", "short", Method::Deflate),
+            ("a.txt", "the longest of them all", Method::Store),
+        ] {
+            let options = EntryOptions::default().method(method);
+            writer.start_entry(name, &options).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+            writer.finish_entry().unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        let archive = bytes.read_zip().unwrap();
+
+        let names = |sort, reverse| {
+            listing_order(&archive, sort, reverse)
+                .into_iter()
+                .map(|entry| entry.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(None, false), ["b.txt", "c.txt", "a.txt"]);
+        assert_eq!(
+            names(Some(SortBy::Name), false),
+            ["a.txt", "b.txt", "c.txt"]
+        );
+        assert_eq!(names(Some(SortBy::Name), true), ["c.txt", "b.txt", "a.txt"]);
+        assert_eq!(
+            names(Some(SortBy::Size), false),
+            ["c.txt", "b.txt", "a.txt"]
+        );
+        assert_eq!(
+            names(Some(SortBy::Method), false),
+            ["b.txt", "a.txt", "c.txt"]
+        );
+        assert_eq!(
+            names(Some(SortBy::Offset), true),
+            ["a.txt", "c.txt", "b.txt"]
+        );
+    }
+
+    #[test]
+    fn repair_zeroed_central_directory() {
+        let mut writer = ZipWriter::new(Vec::new());
+        for (name, contents) in [("a.txt", "first\n"), ("dir/b.txt", "second\n")] {
+            writer.start_entry(name, &EntryOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+            writer.finish_entry().unwrap();
+        }
+        let mut bytes = writer.finish().unwrap();
+
+        // wipe everything from the central directory on
+        let central_directory = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[central_directory..].fill(0);
+        assert!(bytes.read_zip().is_err());
+
+        let dir = temp_dir::TempDir::new().unwrap();
+        let (broken, fixed) = (dir.path().join("broken.zip"), dir.path().join("fixed.zip"));
+        std::fs::write(&broken, &bytes).unwrap();
+        assert_eq!(repair(&broken, &fixed).unwrap(), 2);
+
+        let fixed = File::open(fixed).unwrap();
+        let archive = fixed.read_zip().unwrap();
+        let contents: Vec<_> = archive
+            .entries()
+            .map(|entry| (entry.name.clone(), entry.bytes().unwrap()))
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                ("a.txt".to_string(), b"first\n".to_vec()),
+                ("dir/b.txt".to_string(), b"second\n".to_vec()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use rc_zip::{
+    error::{Error, FormatError},
+    fsm::{ArchiveFsm, FsmResult},
+    parse::Entry,
+};
+use tracing::trace;
+
+use crate::HasCursor;
+use std::io::Read;
+
+/// Entries of a zip archive, parsed one at a time from its central directory,
+/// see [ReadZip::stream_central_directory](crate::ReadZip::stream_central_directory).
+///
+/// Only the entries that have been parsed but not handed out yet are kept in
+/// memory, so filtering the entries of a huge archive this way is much
+/// cheaper than reading all of them into an [Archive](rc_zip::parse::Archive).
+/// See [ArchiveFsm::with_streaming] for caveats.
+///
+/// Iteration stops after the first error.
+pub struct CentralDirectoryStream<'a, F>
+where
+    F: HasCursor,
+{
+    file: &'a F,
+    size: u64,
+    fsm: Option<ArchiveFsm>,
+    rest: std::vec::IntoIter<Entry>,
+    error: Option<Error>,
+}
+
+impl<'a, F> CentralDirectoryStream<'a, F>
+where
+    F: HasCursor,
+{
+    /// Drives `fsm`, which should have been created for `size` bytes, in
+    /// streaming mode.
+    pub(crate) fn new(file: &'a F, size: u64, fsm: ArchiveFsm) -> Self {
+        Self {
+            file,
+            size,
+            fsm: Some(fsm.with_streaming(true)),
+            rest: Vec::new().into_iter(),
+            error: None,
+        }
+    }
+
+    /// A stream that only yields `error`.
+    #[cfg(feature = "file")]
+    pub(crate) fn failed(file: &'a F, error: Error) -> Self {
+        Self {
+            file,
+            size: 0,
+            fsm: None,
+            rest: Vec::new().into_iter(),
+            error: Some(error),
+        }
+    }
+}
+
+impl<F> Iterator for CentralDirectoryStream<'_, F>
+where
+    F: HasCursor,
+{
+    type Item = Result<Entry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+
+        loop {
+            if let Some(entry) = self.rest.next() {
+                return Some(Ok(entry));
+            }
+
+            let mut fsm = self.fsm.take()?;
+            if let Some(entry) = fsm.next_entry() {
+                self.fsm = Some(fsm);
+                return Some(Ok(entry));
+            }
+
+            if let Some(offset) = fsm.wants_read() {
+                let read_bytes = match self.file.cursor_at(offset).read(fsm.space()) {
+                    Ok(read_bytes) => read_bytes,
+                    Err(err) => return Some(Err(Error::IO(err))),
+                };
+                trace!(%offset, %read_bytes, "stream_central_directory: read");
+                if read_bytes == 0 {
+                    return Some(Err(FormatError::TruncatedArchive {
+                        expected_at: offset,
+                        file_size: self.size,
+                    }
+                    .into()));
+                }
+                fsm.fill(read_bytes);
+            }
+
+            match fsm.process() {
+                Ok(FsmResult::Continue(fsm)) => self.fsm = Some(fsm),
+                Ok(FsmResult::Done(archive)) => self.rest = archive.into_entries().into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
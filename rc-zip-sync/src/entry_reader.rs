@@ -1,16 +1,26 @@
+use oval::Buffer;
 use rc_zip::{
-    fsm::{EntryFsm, FsmResult},
+    error::{Error, FormatError},
+    fsm::{BufferPool, DecompressLimits, EntryFsm, FsmResult, Validation},
     parse::Entry,
 };
-use std::io;
+use std::io::{self, Read};
 use tracing::trace;
 
+use crate::HasCursor;
+
 pub(crate) struct EntryReader<R>
 where
     R: io::Read,
 {
     rd: R,
     fsm: Option<EntryFsm>,
+    buffer: Option<Buffer>,
+    pool: Option<BufferPool>,
+    /// compressed bytes consumed so far, see [Self::progress]
+    compressed_bytes: u64,
+    /// uncompressed bytes produced so far, see [Self::progress]
+    uncompressed_bytes: u64,
 }
 
 impl<R> EntryReader<R>
@@ -18,11 +28,81 @@ where
     R: io::Read,
 {
     pub(crate) fn new(entry: &Entry, rd: R) -> Self {
+        Self::with_buffer(entry, rd, None)
+    }
+
+    /// Like [Self::new], but with an internal buffer of `capacity` bytes
+    /// (see [EntryFsm::with_capacity]).
+    pub(crate) fn with_capacity(entry: &Entry, rd: R, capacity: usize) -> Self {
+        Self {
+            rd,
+            fsm: Some(EntryFsm::with_capacity(Some(entry.clone()), None, capacity)),
+            buffer: None,
+            pool: None,
+            compressed_bytes: 0,
+            uncompressed_bytes: 0,
+        }
+    }
+
+    /// Like [Self::new], but reuses `buffer` (if any), which gets reset
+    /// first. Get it back with [Self::into_buffer] once done.
+    pub(crate) fn with_buffer(entry: &Entry, rd: R, buffer: Option<Buffer>) -> Self {
+        let buffer = buffer.map(|mut buffer| {
+            buffer.reset();
+            buffer
+        });
         Self {
             rd,
-            fsm: Some(EntryFsm::new(Some(entry.clone()), None)),
+            fsm: Some(EntryFsm::new(Some(entry.clone()), buffer)),
+            buffer: None,
+            pool: None,
+            compressed_bytes: 0,
+            uncompressed_bytes: 0,
         }
     }
+
+    /// Like [Self::new], but takes a buffer from `pool`, and gives it back
+    /// once the entry has been read to the end.
+    pub(crate) fn with_pool(entry: &Entry, rd: R, pool: BufferPool) -> Self {
+        Self {
+            rd,
+            fsm: Some(EntryFsm::new(Some(entry.clone()), Some(pool.get()))),
+            buffer: None,
+            pool: Some(pool),
+            compressed_bytes: 0,
+            uncompressed_bytes: 0,
+        }
+    }
+
+    /// Returns the buffer used by the state machine, if the entry has been
+    /// read to the end.
+    pub(crate) fn into_buffer(self) -> Option<Buffer> {
+        self.buffer
+    }
+
+    /// Returns how many compressed bytes have been fed to the decompressor,
+    /// and how many uncompressed bytes it has produced, so far.
+    pub(crate) fn progress(&self) -> (u64, u64) {
+        (self.compressed_bytes, self.uncompressed_bytes)
+    }
+
+    /// See [EntryFsm::with_validation]
+    pub(crate) fn with_validation(mut self, validation: Validation) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_validation(validation));
+        self
+    }
+
+    /// See [EntryFsm::with_strict_descriptors]
+    pub(crate) fn with_strict_descriptors(mut self, strict: bool) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_strict_descriptors(strict));
+        self
+    }
+
+    /// See [EntryFsm::with_limits]
+    pub(crate) fn with_limits(mut self, limits: DecompressLimits) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_limits(limits));
+        self
+    }
 }
 
 impl<R> io::Read for EntryReader<R>
@@ -51,6 +131,8 @@ where
             match fsm.process(buf)? {
                 FsmResult::Continue((fsm, outcome)) => {
                     self.fsm = Some(fsm);
+                    self.compressed_bytes += outcome.bytes_read as u64;
+                    self.uncompressed_bytes += outcome.bytes_written as u64;
 
                     if outcome.bytes_written > 0 {
                         tracing::trace!("wrote {} bytes", outcome.bytes_written);
@@ -65,11 +147,254 @@ where
                         ));
                     }
                 }
-                FsmResult::Done(_) => {
+                FsmResult::Done(buffer) => {
                     // neat!
+                    match self.pool.as_ref() {
+                        Some(pool) => pool.put(buffer),
+                        None => self.buffer = Some(buffer),
+                    }
                     return Ok(0);
                 }
             }
         }
     }
 }
+
+/// Hashes everything read through it, and hands the CRC-32 and number of
+/// bytes read to a callback once the inner reader reaches EOF.
+pub(crate) struct TeeReader<R, F> {
+    rd: R,
+    hasher: crc32fast::Hasher,
+    bytes_read: u64,
+    on_eof: Option<F>,
+}
+
+impl<R, F> TeeReader<R, F>
+where
+    R: io::Read,
+    F: FnOnce(u32, u64),
+{
+    pub(crate) fn new(rd: R, on_eof: F) -> Self {
+        Self {
+            rd,
+            hasher: crc32fast::Hasher::new(),
+            bytes_read: 0,
+            on_eof: Some(on_eof),
+        }
+    }
+}
+
+impl<R, F> io::Read for TeeReader<R, F>
+where
+    R: io::Read,
+    F: FnOnce(u32, u64),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rd.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            if let Some(on_eof) = self.on_eof.take() {
+                on_eof(self.hasher.clone().finalize(), self.bytes_read);
+            }
+        }
+        self.hasher.update(&buf[..n]);
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+/// Hands the progress of an [EntryReader] (see [EntryReader::progress]) to a
+/// callback after each read.
+pub(crate) struct ProgressReader<R, F>
+where
+    R: io::Read,
+{
+    rd: EntryReader<R>,
+    on_progress: F,
+}
+
+impl<R, F> ProgressReader<R, F>
+where
+    R: io::Read,
+    F: FnMut(u64, u64),
+{
+    pub(crate) fn new(rd: EntryReader<R>, on_progress: F) -> Self {
+        Self { rd, on_progress }
+    }
+}
+
+impl<R, F> io::Read for ProgressReader<R, F>
+where
+    R: io::Read,
+    F: FnMut(u64, u64),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rd.read(buf)?;
+        let (compressed, uncompressed) = self.rd.progress();
+        (self.on_progress)(compressed, uncompressed);
+        Ok(n)
+    }
+}
+
+/// Reads the data of a stored entry as-is, optionally checking its size and
+/// CRC-32 checksum once the end is reached.
+///
+/// Since the data is stored as-is, it can be seeked through: this just reads
+/// from a different offset in the file. Once it has, nothing is checked
+/// anymore.
+///
+/// Reads stop at the smaller of the entry's compressed and uncompressed
+/// sizes, so they never run into what follows its data.
+///
+/// [DecompressLimits::max_output_bytes] applies to positions in the data:
+/// reading past it fails, wherever the reading started.
+pub(crate) struct StoredReader<'a, F>
+where
+    F: HasCursor,
+{
+    file: &'a F,
+    data_offset: u64,
+    rd: io::Take<F::Cursor<'a>>,
+    hasher: Option<crc32fast::Hasher>,
+    pos: u64,
+    crc32: u32,
+    size: u64,
+    /// how much data there is to read, see [Self::new]
+    len: u64,
+    max_output_bytes: Option<u64>,
+}
+
+impl<'a, F> StoredReader<'a, F>
+where
+    F: HasCursor,
+{
+    /// `data_offset` is the offset of the entry's data in `file`.
+    pub(crate) fn new(
+        entry: &Entry,
+        file: &'a F,
+        data_offset: u64,
+        verify: bool,
+        limits: DecompressLimits,
+    ) -> Self {
+        let len = entry.compressed_size.min(entry.uncompressed_size);
+        Self {
+            file,
+            data_offset,
+            rd: file.cursor_at(data_offset).take(len),
+            hasher: verify.then(crc32fast::Hasher::new),
+            pos: 0,
+            crc32: entry.crc32,
+            size: entry.uncompressed_size,
+            len,
+            max_output_bytes: limits.max_output_bytes,
+        }
+    }
+}
+
+impl<F> io::Read for StoredReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.rd.read(buf)?;
+        self.pos += n as u64;
+
+        // stored data has a ratio of 1, only the output cap applies
+        if self.max_output_bytes.is_some_and(|max| self.pos > max) {
+            return Err(Error::LimitExceeded {
+                compressed_bytes: self.pos,
+                uncompressed_bytes: self.pos,
+            }
+            .into());
+        }
+
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..n]);
+            if n == 0 && !buf.is_empty() {
+                let hasher = self.hasher.take().unwrap();
+                if self.pos != self.size {
+                    return Err(Error::from(FormatError::WrongSize {
+                        expected: self.size,
+                        actual: self.pos,
+                    })
+                    .into());
+                }
+                let actual = hasher.finalize();
+                if self.crc32 != 0 && actual != self.crc32 {
+                    return Err(Error::from(FormatError::WrongChecksum {
+                        expected: self.crc32,
+                        actual,
+                    })
+                    .into());
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<F> io::Seek for StoredReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            io::SeekFrom::Start(offset) => Some(offset),
+            io::SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            io::SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+
+        if new_pos != self.pos {
+            // we won't see all the data in order anymore, so there's no
+            // checking it
+            self.hasher = None;
+            self.rd = self
+                .file
+                .cursor_at(self.data_offset.saturating_add(new_pos))
+                .take(self.len.saturating_sub(new_pos));
+            self.pos = new_pos;
+        }
+        Ok(new_pos)
+    }
+}
+
+/// Either a [StoredReader] or a regular [EntryReader].
+pub(crate) enum FastReader<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    Stored(StoredReader<'a, F>),
+    Fsm(Box<EntryReader<F::Cursor<'a>>>),
+}
+
+impl<F> io::Read for FastReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            FastReader::Stored(rd) => rd.read(buf),
+            FastReader::Fsm(rd) => rd.read(buf),
+        }
+    }
+}
+
+impl<F> io::Seek for FastReader<'_, F>
+where
+    F: HasCursor,
+{
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match self {
+            FastReader::Stored(rd) => rd.seek(pos),
+            FastReader::Fsm(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only stored entries can be seeked through",
+            )),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use oval::Buffer;
+use rc_zip::{
+    error::Error,
+    fsm::EntryFsm,
+    parse::{EntryKind, Mode},
+};
+use tracing::trace;
+
+use crate::{has_symlink_parent, ArchiveHandle, HasCursor, LinkTarget};
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+/// What to do when extracting an entry to a path that already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Leave the existing file alone, and count the entry as skipped in the
+    /// [ExtractReport].
+    Skip,
+
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+
+    /// Stop extracting and return an error.
+    Error,
+}
+
+/// Options for [ArchiveHandle::extract_all].
+#[derive(Debug, Clone)]
+pub struct ExtractOptions {
+    /// What to do with paths that already exist on disk.
+    pub overwrite: Overwrite,
+
+    /// Restore the Unix permissions of regular files from the archive.
+    /// When false, files get the process's default permissions.
+    ///
+    /// This has no effect on other platforms.
+    pub preserve_permissions: bool,
+
+    /// When preserving permissions, strip the setuid, setgid and sticky bits.
+    ///
+    /// Archives from untrusted sources can use these to escalate privileges,
+    /// so this defaults to true.
+    pub mask_setuid: bool,
+
+    /// Size of the buffer used to read entries, in bytes. A single buffer is
+    /// allocated for the whole extraction, and reused from one entry to the
+    /// next.
+    ///
+    /// Sizes smaller than [EntryFsm::MIN_BUFFER_CAPACITY] are rounded up.
+    pub buffer_size: usize,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            overwrite: Overwrite::default(),
+            preserve_permissions: false,
+            mask_setuid: true,
+            buffer_size: EntryFsm::DEFAULT_BUFFER_CAPACITY,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Returns the Unix permissions to give a file extracted from an entry
+    /// with the given mode, if any.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    fn unix_permissions(&self, mode: Mode) -> Option<u32> {
+        if !self.preserve_permissions {
+            return None;
+        }
+
+        let mut permissions = mode.0 & 0o777;
+        if permissions == 0 {
+            // no permissions recorded (or none worth restoring)
+            return None;
+        }
+
+        if !self.mask_setuid {
+            for (flag, bit) in [
+                (Mode::SETUID, 0o4000),
+                (Mode::SETGID, 0o2000),
+                (Mode::STICKY, 0o1000),
+            ] {
+                if mode.has(flag) {
+                    permissions |= bit;
+                }
+            }
+        }
+        Some(permissions)
+    }
+}
+
+/// A summary of what [ArchiveHandle::extract_all] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractReport {
+    /// Number of regular files written
+    pub files: usize,
+
+    /// Number of directories created
+    pub dirs: usize,
+
+    /// Number of symbolic links created
+    pub symlinks: usize,
+
+    /// Number of entries that were not extracted, either because their name
+    /// was unsafe, or because they already existed and [Overwrite::Skip] was
+    /// in effect.
+    pub skipped: usize,
+
+    /// Number of uncompressed bytes written
+    pub bytes: u64,
+}
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Extracts all entries of this archive under `dir`, creating parent
+    /// directories as needed.
+    ///
+    /// Entries with unsafe names (see [rc_zip::parse::Entry::sanitized_name]),
+    /// symlinks pointing outside of `dir`, and entries whose path goes
+    /// through a symlink (see [has_symlink_parent]) are skipped.
+    pub fn extract_all(
+        &self,
+        dir: impl AsRef<Path>,
+        options: &ExtractOptions,
+    ) -> Result<ExtractReport, Error> {
+        let dir = dir.as_ref();
+        let mut report = ExtractReport::default();
+        let mut buffer = Some(Buffer::with_capacity(
+            options.buffer_size.max(EntryFsm::MIN_BUFFER_CAPACITY),
+        ));
+
+        for entry in self.entries() {
+            let name = match entry.sanitized_name() {
+                Some(name) => name,
+                None => {
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+            let path = dir.join(&name);
+
+            // a symlink extracted earlier could send this entry anywhere
+            if has_symlink_parent(dir, &name) {
+                trace!(path = %path.display(), "extract_all: skipping entry under a symlink");
+                report.skipped += 1;
+                continue;
+            }
+
+            if let EntryKind::Directory = entry.kind() {
+                fs::create_dir_all(&path)?;
+                report.dirs += 1;
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            if let Ok(metadata) = fs::symlink_metadata(&path) {
+                match options.overwrite {
+                    Overwrite::Skip => {
+                        trace!(path = %path.display(), "extract_all: skipping existing path");
+                        report.skipped += 1;
+                        continue;
+                    }
+                    Overwrite::Error => {
+                        return Err(Error::IO(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            format!("{} already exists", path.display()),
+                        )));
+                    }
+                    Overwrite::Overwrite => {
+                        // don't write through existing symlinks, and make
+                        // room for new ones
+                        if metadata.is_symlink() || matches!(entry.kind(), EntryKind::Symlink) {
+                            fs::remove_file(&path)?;
+                        }
+                    }
+                }
+            }
+
+            match entry.kind() {
+                EntryKind::Symlink if cfg!(unix) => {
+                    let target = entry.read_symlink_target()?;
+
+                    // don't let symlinks point outside of the destination
+                    if target.is_escaping(&name) {
+                        report.skipped += 1;
+                        continue;
+                    }
+
+                    #[cfg(unix)]
+                    std::os::unix::fs::symlink(target, &path)?;
+                    report.symlinks += 1;
+                }
+                _ => {
+                    let mut writer = File::create(&path)?;
+                    let mut reader = entry.reader_with_buffer(buffer.take());
+                    report.bytes += io::copy(&mut reader, &mut writer)?;
+                    report.files += 1;
+                    buffer = reader.into_buffer();
+
+                    #[cfg(unix)]
+                    if let Some(permissions) = options.unix_permissions(entry.mode) {
+                        use std::os::unix::fs::PermissionsExt;
+                        writer.set_permissions(fs::Permissions::from_mode(permissions))?;
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
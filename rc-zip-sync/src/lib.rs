@@ -1,4 +1,4 @@
-//! A library for reading zip files synchronously using std I/O traits,
+//! A library for reading and writing zip files synchronously using std I/O traits,
 //! built on top of [rc-zip](https://crates.io/crates/rc-zip).
 //!
 //! See also:
@@ -10,11 +10,41 @@
 mod entry_reader;
 mod read_zip;
 
+mod extract;
+pub use extract::{ExtractOptions, ExtractReport, Overwrite};
+
+mod symlink;
+pub use symlink::{has_symlink_parent, read_symlink_target, LinkTarget, MAX_SYMLINK_TARGET_LEN};
+
+mod verify;
+pub use verify::{VerifyError, VerifyReport};
+
+mod owned_archive;
+pub use owned_archive::OwnedArchive;
+
 mod streaming_entry_reader;
 pub use streaming_entry_reader::StreamingEntryReader;
 
+mod central_directory_stream;
+pub use central_directory_stream::CentralDirectoryStream;
+
+mod zip_writer;
+pub use zip_writer::ZipWriter;
+
+mod split_reader;
+#[cfg(feature = "file")]
+pub use split_reader::read_split_zip;
+pub use split_reader::{SplitCursor, SplitReader};
+
+mod range_source;
+pub use range_source::{ByteRange, RangeCursor, RangeResponse, RangeSource};
+
+mod recover;
+pub use recover::recover_from_local_headers;
+
 // re-exports
 pub use rc_zip;
 pub use read_zip::{
-    ArchiveHandle, EntryHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    ArchiveHandle, EntryHandle, HasCursor, NestedZip, ReadZip, ReadZipOptions, ReadZipStreaming,
+    ReadZipWithSize, Subrange, MAX_NESTING_DEPTH,
 };
@@ -0,0 +1,105 @@
+use rc_zip::{error::Error, parse::Archive};
+
+use crate::{entry_reader::EntryReader, HasCursor, ReadZipWithSize};
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+/// A zip archive that owns the [Read] + [Seek] resource it was read from.
+///
+/// Unlike [ArchiveHandle](crate::ArchiveHandle), this doesn't borrow
+/// anything, and neither do the entry readers it hands out: they share the
+/// underlying reader, seeking it as needed. This is the easiest way to read
+/// zip files when lifetimes get in the way.
+pub struct OwnedArchive<R> {
+    file: SharedReader<R>,
+    archive: Archive,
+}
+
+impl<R> OwnedArchive<R>
+where
+    R: Read + Seek + 'static,
+{
+    /// Reads the archive's metadata from `reader`, taking ownership of it.
+    pub fn from_reader(mut reader: R) -> Result<Self, Error> {
+        let size = reader.seek(SeekFrom::End(0))?;
+        let file = SharedReader(Arc::new(Mutex::new(reader)));
+        let archive = file.read_zip_with_size(size)?.into_archive();
+        Ok(Self { file, archive })
+    }
+
+    /// Returns a reader for the entry at the given index (in central
+    /// directory order), if any.
+    pub fn reader_at(&self, index: usize) -> Option<impl Read + 'static> {
+        self.archive
+            .entries()
+            .nth(index)
+            .map(|entry| EntryReader::new(entry, self.file.cursor_at(entry.header_offset)))
+    }
+
+    /// Returns a reader for the first entry with the given name, if any.
+    /// See [Archive::by_name] for caveats.
+    pub fn reader_by_name<N: AsRef<str>>(&self, name: N) -> Option<impl Read + 'static> {
+        self.archive
+            .by_name(name)
+            .map(|entry| EntryReader::new(entry, self.file.cursor_at(entry.header_offset)))
+    }
+
+    /// Gives back the underlying reader, unless entry readers are still
+    /// using it.
+    pub fn into_inner(self) -> Option<R> {
+        let file = Arc::try_unwrap(self.file.0).ok()?;
+        Some(file.into_inner().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl<R> Deref for OwnedArchive<R> {
+    type Target = Archive;
+
+    fn deref(&self) -> &Self::Target {
+        &self.archive
+    }
+}
+
+/// A [Read] + [Seek] resource shared between all the readers of an
+/// [OwnedArchive].
+struct SharedReader<R>(Arc<Mutex<R>>);
+
+impl<R> HasCursor for SharedReader<R>
+where
+    R: Read + Seek + 'static,
+{
+    type Cursor<'a> = SharedCursor<R>;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        SharedCursor {
+            file: Arc::clone(&self.0),
+            offset,
+        }
+    }
+}
+
+/// Reads a [SharedReader] from a given offset: since other cursors may have
+/// moved it in the meantime, it seeks before every read.
+struct SharedCursor<R> {
+    file: Arc<Mutex<R>>,
+    offset: u64,
+}
+
+impl<R> Read for SharedCursor<R>
+where
+    R: Read + Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "shared reader is poisoned"))?;
+        file.seek(SeekFrom::Start(self.offset))?;
+        let n = file.read(buf)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
@@ -0,0 +1,215 @@
+use rc_zip::{error::Error, fsm::ArchiveFsm};
+use std::{
+    io::{self, Read},
+    ops::Range,
+};
+
+use crate::{
+    read_zip::read_zip_with_fsm, ArchiveHandle, CentralDirectoryStream, HasCursor, ReadZip,
+    ReadZipOptions,
+};
+
+/// A range of bytes requested by a [RangeSource], like the `Range` header of
+/// an HTTP request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The last `n` bytes of the resource (`Range: bytes=-n`), or all of it
+    /// if it's smaller than that
+    Suffix(u64),
+
+    /// The bytes in `start..end` (`Range: bytes=start-{end - 1}`)
+    Bounded(Range<u64>),
+}
+
+/// The answer to a [ByteRange] request.
+#[derive(Debug, Clone)]
+pub struct RangeResponse {
+    /// The bytes requested. There may be fewer than asked for, but not
+    /// none, unless the range starts past the end of the resource. The
+    /// whole resource is accepted too, for servers that ignore ranges:
+    /// anything else that's longer than the range is an error.
+    pub data: Vec<u8>,
+
+    /// Size of the whole resource, in bytes: for HTTP, that's what follows
+    /// the `/` in the `Content-Range` header of the response.
+    pub total_size: u64,
+}
+
+/// A remote resource read with range requests (like HTTP `Range` requests),
+/// whose size doesn't need to be known up front.
+///
+/// The first request asks for the last [Self::TAIL_SIZE] bytes of the
+/// resource, which tells its size, holds the end of central directory
+/// record, and often the whole central directory. From then on, only the
+/// ranges that are actually read are fetched, in chunks of at least
+/// [Self::MIN_FETCH_SIZE] bytes: reading one entry out of a huge archive
+/// takes a handful of requests.
+///
+/// ```no_run
+/// use rc_zip_sync::{ByteRange, RangeResponse, RangeSource, ReadZip};
+/// # fn http_get(range: ByteRange) -> std::io::Result<RangeResponse> { unimplemented!() }
+///
+/// let source = RangeSource::new(|range| http_get(range))?;
+/// let archive = source.read_zip()?;
+/// let entry = archive.by_name("README.md").unwrap();
+/// println!("{}", String::from_utf8_lossy(&entry.bytes()?));
+/// # Ok::<_, rc_zip_sync::rc_zip::error::Error>(())
+/// ```
+pub struct RangeSource<F> {
+    fetch: F,
+    size: u64,
+    /// the end of the resource, fetched up front
+    tail: Vec<u8>,
+    /// where `tail` starts
+    tail_offset: u64,
+}
+
+impl<F> RangeSource<F>
+where
+    F: Fn(ByteRange) -> io::Result<RangeResponse>,
+{
+    /// How many bytes from the end of the resource are fetched by
+    /// [Self::new]: enough for [ArchiveFsm] to find the end of central
+    /// directory record, and for the central directory of most archives.
+    pub const TAIL_SIZE: u64 = ArchiveFsm::DEFAULT_BUFFER_CAPACITY as u64;
+
+    /// The smallest number of bytes fetched by a single request, past the
+    /// tail.
+    pub const MIN_FETCH_SIZE: u64 = 64 * 1024;
+
+    /// Creates a source that fetches ranges with `fetch`, and fetches the
+    /// end of the resource right away to find out its size.
+    pub fn new(fetch: F) -> Result<Self, Error> {
+        let RangeResponse {
+            mut data,
+            total_size,
+        } = fetch(ByteRange::Suffix(Self::TAIL_SIZE))?;
+        if data.len() as u64 > total_size {
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "got {} bytes for a resource of {} bytes",
+                    data.len(),
+                    total_size
+                ),
+            )));
+        }
+        // servers that ignore range requests send the whole resource: only
+        // keep its end
+        if data.len() as u64 > Self::TAIL_SIZE {
+            data.drain(..data.len() - Self::TAIL_SIZE as usize);
+        }
+
+        Ok(Self {
+            fetch,
+            size: total_size,
+            tail_offset: total_size - data.len() as u64,
+            tail: data,
+        })
+    }
+
+    /// Size of the resource, in bytes, as reported by the first response
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<F> HasCursor for RangeSource<F>
+where
+    F: Fn(ByteRange) -> io::Result<RangeResponse>,
+{
+    type Cursor<'a> = RangeCursor<'a, F>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        RangeCursor {
+            source: self,
+            offset,
+            chunk: Vec::new(),
+            chunk_pos: 0,
+        }
+    }
+}
+
+impl<F> ReadZip for RangeSource<F>
+where
+    F: Fn(ByteRange) -> io::Result<RangeResponse>,
+{
+    type File = Self;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let options = ReadZipOptions::default();
+        let fsm = ArchiveFsm::with_capacity(self.size, options.buffer_size);
+        read_zip_with_fsm(self, self.size, fsm, &options)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        CentralDirectoryStream::new(self, self.size, ArchiveFsm::new(self.size))
+    }
+}
+
+/// A [Read] at a given offset of a [RangeSource], which fetches the data it
+/// doesn't already have in chunks.
+pub struct RangeCursor<'a, F> {
+    source: &'a RangeSource<F>,
+    /// offset in the resource of the next byte to read
+    offset: u64,
+    /// the last chunk fetched
+    chunk: Vec<u8>,
+    /// how much of `chunk` has been read
+    chunk_pos: usize,
+}
+
+impl<F> Read for RangeCursor<'_, F>
+where
+    F: Fn(ByteRange) -> io::Result<RangeResponse>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let source = self.source;
+        if buf.is_empty() || self.offset >= source.size {
+            return Ok(0);
+        }
+
+        let available = if self.offset >= source.tail_offset {
+            &source.tail[(self.offset - source.tail_offset) as usize..]
+        } else {
+            if self.chunk_pos == self.chunk.len() {
+                // never fetch what's already in the tail
+                let len = (buf.len() as u64).max(RangeSource::<F>::MIN_FETCH_SIZE);
+                let end = (self.offset + len).min(source.tail_offset);
+                let RangeResponse { mut data, .. } =
+                    (source.fetch)(ByteRange::Bounded(self.offset..end))?;
+                if data.is_empty() {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                if data.len() as u64 == source.size {
+                    // servers that ignore range requests send the whole
+                    // resource: only keep the range
+                    data.truncate(end as usize);
+                    data.drain(..self.offset as usize);
+                } else if data.len() as u64 > end - self.offset {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "got {} bytes for a range of {} bytes",
+                            data.len(),
+                            end - self.offset
+                        ),
+                    ));
+                }
+                self.chunk = data;
+                self.chunk_pos = 0;
+            }
+            &self.chunk[self.chunk_pos..]
+        };
+
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        if self.offset < source.tail_offset {
+            self.chunk_pos += n;
+        }
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
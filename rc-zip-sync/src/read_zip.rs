@@ -1,14 +1,30 @@
+use oval::Buffer;
 use rc_zip::{
-    error::Error,
-    fsm::{ArchiveFsm, FsmResult},
-    parse::Archive,
+    encoding::Encoding,
+    error::{Error, FormatError, UnsupportedError},
+    fsm::{
+        ArchiveFsm, ArchiveLimits, BufferPool, DecompressLimits, FsmResult, RecoveryMode,
+        Validation,
+    },
+    parse::{
+        Archive, EndOfCentralDirectory64Locator, Method, RawArchive, RawRecord, RawRecordKind,
+        SortKey,
+    },
 };
 use rc_zip::{fsm::EntryFsm, parse::Entry};
 use tracing::trace;
 
-use crate::entry_reader::EntryReader;
+use crate::central_directory_stream::CentralDirectoryStream;
+use crate::entry_reader::{EntryReader, FastReader, ProgressReader, StoredReader, TeeReader};
 use crate::streaming_entry_reader::StreamingEntryReader;
-use std::{io::Read, ops::Deref};
+use crate::symlink::read_symlink_target;
+use crate::verify::into_zip_error;
+use std::{
+    io::{self, BufRead, BufReader, Read, Seek},
+    ops::{Deref, Range},
+    path::PathBuf,
+    sync::Arc,
+};
 
 /// A trait for reading something as a zip archive
 ///
@@ -19,6 +35,90 @@ pub trait ReadZipWithSize {
 
     /// Reads self as a zip archive.
     fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive, enforcing the given limits while reading
+    /// its metadata.
+    ///
+    /// Use this to bound how much an untrusted archive can make us read
+    /// (see [ArchiveLimits::max_bytes_read] and [ArchiveLimits::deadline]):
+    /// going over budget fails with [Error::Budget].
+    fn read_zip_with_limits(
+        &self,
+        size: u64,
+        limits: ArchiveLimits,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive, with the given options, which also
+    /// apply to the readers of its entries.
+    fn read_zip_with_options(
+        &self,
+        size: u64,
+        options: ReadZipOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive, reading its central directory in memory
+    /// as a whole and parsing its headers in parallel, see
+    /// [ArchiveFsm::with_parallel].
+    ///
+    /// This is much faster for archives with lots of entries.
+    #[cfg(feature = "rayon")]
+    fn read_zip_parallel(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads the `len` bytes of self starting at `offset` as a zip archive,
+    /// for archives embedded in a larger container at a known position.
+    ///
+    /// Nothing outside of that window is looked at, so data before the
+    /// archive can't be mistaken for part of it. Like with [Subrange],
+    /// offsets in the archive (like [Entry::header_offset]) are relative to
+    /// `offset`.
+    fn read_zip_at(&self, offset: u64, len: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Parses the entries of self as a zip archive one at a time, see
+    /// [ReadZip::stream_central_directory].
+    fn stream_central_directory_with_size(
+        &self,
+        size: u64,
+    ) -> CentralDirectoryStream<'_, Self::File>;
+}
+
+/// Options for [ReadZipWithSize::read_zip_with_options].
+#[derive(Debug, Clone, Copy)]
+pub struct ReadZipOptions {
+    /// Limits enforced while reading the archive's metadata, see
+    /// [ReadZipWithSize::read_zip_with_limits].
+    pub limits: ArchiveLimits,
+
+    /// Size of the buffer used to read the archive's metadata, and of the
+    /// buffer each entry reader allocates, in bytes.
+    ///
+    /// Lowering this makes it cheaper to have many entry readers open at
+    /// once. It is rounded up to [ArchiveFsm::MIN_BUFFER_CAPACITY] for the
+    /// archive, and to [EntryFsm::MIN_BUFFER_CAPACITY] for entries.
+    pub buffer_size: usize,
+
+    /// Encoding of the entry names and comments, detected when `None` (the
+    /// default). See [ArchiveFsm::with_encoding].
+    pub encoding: Option<Encoding>,
+
+    /// Limits enforced by entry readers while decompressing, see
+    /// [DecompressLimits]. Off by default.
+    pub decompress_limits: DecompressLimits,
+
+    /// What to do with a damaged central directory, see
+    /// [ArchiveFsm::with_recovery]. Strict by default.
+    pub recovery: RecoveryMode,
+}
+
+impl Default for ReadZipOptions {
+    fn default() -> Self {
+        Self {
+            limits: Default::default(),
+            buffer_size: EntryFsm::DEFAULT_BUFFER_CAPACITY,
+            encoding: None,
+            decompress_limits: Default::default(),
+            recovery: Default::default(),
+        }
+    }
 }
 
 /// A trait for reading something as a zip archive when we can tell size from
@@ -31,6 +131,28 @@ pub trait ReadZip {
 
     /// Reads self as a zip archive.
     fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Parses the entries of self as a zip archive one at a time, as they
+    /// come in the central directory, instead of reading them all up front.
+    ///
+    /// Use this to pick a few entries out of a huge archive: only the ones
+    /// that are kept take up memory.
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File>;
+
+    /// Reads self as a zip archive, whose entry readers take their buffers
+    /// from `pool`, and give them back once they've read their entry to the
+    /// end.
+    ///
+    /// This keeps memory usage down when reading many entries, in sequence
+    /// or in parallel.
+    fn read_zip_with_pool(
+        &self,
+        pool: &BufferPool,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let mut archive = self.read_zip()?;
+        archive.pool = Some(pool.clone());
+        Ok(archive)
+    }
 }
 
 impl<F> ReadZipWithSize for F
@@ -40,60 +162,133 @@ where
     type File = F;
 
     fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
-        struct CursorState<'a, F: HasCursor + 'a> {
-            cursor: <F as HasCursor>::Cursor<'a>,
-            offset: u64,
+        self.read_zip_with_limits(size, Default::default())
+    }
+
+    fn read_zip_with_limits(
+        &self,
+        size: u64,
+        limits: ArchiveLimits,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
+        self.read_zip_with_options(
+            size,
+            ReadZipOptions {
+                limits,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn read_zip_with_options(
+        &self,
+        size: u64,
+        options: ReadZipOptions,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
+        let mut fsm = ArchiveFsm::with_capacity(size, options.buffer_size)
+            .with_limits(options.limits)
+            .with_recovery(options.recovery);
+        if let Some(encoding) = options.encoding {
+            fsm = fsm.with_encoding(encoding);
         }
-        let mut cstate: Option<CursorState<'_, F>> = None;
+        read_zip_with_fsm(self, size, fsm, &options)
+    }
 
-        let mut fsm = ArchiveFsm::new(size);
-        loop {
-            if let Some(offset) = fsm.wants_read() {
-                trace!(%offset, "read_zip_with_size: wants_read, space len = {}", fsm.space().len());
-
-                let mut cstate_next = match cstate.take() {
-                    Some(cstate) => {
-                        if cstate.offset == offset {
-                            // all good, re-using
-                            cstate
-                        } else {
-                            CursorState {
-                                cursor: self.cursor_at(offset),
-                                offset,
-                            }
+    #[cfg(feature = "rayon")]
+    fn read_zip_parallel(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+        let options = ReadZipOptions::default();
+        let fsm = ArchiveFsm::with_capacity(size, options.buffer_size).with_parallel(true);
+        read_zip_with_fsm(self, size, fsm, &options)
+    }
+
+    fn read_zip_at(&self, offset: u64, len: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+        let archive = Subrange::new(self, offset, len).read_zip()?.into_archive();
+        Ok(ArchiveHandle {
+            file: self,
+            offset,
+            archive,
+            depth: 0,
+            buffer_size: EntryFsm::DEFAULT_BUFFER_CAPACITY,
+            decompress_limits: Default::default(),
+            pool: None,
+        })
+    }
+
+    fn stream_central_directory_with_size(&self, size: u64) -> CentralDirectoryStream<'_, F> {
+        CentralDirectoryStream::new(self, size, ArchiveFsm::new(size))
+    }
+}
+
+/// Drives `fsm` to completion, reading from `file`.
+pub(crate) fn read_zip_with_fsm<'a, F>(
+    file: &'a F,
+    size: u64,
+    mut fsm: ArchiveFsm,
+    options: &ReadZipOptions,
+) -> Result<ArchiveHandle<'a, F>, Error>
+where
+    F: HasCursor,
+{
+    struct CursorState<'a, F: HasCursor + 'a> {
+        cursor: <F as HasCursor>::Cursor<'a>,
+        offset: u64,
+    }
+    let mut cstate: Option<CursorState<'_, F>> = None;
+
+    loop {
+        if let Some(offset) = fsm.wants_read() {
+            trace!(%offset, "read_zip_with_size: wants_read, space len = {}", fsm.space().len());
+
+            let mut cstate_next = match cstate.take() {
+                Some(cstate) => {
+                    if cstate.offset == offset {
+                        // all good, re-using
+                        cstate
+                    } else {
+                        CursorState {
+                            cursor: file.cursor_at(offset),
+                            offset,
                         }
                     }
-                    None => CursorState {
-                        cursor: self.cursor_at(offset),
-                        offset,
-                    },
-                };
-
-                match cstate_next.cursor.read(fsm.space()) {
-                    Ok(read_bytes) => {
-                        cstate_next.offset += read_bytes as u64;
-                        cstate = Some(cstate_next);
-
-                        trace!(%read_bytes, "read_zip_with_size: read");
-                        if read_bytes == 0 {
-                            return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+                None => CursorState {
+                    cursor: file.cursor_at(offset),
+                    offset,
+                },
+            };
+
+            match cstate_next.cursor.read(fsm.space()) {
+                Ok(read_bytes) => {
+                    cstate_next.offset += read_bytes as u64;
+                    cstate = Some(cstate_next);
+
+                    trace!(%read_bytes, "read_zip_with_size: read");
+                    if read_bytes == 0 {
+                        return Err(FormatError::TruncatedArchive {
+                            expected_at: offset,
+                            file_size: size,
                         }
-                        fsm.fill(read_bytes);
+                        .into());
                     }
-                    Err(err) => return Err(Error::IO(err)),
+                    fsm.fill(read_bytes);
                 }
+                Err(err) => return Err(Error::IO(err)),
             }
+        }
 
-            fsm = match fsm.process()? {
-                FsmResult::Done(archive) => {
-                    trace!("read_zip_with_size: done");
-                    return Ok(ArchiveHandle {
-                        file: self,
-                        archive,
-                    });
-                }
-                FsmResult::Continue(fsm) => fsm,
+        fsm = match fsm.process()? {
+            FsmResult::Done(archive) => {
+                trace!("read_zip_with_size: done");
+                return Ok(ArchiveHandle {
+                    file,
+                    offset: 0,
+                    archive,
+                    depth: 0,
+                    buffer_size: options.buffer_size,
+                    decompress_limits: options.decompress_limits,
+                    pool: None,
+                });
             }
+            FsmResult::Continue(fsm) => fsm,
         }
     }
 }
@@ -104,6 +299,10 @@ impl ReadZip for &[u8] {
     fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
         self.read_zip_with_size(self.len() as u64)
     }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.stream_central_directory_with_size(self.len() as u64)
+    }
 }
 
 impl ReadZip for Vec<u8> {
@@ -112,6 +311,47 @@ impl ReadZip for Vec<u8> {
     fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
         self.read_zip_with_size(self.len() as u64)
     }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.stream_central_directory_with_size(self.len() as u64)
+    }
+}
+
+impl ReadZip for Arc<[u8]> {
+    type File = Self;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.len() as u64)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.stream_central_directory_with_size(self.len() as u64)
+    }
+}
+
+impl ReadZip for io::Cursor<Vec<u8>> {
+    type File = Self;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.get_ref().len() as u64)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.stream_central_directory_with_size(self.get_ref().len() as u64)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ReadZip for bytes::Bytes {
+    type File = Self;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.len() as u64)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.stream_central_directory_with_size(self.len() as u64)
+    }
 }
 
 /// A zip archive, read synchronously from a file or other I/O resource.
@@ -124,7 +364,13 @@ where
     F: HasCursor,
 {
     file: &'a F,
+    /// where the archive starts in `file`, see [ReadZipWithSize::read_zip_at]
+    offset: u64,
     archive: Archive,
+    depth: usize,
+    buffer_size: usize,
+    decompress_limits: DecompressLimits,
+    pool: Option<BufferPool>,
 }
 
 impl<F> Deref for ArchiveHandle<'_, F>
@@ -138,35 +384,182 @@ where
     }
 }
 
-impl<F> ArchiveHandle<'_, F>
+impl<'a, F> ArchiveHandle<'a, F>
 where
     F: HasCursor,
 {
-    /// Iterate over all files in this zip, read from the central directory.
-    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
-        self.archive.entries().map(move |entry| EntryHandle {
+    /// Wraps an archive read (or reconstructed) from `file` some other way
+    /// than with [read_zip_with_fsm].
+    pub(crate) fn new(file: &'a F, archive: Archive, options: &ReadZipOptions) -> Self {
+        Self {
+            file,
+            offset: 0,
+            archive,
+            depth: 0,
+            buffer_size: options.buffer_size,
+            decompress_limits: options.decompress_limits,
+            pool: None,
+        }
+    }
+
+    /// Gives up access to the file, keeping only the archive's metadata.
+    pub(crate) fn into_archive(self) -> Archive {
+        self.archive
+    }
+
+    /// Gives access to the data of `entry`, one of this archive's entries.
+    fn handle<'h>(&'h self, entry: &'h Entry) -> EntryHandle<'h, F> {
+        EntryHandle {
             file: self.file,
+            offset: self.offset,
+            archive_size: self.archive.size(),
+            encoding: self.archive.encoding(),
             entry,
-        })
+            depth: self.depth,
+            buffer_size: self.buffer_size,
+            decompress_limits: self.decompress_limits,
+            pool: self.pool.as_ref(),
+        }
+    }
+
+    /// Iterate over all files in this zip, read from the central directory.
+    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
+        self.archive.entries().map(move |entry| self.handle(entry))
+    }
+
+    /// Returns all entries, ordered by `key`, see [Archive::entries_sorted_by].
+    pub fn entries_sorted_by(&self, key: SortKey) -> Vec<EntryHandle<'_, F>> {
+        self.archive
+            .entries_sorted_by(key)
+            .into_iter()
+            .map(|entry| self.handle(entry))
+            .collect()
     }
 
     /// Attempts to look up an entry by name. This is usually a bad idea,
     /// as names aren't necessarily normalized in zip archives.
+    ///
+    /// See [Archive::by_name] for how duplicate names are handled.
     pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| self.handle(entry))
+    }
+
+    /// Returns the first entry (in central directory order) for which
+    /// `predicate` returns true, if any.
+    pub fn find_first<P>(&self, mut predicate: P) -> Option<EntryHandle<'_, F>>
+    where
+        P: FnMut(&Entry) -> bool,
+    {
         self.archive
-            .entries()
-            .find(|&x| x.name == name.as_ref())
-            .map(|entry| EntryHandle {
-                file: self.file,
-                entry,
-            })
+            .find_first(|entry| predicate(entry))
+            .map(|entry| self.handle(entry))
+    }
+
+    /// Returns a reader for the entry at the given index (in central
+    /// directory order), if any.
+    pub fn reader_at(&self, index: usize) -> Option<impl Read + '_> {
+        self.entries().nth(index).map(|entry| entry.entry_reader())
+    }
+
+    /// Returns a reader for the first entry with the given name, if any.
+    /// See [Self::by_name] for caveats.
+    pub fn reader_by_name<N: AsRef<str>>(&self, name: N) -> Option<impl Read + '_> {
+        self.by_name(name).map(|entry| entry.entry_reader())
+    }
+
+    /// Walks the whole file and returns every structural record (local file
+    /// headers, file data, data descriptors, central directory headers, and
+    /// the various end of central directory records) in physical order,
+    /// without any normalization.
+    ///
+    /// This is meant for forensic or repacking tools, most users want
+    /// [Self::entries] instead.
+    pub fn raw_archive(&self) -> Result<RawArchive, Error> {
+        let cursor_at = |offset: u64| self.file.cursor_at(self.offset + offset);
+        let mut records = Vec::new();
+
+        for (entry_index, entry) in self.archive.entries().enumerate() {
+            let offset = entry.header_offset;
+            let header = read_record(cursor_at(offset), |data| {
+                RawRecord::local_file_header(offset, data)
+            })?;
+            let (has_data_descriptor, is_zip64) = match &header.kind {
+                RawRecordKind::LocalFileHeader(h) => (
+                    h.has_data_descriptor(),
+                    h.compressed_size == u32::MAX || h.uncompressed_size == u32::MAX,
+                ),
+                _ => unreachable!(),
+            };
+
+            let data_offset = header.offset + header.length;
+            records.push(header);
+            records.push(RawRecord {
+                offset: data_offset,
+                length: entry.compressed_size,
+                kind: RawRecordKind::FileData { entry_index },
+            });
+
+            if has_data_descriptor {
+                let offset = data_offset + entry.compressed_size;
+                records.push(read_record(cursor_at(offset), |data| {
+                    RawRecord::data_descriptor(offset, data, is_zip64)
+                })?);
+            }
+        }
+
+        let eocd = self.archive.eocd();
+        let mut directory = Vec::new();
+        cursor_at(eocd.directory_offset())
+            .take(eocd.directory_size())
+            .read_to_end(&mut directory)?;
+        if directory.len() as u64 != eocd.directory_size() {
+            return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        records.extend(RawRecord::central_directory(
+            eocd.directory_offset(),
+            &directory,
+            eocd.directory_records(),
+        )?);
+
+        // some of these may be missing from the archive, see
+        // EndOfCentralDirectory::dir_found
+        if let Some(dir64) = eocd.dir64.as_ref() {
+            records.push(read_record(cursor_at(dir64.offset), |data| {
+                RawRecord::eocd64(dir64.offset, data)
+            })?);
+
+            if eocd.locator_found {
+                let offset = eocd.dir.offset - EndOfCentralDirectory64Locator::LENGTH as u64;
+                records.push(read_record(cursor_at(offset), |data| {
+                    RawRecord::eocd64_locator(offset, data)
+                })?);
+            }
+        }
+
+        if eocd.dir_found {
+            let offset = eocd.dir.offset;
+            records.push(read_record(cursor_at(offset), |data| {
+                RawRecord::eocd(offset, data)
+            })?);
+        }
+
+        Ok(RawArchive::new(records))
     }
 }
 
 /// A zip entry, read synchronously from a file or other I/O resource.
 pub struct EntryHandle<'a, F> {
     file: &'a F,
+    /// where the archive starts in `file`
+    offset: u64,
+    archive_size: u64,
+    /// the archive's encoding, see [Self::read_symlink_target]
+    encoding: Encoding,
     entry: &'a Entry,
+    depth: usize,
+    buffer_size: usize,
+    decompress_limits: DecompressLimits,
+    pool: Option<&'a BufferPool>,
 }
 
 impl<F> Deref for EntryHandle<'_, F> {
@@ -183,7 +576,177 @@ where
 {
     /// Returns a reader for the entry.
     pub fn reader(&self) -> impl Read + 'a {
-        EntryReader::new(self.entry, self.file.cursor_at(self.entry.header_offset))
+        self.entry_reader()
+    }
+
+    /// Returns a cursor at `offset` in the archive.
+    fn cursor_at(&self, offset: u64) -> F::Cursor<'a> {
+        self.file.cursor_at(self.offset + offset)
+    }
+
+    pub(crate) fn entry_reader(&self) -> EntryReader<F::Cursor<'a>> {
+        let rd = self.cursor_at(self.entry.header_offset);
+        let reader = match self.pool {
+            Some(pool) => EntryReader::with_pool(self.entry, rd, pool.clone()),
+            None => EntryReader::with_capacity(self.entry, rd, self.buffer_size),
+        };
+        reader.with_limits(self.decompress_limits)
+    }
+
+    /// Returns a reader for the entry that reuses `buffer`, if any: get it
+    /// back with [EntryReader::into_buffer] once the entry has been read.
+    pub(crate) fn reader_with_buffer(&self, buffer: Option<Buffer>) -> EntryReader<F::Cursor<'a>> {
+        EntryReader::with_buffer(self.entry, self.cursor_at(self.entry.header_offset), buffer)
+            .with_limits(self.decompress_limits)
+    }
+
+    /// Returns a reader for the entry that doesn't check its size and CRC-32
+    /// checksum, and instead passes the CRC-32 and size of what was read to
+    /// `on_eof` once the end of the entry is reached.
+    ///
+    /// This is for callers that would rather report a mismatch than fail.
+    /// Other errors (like corrupted compressed data) are still returned.
+    pub fn tee_reader<C>(&self, on_eof: C) -> impl Read + 'a
+    where
+        C: FnOnce(u32, u64) + 'a,
+    {
+        let rd = self.entry_reader().with_validation(Validation::Skip);
+        TeeReader::new(rd, on_eof)
+    }
+
+    /// Returns a reader for the entry that calls `on_progress` after each
+    /// read, with the number of compressed bytes consumed and uncompressed
+    /// bytes produced so far, in that order.
+    ///
+    /// This lets extraction UIs show both rates without wrapping the reader.
+    /// Regular readers keep count too, at no extra cost, but don't report it.
+    pub fn reader_with_progress<C>(&self, on_progress: C) -> impl Read + 'a
+    where
+        C: FnMut(u64, u64) + 'a,
+    {
+        ProgressReader::new(self.entry_reader(), on_progress)
+    }
+
+    /// Returns a reader for the entry that, for stored (uncompressed)
+    /// entries, reads their data straight from the file instead of going
+    /// through [EntryFsm]. Other entries get a regular [Self::reader].
+    ///
+    /// When `verify` is false, the size and CRC-32 checksum of the entry
+    /// aren't checked (for any method), which saves hashing the data.
+    ///
+    /// The reader can seek through stored entries, which gives random access
+    /// to their data. Seeking fails with [io::ErrorKind::Unsupported] for
+    /// other entries.
+    ///
+    /// **Seeking turns `verify` off**: once the reader of a stored entry has
+    /// been seeked to another position, the entry's size and checksum are
+    /// never checked, even if it's then read to the end.
+    /// With [DecompressLimits::max_output_bytes] set, reading a stored entry
+    /// past that offset fails.
+    ///
+    /// Stored entries whose data can't be read as is fail like with
+    /// [Self::read_range].
+    pub fn fast_reader(&self, verify: bool) -> Result<impl Read + Seek + 'a, Error> {
+        if self.entry.method != Method::Store {
+            let validation = if verify {
+                Validation::OnFullRead
+            } else {
+                Validation::Skip
+            };
+            let rd = self.entry_reader().with_validation(validation);
+            return Ok(FastReader::Fsm(Box::new(rd)));
+        }
+
+        self.check_stored_data()?;
+        Ok(FastReader::Stored(StoredReader::new(
+            self.entry,
+            self.file,
+            self.data_offset()?,
+            verify,
+            self.decompress_limits,
+        )))
+    }
+
+    /// Returns a reader for the given range of a stored entry's data, read
+    /// straight from the file: there's no need to read what comes before.
+    ///
+    /// Fails with [UnsupportedError::RandomAccessUnsupported] for compressed
+    /// entries, with [UnsupportedError::EncryptedEntry] for encrypted ones,
+    /// with [FormatError::WrongSize] if the entry's compressed and
+    /// uncompressed sizes differ, and with [io::ErrorKind::InvalidInput] if
+    /// the range doesn't fit in the entry. The data read this way isn't
+    /// checked against the entry's CRC-32 checksum.
+    pub fn read_range(&self, range: Range<u64>) -> Result<impl Read + 'a, Error> {
+        if self.entry.method != Method::Store {
+            return Err(UnsupportedError::RandomAccessUnsupported(self.entry.method).into());
+        }
+        self.check_stored_data()?;
+        if range.start > range.end || range.end > self.entry.uncompressed_size {
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "range {}..{} is out of bounds for an entry of {} bytes",
+                    range.start, range.end, self.entry.uncompressed_size
+                ),
+            )));
+        }
+
+        Ok(self
+            .file
+            .cursor_at(self.data_offset()? + range.start)
+            .take(range.end - range.start))
+    }
+
+    /// Checks that the data of a stored entry can be read as is, straight
+    /// from the file: nothing would catch it running into the next records,
+    /// or being encrypted.
+    fn check_stored_data(&self) -> Result<(), Error> {
+        if self.entry.is_encrypted() {
+            return Err(UnsupportedError::EncryptedEntry.into());
+        }
+        if self.entry.compressed_size != self.entry.uncompressed_size {
+            return Err(FormatError::WrongSize {
+                expected: self.entry.uncompressed_size,
+                actual: self.entry.compressed_size,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Returns a reader for the entry's data exactly as stored in the
+    /// archive, without decompressing it: it's read straight from the file,
+    /// right after the local header, and ends after
+    /// [compressed_size](Entry::compressed_size) bytes, before the data
+    /// descriptor (if any).
+    ///
+    /// Nothing is checked: this is meant for copying entries to another
+    /// archive (see [ZipWriter::copy_raw](crate::ZipWriter::copy_raw)),
+    /// decompressing them elsewhere, or inspecting them.
+    pub fn raw_reader(&self) -> Result<impl Read + 'a, Error> {
+        Ok(self
+            .file
+            .cursor_at(self.data_offset()?)
+            .take(self.entry.compressed_size))
+    }
+
+    /// Returns the offset of the first byte of the entry's (compressed)
+    /// data in the file, right after its local header, which is read to
+    /// find out. See [Entry::data_offset].
+    ///
+    /// Fails with [FormatError::DataOutOfBounds] if the data doesn't fit in
+    /// the archive.
+    pub fn data_offset(&self) -> Result<u64, Error> {
+        let offset = read_record(self.cursor_at(self.entry.header_offset), |data| {
+            self.entry.data_offset(data, self.archive_size)
+        })?;
+        Ok(self.offset + offset)
+    }
+
+    /// Returns a buffered reader for the entry, which is handy for
+    /// line-oriented text entries (see [BufRead::lines]).
+    pub fn buf_reader(&self) -> impl BufRead + 'a {
+        BufReader::new(self.reader())
     }
 
     /// Reads the entire entry into a vector.
@@ -192,6 +755,68 @@ where
         self.reader().read_to_end(&mut v)?;
         Ok(v)
     }
+
+    /// Reads the target of this symlink entry (see [EntryKind::Symlink]),
+    /// decoded with the archive's encoding.
+    ///
+    /// See [read_symlink_target] for details, and [LinkTarget::is_escaping]
+    /// to check where it points before creating the link.
+    ///
+    /// [EntryKind::Symlink]: rc_zip::parse::EntryKind::Symlink
+    /// [LinkTarget::is_escaping]: crate::LinkTarget::is_escaping
+    pub fn read_symlink_target(&self) -> Result<PathBuf, Error> {
+        read_symlink_target(self.reader(), self.encoding)
+    }
+
+    /// Reads the entire entry into memory, so that it can be read as a zip
+    /// archive of its own with [ReadZip::read_zip].
+    ///
+    /// Archives opened this way keep track of how deeply they're nested:
+    /// going more than [MAX_NESTING_DEPTH] levels deep returns
+    /// [Error::NestingTooDeep], to guard against zip bombs made of nested
+    /// archives.
+    pub fn open_nested_zip(&self) -> Result<NestedZip, Error> {
+        let depth = self.depth + 1;
+        if depth > MAX_NESTING_DEPTH {
+            return Err(Error::NestingTooDeep {
+                max_depth: MAX_NESTING_DEPTH,
+            });
+        }
+
+        let data = self.bytes().map_err(into_zip_error)?;
+        Ok(NestedZip { data, depth })
+    }
+}
+
+impl EntryHandle<'_, Arc<[u8]>> {
+    /// Returns a reader for the entry that holds on to the archive's data
+    /// itself, rather than borrowing it, so it can be stored or moved to
+    /// another thread independently of the [ArchiveHandle].
+    pub fn owned_reader(&self) -> impl Read + Send + 'static {
+        let mut cursor = io::Cursor::new(Arc::clone(self.file));
+        cursor.set_position(self.offset + self.entry.header_offset);
+        EntryReader::with_capacity(self.entry, cursor, self.buffer_size)
+            .with_limits(self.decompress_limits)
+    }
+}
+
+/// Reads increasingly large chunks from `cursor` until `parse` succeeds.
+fn read_record<T>(
+    mut cursor: impl Read,
+    parse: impl Fn(&[u8]) -> Result<Option<T>, Error>,
+) -> Result<T, Error> {
+    let mut buf = Vec::new();
+    let mut chunk_size = 64;
+    loop {
+        let n = cursor.by_ref().take(chunk_size).read_to_end(&mut buf)?;
+        if let Some(record) = parse(&buf)? {
+            return Ok(record);
+        }
+        if n == 0 {
+            return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        chunk_size = buf.len() as u64;
+    }
 }
 
 /// A sliceable I/O resource: we can ask for a [Read] at a given offset.
@@ -211,7 +836,9 @@ impl HasCursor for &[u8] {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
     }
 }
 
@@ -221,7 +848,49 @@ impl HasCursor for Vec<u8> {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
+    }
+}
+
+impl HasCursor for Arc<[u8]> {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
+    }
+}
+
+/// The whole vector is read as a zip archive, regardless of the cursor's
+/// position.
+impl HasCursor for io::Cursor<Vec<u8>> {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // reading past the end yields nothing, rather than panicking
+        self.get_ref()
+            .get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl HasCursor for bytes::Bytes {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
     }
 }
 
@@ -244,6 +913,107 @@ impl ReadZip for std::fs::File {
         let size = self.metadata()?.len();
         self.read_zip_with_size(size)
     }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self> {
+        match self.metadata() {
+            Ok(metadata) => self.stream_central_directory_with_size(metadata.len()),
+            Err(err) => CentralDirectoryStream::failed(self, err.into()),
+        }
+    }
+}
+
+/// A window `[offset, offset + length)` into a larger [HasCursor], which can
+/// be read as a zip archive of its own: offsets recorded in the archive are
+/// resolved relative to `offset`.
+///
+/// This is useful for container formats that embed a zip file at a known
+/// position, like asset packs.
+pub struct Subrange<'a, F> {
+    file: &'a F,
+    offset: u64,
+    length: u64,
+}
+
+impl<'a, F> Subrange<'a, F>
+where
+    F: HasCursor,
+{
+    /// Creates a view of `length` bytes of `file`, starting at `offset`.
+    pub fn new(file: &'a F, offset: u64, length: u64) -> Self {
+        Self {
+            file,
+            offset,
+            length,
+        }
+    }
+}
+
+impl<F> HasCursor for Subrange<'_, F>
+where
+    F: HasCursor,
+{
+    type Cursor<'a> = std::io::Take<F::Cursor<'a>>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.file
+            .cursor_at(self.offset + offset)
+            .take(self.length.saturating_sub(offset))
+    }
+}
+
+impl<F> ReadZip for Subrange<'_, F>
+where
+    F: HasCursor,
+{
+    type File = Self;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.length)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.stream_central_directory_with_size(self.length)
+    }
+}
+
+/// How many levels of zip files within zip files [EntryHandle::open_nested_zip]
+/// is willing to open.
+pub const MAX_NESTING_DEPTH: usize = 8;
+
+/// A zip file stored as an entry of another zip file, read into memory by
+/// [EntryHandle::open_nested_zip].
+pub struct NestedZip {
+    data: Vec<u8>,
+    depth: usize,
+}
+
+impl NestedZip {
+    /// How deeply this zip file is nested: 1 for an entry of a top-level
+    /// archive, 2 for an entry of that entry, etc.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The raw contents of the nested zip file
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl ReadZip for NestedZip {
+    type File = Vec<u8>;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let mut archive = self.data.read_zip()?;
+        archive.depth = self.depth;
+        Ok(archive)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        self.data.stream_central_directory()
+    }
 }
 
 /// Allows reading zip entries in a streaming fashion, without seeking,
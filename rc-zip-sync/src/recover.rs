@@ -0,0 +1,236 @@
+use rc_zip::{
+    error::Error,
+    parse::{Archive, Entry, RawRecord, RawRecordKind},
+};
+use std::{cmp, io::Read, ops::Range};
+use tracing::trace;
+
+use crate::{ArchiveHandle, HasCursor, ReadZipOptions};
+
+/// Local file headers are at least this long (with empty name and extra
+/// field)
+const MIN_LOCAL_HEADER_LEN: u64 = 30;
+
+/// How much of the file is scanned for signatures at a time
+const SCAN_CHUNK_SIZE: usize = 256 * 1024;
+
+const LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+const DATA_DESCRIPTOR_SIGNATURE: &[u8] = b"PK\x07\x08";
+const CENTRAL_HEADER_SIGNATURE: &[u8] = b"PK\x01\x02";
+
+/// Reconstructs an archive of `size` bytes from the local headers found in
+/// `file`, for when its central directory is missing or can't be found: the
+/// file is truncated, junk was appended to it, or the central directory was
+/// stripped.
+///
+/// The whole file is scanned for local file header signatures, and every one
+/// that parses becomes an entry, in the order they appear. Entries lack what
+/// only the central directory holds (comments, permissions), and the
+/// [Archive] is marked as [truncated][Archive::truncated].
+///
+/// The sizes and checksum of entries written with a data descriptor are taken
+/// from the descriptor found before the next header: when there's none,
+/// reading such an entry fails validation.
+pub fn recover_from_local_headers<F>(file: &F, size: u64) -> Result<ArchiveHandle<'_, F>, Error>
+where
+    F: HasCursor,
+{
+    let signatures = find_signatures(file, size)?;
+    trace!(
+        local_headers = signatures.local_headers.len(),
+        data_descriptors = signatures.data_descriptors.len(),
+        central_headers = signatures.central_headers.len(),
+        "recover_from_local_headers | found signatures"
+    );
+
+    // an entry's data ends at most where the next header starts
+    let mut boundaries: Vec<u64> = signatures
+        .local_headers
+        .iter()
+        .chain(signatures.central_headers.first())
+        .copied()
+        .collect();
+    boundaries.push(size);
+
+    let mut entries: Vec<Entry> = Vec::new();
+    // offset past the data of the last entry, when known
+    let mut data_end = 0;
+    for &offset in &signatures.local_headers {
+        if offset < data_end {
+            // that's in the data of the previous entry, a stored zip maybe
+            continue;
+        }
+        let Some((mut entry, data_offset)) = read_local_header(file, offset, size)? else {
+            continue;
+        };
+        entry.header_offset = offset;
+
+        let next_boundary = boundaries
+            .iter()
+            .copied()
+            .find(|&boundary| boundary >= data_offset)
+            .unwrap_or(size);
+        if entry.flags & 0b1000 != 0 {
+            // sizes are in the data descriptor, right before the next header
+            let range = data_offset..next_boundary;
+            if !read_data_descriptor(file, &mut entry, range, &signatures.data_descriptors)? {
+                trace!(name = %entry.name, "no data descriptor found");
+                entry.compressed_size = next_boundary - data_offset;
+            }
+        }
+
+        match data_offset.checked_add(entry.compressed_size) {
+            Some(end) if end <= size => data_end = end,
+            _ => {
+                trace!(name = %entry.name, "entry data doesn't fit in the file, skipping");
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+
+    Ok(ArchiveHandle::new(
+        file,
+        Archive::recovered(size, entries),
+        &ReadZipOptions::default(),
+    ))
+}
+
+/// Offsets of the signatures found in the file
+#[derive(Default)]
+struct Signatures {
+    local_headers: Vec<u64>,
+    data_descriptors: Vec<u64>,
+    central_headers: Vec<u64>,
+}
+
+/// Scans the whole file for local file header, data descriptor and central
+/// directory header signatures.
+fn find_signatures<F>(file: &F, size: u64) -> Result<Signatures, Error>
+where
+    F: HasCursor,
+{
+    let mut signatures = Signatures::default();
+
+    let mut cursor = file.cursor_at(0);
+    let mut buf = vec![0u8; SCAN_CHUNK_SIZE];
+    // offset of `buf[0]` in the file
+    let mut buf_offset = 0;
+    // bytes kept from the previous chunk, in case a signature straddles it
+    let mut kept = 0;
+    loop {
+        let n = cursor.read(&mut buf[kept..])?;
+        if n == 0 {
+            break;
+        }
+        let filled = kept + n;
+        for (i, window) in buf[..filled].windows(4).enumerate() {
+            let offset = buf_offset + i as u64;
+            let found = match window {
+                LOCAL_HEADER_SIGNATURE if offset + MIN_LOCAL_HEADER_LEN <= size => {
+                    &mut signatures.local_headers
+                }
+                DATA_DESCRIPTOR_SIGNATURE => &mut signatures.data_descriptors,
+                CENTRAL_HEADER_SIGNATURE => &mut signatures.central_headers,
+                _ => continue,
+            };
+            found.push(offset);
+        }
+
+        kept = 3.min(filled);
+        buf.copy_within(filled - kept..filled, 0);
+        buf_offset += (filled - kept) as u64;
+    }
+
+    Ok(signatures)
+}
+
+/// Parses the local header at `offset`, returning its entry and where its
+/// data starts, or `None` if there's no valid header there.
+fn read_local_header<F>(file: &F, offset: u64, size: u64) -> Result<Option<(Entry, u64)>, Error>
+where
+    F: HasCursor,
+{
+    let mut fixed = [0u8; MIN_LOCAL_HEADER_LEN as usize];
+    file.cursor_at(offset).read_exact(&mut fixed)?;
+    let name_len = u16::from_le_bytes([fixed[26], fixed[27]]) as u64;
+    let extra_len = u16::from_le_bytes([fixed[28], fixed[29]]) as u64;
+    let data_offset = offset + MIN_LOCAL_HEADER_LEN + name_len + extra_len;
+    if data_offset > size {
+        return Ok(None);
+    }
+
+    // some methods have properties right after the header (LZMA has 9
+    // bytes of them), which the parser reads as well
+    let header_len = (data_offset + 16).min(size) - offset;
+    let mut header = vec![0u8; header_len as usize];
+    file.cursor_at(offset).read_exact(&mut header)?;
+
+    let header = match RawRecord::local_file_header(offset, &header) {
+        Ok(Some(RawRecord {
+            kind: RawRecordKind::LocalFileHeader(header),
+            ..
+        })) => header,
+        Ok(_) => return Ok(None),
+        Err(e) => {
+            trace!(%offset, "not a local header: {e}");
+            return Ok(None);
+        }
+    };
+    match header.as_entry() {
+        Ok(entry) => Ok(Some((entry, data_offset))),
+        Err(e) => {
+            // a damaged header shouldn't keep the others from being recovered
+            trace!(%offset, "skipping local header: {e}");
+            Ok(None)
+        }
+    }
+}
+
+/// Looks for the data descriptor of `entry`, which ends its `data` range:
+/// it's either at one of the `descriptors` signatures in that range, or,
+/// without a signature, right at its end. Takes its sizes and checksum if one
+/// matches where it's found.
+fn read_data_descriptor<F>(
+    file: &F,
+    entry: &mut Entry,
+    data: Range<u64>,
+    descriptors: &[u64],
+) -> Result<bool, Error>
+where
+    F: HasCursor,
+{
+    let with_signature = descriptors
+        .iter()
+        .filter(|&&offset| data.contains(&offset))
+        .flat_map(|&offset| [(offset, false), (offset, true)]);
+    let without_signature = [(12, false), (20, true)]
+        .into_iter()
+        .filter_map(|(len, is_zip64)| Some((data.end.checked_sub(len)?, is_zip64)));
+
+    for (offset, is_zip64) in with_signature.chain(without_signature) {
+        if offset < data.start {
+            continue;
+        }
+        // signature, checksum, then two 4-byte or 8-byte sizes
+        let max_len = if is_zip64 { 24 } else { 16 };
+        let len = cmp::min(max_len, data.end - offset);
+        let mut bytes = vec![0u8; len as usize];
+        file.cursor_at(offset).read_exact(&mut bytes)?;
+
+        let Ok(Some(RawRecord {
+            kind: RawRecordKind::DataDescriptor(descriptor),
+            ..
+        })) = RawRecord::data_descriptor(offset, &bytes, is_zip64)
+        else {
+            continue;
+        };
+        if descriptor.compressed_size == offset - data.start {
+            entry.crc32 = descriptor.crc32;
+            entry.compressed_size = descriptor.compressed_size;
+            entry.uncompressed_size = descriptor.uncompressed_size;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,172 @@
+use rc_zip::{error::Error, fsm::ArchiveFsm};
+use std::io::{self, Read};
+
+use crate::{
+    read_zip::read_zip_with_fsm, ArchiveHandle, CentralDirectoryStream, HasCursor, ReadZip,
+    ReadZipOptions,
+};
+
+/// The segments of a split archive (`.z01`, `.z02`, …, then `.zip`), read as
+/// one contiguous file: offsets past the end of a segment land in the next.
+///
+/// Reading it as a zip archive with [ReadZip] translates the offsets
+/// recorded in the archive, which are relative to the start of their
+/// segment, see [ArchiveFsm::with_disk_offsets].
+pub struct SplitReader<F> {
+    segments: Vec<F>,
+    /// where each segment starts
+    offsets: Vec<u64>,
+    size: u64,
+}
+
+impl<F> SplitReader<F>
+where
+    F: HasCursor,
+{
+    /// Creates a reader over `segments`, each given with its length, in
+    /// order.
+    pub fn new(segments: Vec<(F, u64)>) -> Self {
+        let mut offsets = Vec::with_capacity(segments.len());
+        let mut size = 0u64;
+        let segments = segments
+            .into_iter()
+            .map(|(segment, len)| {
+                offsets.push(size);
+                size = size.saturating_add(len);
+                segment
+            })
+            .collect();
+        Self {
+            segments,
+            offsets,
+            size,
+        }
+    }
+
+    /// Total size of all segments, in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Where `index` ends: the start of the next segment, or the end of the
+    /// last one.
+    fn segment_end(&self, index: usize) -> u64 {
+        self.offsets.get(index + 1).copied().unwrap_or(self.size)
+    }
+}
+
+impl<F> HasCursor for SplitReader<F>
+where
+    F: HasCursor,
+{
+    type Cursor<'a> = SplitCursor<'a, F>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // the last segment starting at or before `offset`
+        let index = self
+            .offsets
+            .partition_point(|&start| start <= offset)
+            .saturating_sub(1);
+        SplitCursor {
+            reader: self,
+            index,
+            offset,
+            inner: None,
+        }
+    }
+}
+
+impl<F> ReadZip for SplitReader<F>
+where
+    F: HasCursor,
+{
+    type File = Self;
+
+    fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let options = ReadZipOptions::default();
+        let fsm = ArchiveFsm::with_capacity(self.size, options.buffer_size)
+            .with_disk_offsets(self.offsets.clone());
+        read_zip_with_fsm(self, self.size, fsm, &options)
+    }
+
+    fn stream_central_directory(&self) -> CentralDirectoryStream<'_, Self::File> {
+        let fsm = ArchiveFsm::new(self.size).with_disk_offsets(self.offsets.clone());
+        CentralDirectoryStream::new(self, self.size, fsm)
+    }
+}
+
+/// A [Read] at a given offset of a [SplitReader], moving on to the next
+/// segment whenever one runs out.
+pub struct SplitCursor<'a, F>
+where
+    F: HasCursor + 'a,
+{
+    reader: &'a SplitReader<F>,
+    /// segment being read
+    index: usize,
+    /// offset in the whole split archive
+    offset: u64,
+    inner: Option<io::Take<F::Cursor<'a>>>,
+}
+
+impl<F> Read for SplitCursor<'_, F>
+where
+    F: HasCursor,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(segment) = self.reader.segments.get(self.index) else {
+                return Ok(0);
+            };
+            let start = self.reader.offsets[self.index];
+            let end = self.reader.segment_end(self.index);
+
+            let inner = self.inner.get_or_insert_with(|| {
+                let local = self.offset.saturating_sub(start);
+                segment
+                    .cursor_at(local)
+                    .take(end.saturating_sub(start).saturating_sub(local))
+            });
+            let n = inner.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                self.offset += n as u64;
+                return Ok(n);
+            }
+
+            // this segment is exhausted (or shorter than announced)
+            self.index += 1;
+            self.offset = self.offset.max(end);
+            self.inner = None;
+        }
+    }
+}
+
+/// Opens the segments of a split archive, in order (`.z01`, `.z02`, …, then
+/// `.zip`), to be read with [ReadZip]:
+///
+/// ```no_run
+/// use rc_zip_sync::{read_split_zip, ReadZip};
+///
+/// let split = read_split_zip(&["archive.z01", "archive.z02", "archive.zip"])?;
+/// for entry in split.read_zip()?.entries() {
+///     println!("{}", entry.name);
+/// }
+/// # Ok::<_, rc_zip_sync::rc_zip::error::Error>(())
+/// ```
+#[cfg(feature = "file")]
+pub fn read_split_zip<P>(paths: &[P]) -> Result<SplitReader<std::fs::File>, Error>
+where
+    P: AsRef<std::path::Path>,
+{
+    let segments = paths
+        .iter()
+        .map(|path| {
+            let file = std::fs::File::open(path)?;
+            let len = file.metadata()?.len();
+            Ok((file, len))
+        })
+        .collect::<Result<Vec<_>, io::Error>>()?;
+    Ok(SplitReader::new(segments))
+}
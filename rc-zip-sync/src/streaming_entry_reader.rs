@@ -1,6 +1,6 @@
 use oval::Buffer;
 use rc_zip::{
-    error::{Error, FormatError},
+    error::Error,
     fsm::{EntryFsm, FsmResult},
     parse::Entry,
 };
@@ -54,11 +54,13 @@ where
 
         match std::mem::take(&mut self.state) {
             State::Reading { mut fsm } => {
+                let mut filled = 0;
                 if fsm.wants_read() {
                     trace!("fsm wants read");
                     let n = self.rd.read(fsm.space())?;
                     trace!("giving fsm {} bytes from rd", n);
                     fsm.fill(n);
+                    filled = n;
                 } else {
                     trace!("fsm does not want read");
                 }
@@ -71,7 +73,7 @@ where
                         if outcome.bytes_written > 0 {
                             trace!("bytes have been written");
                             Ok(outcome.bytes_written)
-                        } else if outcome.bytes_read == 0 {
+                        } else if outcome.bytes_read == 0 && filled == 0 {
                             trace!("no bytes have been written or read");
                             // that's EOF, baby!
                             Ok(0)
@@ -130,28 +132,33 @@ where
                 let mut fsm = EntryFsm::new(None, Some(remain));
 
                 loop {
+                    let mut eof = false;
                     if fsm.wants_read() {
                         let n = self.rd.read(fsm.space())?;
                         trace!("read {} bytes into buf for first zip entry", n);
                         fsm.fill(n);
+                        eof = n == 0;
                     }
 
-                    match fsm.process_till_header() {
-                        Ok(Some(entry)) => {
+                    if fsm.reached_end_of_entries() {
+                        trace!("reached the central directory, no entries left");
+                        return Ok(None);
+                    }
+
+                    match fsm.process_till_header()? {
+                        Some(entry) => {
                             let entry = entry.clone();
                             return Ok(Some(StreamingEntryReader::new(fsm, entry, self.rd)));
                         }
-                        Ok(None) => {
+                        None if eof => {
+                            // the stream ended without a central directory:
+                            // there's nothing left to read either way.
+                            trace!("reached end of stream, no entries left");
+                            return Ok(None);
+                        }
+                        None => {
                             // needs more turns
                         }
-                        Err(e) => match e {
-                            Error::Format(FormatError::InvalidLocalHeader) => {
-                                // we probably reached the end of central directory!
-                                // TODO: we should probably check for the end of central directory
-                                return Ok(None);
-                            }
-                            _ => return Err(e),
-                        },
                     }
                 }
             }
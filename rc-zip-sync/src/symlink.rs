@@ -0,0 +1,108 @@
+use rc_zip::{
+    encoding::Encoding,
+    error::{Error, FormatError},
+};
+use std::{
+    fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::verify::into_zip_error;
+
+/// Symlink targets longer than this are rejected, they're way past what any
+/// filesystem accepts anyway.
+pub const MAX_SYMLINK_TARGET_LEN: usize = 64 * 1024;
+
+/// Reads the target of a symlink entry from `reader`, which yields its
+/// contents, decoding it with `encoding` (the archive's, see
+/// [Archive::encoding](rc_zip::parse::Archive::encoding)).
+///
+/// This is for streaming readers: with an [EntryHandle](crate::EntryHandle),
+/// use [EntryHandle::read_symlink_target](crate::EntryHandle::read_symlink_target).
+///
+/// Targets longer than [MAX_SYMLINK_TARGET_LEN] fail with
+/// [FormatError::FieldTooLong]. The target isn't checked in any way: see
+/// [LinkTarget::is_escaping] before creating the link.
+pub fn read_symlink_target(reader: impl Read, encoding: Encoding) -> Result<PathBuf, Error> {
+    let mut bytes = Vec::new();
+    reader
+        .take(MAX_SYMLINK_TARGET_LEN as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(into_zip_error)?;
+    if bytes.len() > MAX_SYMLINK_TARGET_LEN {
+        return Err(FormatError::FieldTooLong {
+            field: "symlink target",
+            len: bytes.len(),
+            max: MAX_SYMLINK_TARGET_LEN,
+        }
+        .into());
+    }
+
+    Ok(PathBuf::from(encoding.decode_lossy(&bytes)))
+}
+
+/// Checks on symlink targets read from archives, to keep them from pointing
+/// outside of the directory an archive is extracted to.
+pub trait LinkTarget {
+    /// Whether this target, for a symlink at `link_path` (relative to the
+    /// extraction directory, like a [sanitized
+    /// name](rc_zip::parse::Entry::sanitized_name)), points outside of the
+    /// extraction directory.
+    ///
+    /// Absolute targets always escape. Relative ones can go up with `..`,
+    /// as long as they stay under the extraction directory: `../b` is fine
+    /// for a link at `dir/a`, but not for one at `a`.
+    ///
+    /// This checks one link in isolation, by only looking at the paths,
+    /// not at the filesystem: it can't catch chains of links. With `x -> .`
+    /// and `x/y -> ..`, neither escapes on its own, but `x/y` resolves to
+    /// the parent of the extraction directory once `x` exists. Use
+    /// [has_symlink_parent] to refuse entries whose path goes through a
+    /// symlink, like [ArchiveHandle::extract_all](crate::ArchiveHandle::extract_all)
+    /// does.
+    fn is_escaping(&self, link_path: impl AsRef<Path>) -> bool;
+}
+
+impl LinkTarget for Path {
+    fn is_escaping(&self, link_path: impl AsRef<Path>) -> bool {
+        let link_dir = link_path.as_ref().parent().unwrap_or(Path::new(""));
+
+        // how deep under the extraction directory we are
+        let mut depth = 0usize;
+        for component in link_dir.components().chain(self.components()) {
+            match component {
+                Component::Prefix(_) | Component::RootDir => return true,
+                Component::CurDir => {}
+                Component::ParentDir => match depth.checked_sub(1) {
+                    Some(parent) => depth = parent,
+                    None => return true,
+                },
+                Component::Normal(_) => depth += 1,
+            }
+        }
+        false
+    }
+}
+
+/// Whether any of the parent directories of `name` (relative to `dir`,
+/// like a [sanitized name](rc_zip::parse::Entry::sanitized_name)) already
+/// exists under `dir` as a symlink.
+///
+/// Entries for which this is true must not be extracted: the symlink may
+/// have been created by an earlier entry of the archive, and writing through
+/// it can land anywhere, see [LinkTarget::is_escaping].
+pub fn has_symlink_parent(dir: impl AsRef<Path>, name: impl AsRef<Path>) -> bool {
+    let mut path = dir.as_ref().to_path_buf();
+    let parent = name.as_ref().parent().unwrap_or(Path::new(""));
+    for component in parent.components() {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => return true,
+            Ok(_) => {}
+            // nothing below a missing directory exists either
+            Err(_) => break,
+        }
+    }
+    false
+}
@@ -0,0 +1,103 @@
+use rc_zip::error::Error;
+use std::{fmt, io};
+
+use crate::{ArchiveHandle, HasCursor};
+
+/// An error found by [ArchiveHandle::verify] or [ArchiveHandle::verify_all].
+#[derive(Debug)]
+pub struct VerifyError {
+    /// Name of the entry that failed verification
+    pub entry_name: String,
+
+    /// What went wrong
+    pub error: Error,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.entry_name, self.error)
+    }
+}
+
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// What [ArchiveHandle::verify_all] found, entry by entry.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Names of the entries whose data checks out
+    pub ok: Vec<String>,
+
+    /// Entries that failed verification, in archive order
+    pub failed: Vec<VerifyError>,
+}
+
+impl VerifyReport {
+    /// Returns true if no entry failed verification
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Decompresses every entry of the archive, discarding the output, to
+    /// check their sizes and CRC32 checksums.
+    ///
+    /// Data descriptors that contradict the central directory are errors
+    /// too, see
+    /// [EntryFsm::with_strict_descriptors](rc_zip::fsm::EntryFsm::with_strict_descriptors).
+    ///
+    /// Returns the first error found, along with the name of the entry it was
+    /// found in.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        for entry in self.entries() {
+            let mut reader = entry.entry_reader().with_strict_descriptors(true);
+            io::copy(&mut reader, &mut io::sink()).map_err(|e| VerifyError {
+                entry_name: entry.name.clone(),
+                error: into_zip_error(e),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Like [Self::verify], but keeps going after an entry fails, and
+    /// reports on every entry.
+    ///
+    /// The same decompression buffer is used for all entries (unless one of
+    /// them fails), so memory usage doesn't grow with the archive.
+    pub fn verify_all(&self) -> VerifyReport {
+        let mut report = VerifyReport::default();
+        let mut buffer = None;
+        for entry in self.entries() {
+            let mut reader = entry
+                .reader_with_buffer(buffer.take())
+                .with_strict_descriptors(true);
+            match io::copy(&mut reader, &mut io::sink()) {
+                Ok(_) => report.ok.push(entry.name.clone()),
+                Err(e) => report.failed.push(VerifyError {
+                    entry_name: entry.name.clone(),
+                    error: into_zip_error(e),
+                }),
+            }
+            buffer = reader.into_buffer();
+        }
+        report
+    }
+}
+
+/// Entry readers report zip errors wrapped in [std::io::Error]: unwrap them.
+pub(crate) fn into_zip_error(e: std::io::Error) -> Error {
+    match e.get_ref() {
+        Some(inner) if inner.is::<Error>() => {
+            let inner = e.into_inner().unwrap();
+            *inner.downcast::<Error>().unwrap()
+        }
+        _ => Error::IO(e),
+    }
+}
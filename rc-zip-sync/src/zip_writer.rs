@@ -0,0 +1,114 @@
+use rc_zip::{
+    error::Error,
+    fsm::{EntryOptions, WriteFsm},
+    parse::Entry,
+};
+use std::io::{self, Write};
+
+use crate::{verify::into_zip_error, EntryHandle, HasCursor};
+
+/// Writes a zip archive to any [Write] implementation.
+///
+/// Start an entry with [Self::start_entry], write its contents through the
+/// [Write] implementation, then call [Self::finish_entry]. Once all entries
+/// are written, call [Self::finish] to write the central directory.
+///
+/// Each entry's data is held in memory until [Self::finish_entry], unless
+/// [Self::with_streaming] is used, see [WriteFsm] for details.
+pub struct ZipWriter<W>
+where
+    W: Write,
+{
+    wr: W,
+    fsm: WriteFsm,
+}
+
+impl<W> ZipWriter<W>
+where
+    W: Write,
+{
+    /// Create a new zip writer that writes to `wr`
+    pub fn new(wr: W) -> Self {
+        Self {
+            wr,
+            fsm: WriteFsm::new(),
+        }
+    }
+
+    /// Write each entry as soon as its data comes in, followed by a data
+    /// descriptor, instead of holding it in memory until it's finished: use
+    /// this when `wr` isn't seekable and entries may be large. See
+    /// [WriteFsm::with_streaming] for details.
+    ///
+    /// This must be set before starting the first entry.
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.fsm = std::mem::take(&mut self.fsm).with_streaming(streaming);
+        self
+    }
+
+    /// Starts a new entry named `name`. The previous entry, if any, must have
+    /// been finished with [Self::finish_entry].
+    pub fn start_entry(&mut self, name: &str, options: &EntryOptions) -> Result<(), Error> {
+        self.fsm.start_entry(name, options)?;
+        self.flush_output()
+    }
+
+    /// Finishes the entry in progress, writing it out.
+    pub fn finish_entry(&mut self) -> Result<(), Error> {
+        self.fsm.finish_entry()?;
+        self.flush_output()
+    }
+
+    /// Copies `entry` from another archive as-is, without decompressing and
+    /// recompressing its data. See [WriteFsm::start_raw_entry] for what is
+    /// preserved.
+    ///
+    /// There must not be an entry in progress.
+    pub fn copy_raw<F>(&mut self, entry: &EntryHandle<'_, F>) -> Result<(), Error>
+    where
+        F: HasCursor,
+    {
+        let mut rd = entry.raw_reader()?;
+        self.fsm.start_raw_entry(entry)?;
+        self.flush_output()?;
+        io::copy(&mut rd, self).map_err(into_zip_error)?;
+        self.finish_entry()
+    }
+
+    /// Entries written so far
+    pub fn entries(&self) -> &[Entry] {
+        self.fsm.entries()
+    }
+
+    /// Writes the central directory and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.fsm.finish()?;
+        self.flush_output()?;
+        self.wr.flush()?;
+        Ok(self.wr)
+    }
+
+    fn flush_output(&mut self) -> Result<(), Error> {
+        let output = self.fsm.output();
+        self.wr.write_all(output)?;
+        let n = output.len();
+        self.fsm.consume(n);
+        Ok(())
+    }
+}
+
+impl<W> Write for ZipWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fsm.write_data(buf)?;
+        self.flush_output()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_output()?;
+        self.wr.flush()
+    }
+}
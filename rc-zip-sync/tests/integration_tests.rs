@@ -1,15 +1,68 @@
 use rc_zip::{
     corpus::{self, zips_dir, Case, Files},
-    error::Error,
-    parse::Archive,
+    encoding::Encoding,
+    error::{Error, FormatError, UnsupportedError, WriteError},
+    fsm::{ArchiveLimits, BufferPool, DecompressLimits, EntryFsm, EntryOptions},
+    parse::{Archive, CentralDirectoryFileHeader, Entry, EntryKind, Method, Mode, RawRecordKind},
+};
+use rc_zip_sync::{
+    read_symlink_target, recover_from_local_headers, ArchiveHandle, ByteRange, EntryHandle,
+    ExtractOptions, HasCursor, LinkTarget, Overwrite, OwnedArchive, RangeResponse, RangeSource,
+    ReadZip, ReadZipOptions, ReadZipStreaming, ReadZipWithSize, SplitReader, Subrange, ZipWriter,
+    MAX_NESTING_DEPTH,
 };
-use rc_zip_sync::{ArchiveHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize};
 
+use chrono::{TimeZone, Utc};
 use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
     fs::File,
-    io::{self, Read},
+    io::{self, BufRead, Read, Seek, SeekFrom, Write},
+    mem,
+    path::Path,
 };
 
+/// Counts large allocations made by the current thread, to check that
+/// buffers get reused, and keeps track of its peak memory usage. It also
+/// counts allocations of exactly [WATCHED_SIZE] bytes, to check how much room
+/// gets reserved up front.
+struct CountingAllocator;
+
+const LARGE_ALLOCATION: usize = 256 * 1024;
+
+thread_local! {
+    static LARGE_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static LIVE_BYTES: Cell<usize> = const { Cell::new(0) };
+    static PEAK_BYTES: Cell<usize> = const { Cell::new(0) };
+    static WATCHED_SIZE: Cell<usize> = const { Cell::new(0) };
+    static WATCHED_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() >= LARGE_ALLOCATION {
+            let _ = LARGE_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        }
+        if WATCHED_SIZE.try_with(Cell::get) == Ok(layout.size()) {
+            let _ = WATCHED_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        }
+        let _ = LIVE_BYTES.try_with(|live| {
+            let live_now = live.get() + layout.size();
+            live.set(live_now);
+            let _ = PEAK_BYTES.try_with(|peak| peak.set(peak.get().max(live_now)));
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE_BYTES.try_with(|live| live.set(live.get().saturating_sub(layout.size())));
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 fn check_case<F: HasCursor>(test: &Case, archive: Result<ArchiveHandle<'_, F>, Error>) {
     corpus::check_case(test, archive.as_ref().map(|ar| -> &Archive { ar }));
     let archive = match archive {
@@ -40,6 +93,53 @@ fn read_from_slice() {
     assert_eq!(archive.entries().count(), 2);
 }
 
+#[test]
+fn read_from_memory() {
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+
+    // the cursor's position doesn't matter
+    let mut cursor = io::Cursor::new(bytes.clone());
+    cursor.set_position(100);
+    let archive = cursor.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 2);
+    assert_eq!(
+        archive.by_name("test.txt").unwrap().bytes().unwrap(),
+        bytes
+            .read_zip()
+            .unwrap()
+            .by_name("test.txt")
+            .unwrap()
+            .bytes()
+            .unwrap()
+    );
+
+    #[cfg(feature = "bytes")]
+    {
+        let bytes = bytes::Bytes::from(bytes);
+        let archive = bytes.read_zip().unwrap();
+        assert_eq!(archive.entries().count(), 2);
+    }
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_slice() {
+    let bytes = bytes::Bytes::from(std::fs::read(zips_dir().join("test.zip")).unwrap());
+    let archive = bytes.read_zip().unwrap();
+
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    assert_eq!(entry.method, Method::Store);
+    let slice = entry.bytes_slice(&bytes).unwrap();
+    assert!(slice == entry.bytes().unwrap());
+    // it points into the archive's own buffer
+    let offset = slice.as_ptr() as usize - bytes.as_ptr() as usize;
+    assert_eq!(offset as u64, entry.data_offset().unwrap());
+
+    let entry = archive.by_name("test.txt").unwrap();
+    assert_eq!(entry.method, Method::Deflate);
+    assert!(entry.bytes_slice(&bytes).is_none());
+}
+
 #[test]
 fn read_from_file() {
     corpus::install_test_subscriber();
@@ -49,6 +149,57 @@ fn read_from_file() {
     assert_eq!(archive.entries().count(), 2);
 }
 
+#[cfg(feature = "rayon")]
+#[test]
+fn read_parallel() {
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let bytes = case.bytes();
+        check_case(&case, bytes.read_zip_parallel(bytes.len() as u64));
+    }
+}
+
+#[test]
+fn stream_central_directory() {
+    corpus::install_test_subscriber();
+
+    let f = File::open(zips_dir().join("test.zip")).unwrap();
+    let entries = f
+        .stream_central_directory()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let archive = f.read_zip().unwrap();
+    assert_eq!(entries.len(), archive.entries().count());
+    for (streamed, entry) in entries.iter().zip(archive.entries()) {
+        assert_eq!(streamed.name, entry.name);
+        assert_eq!(streamed.crc32, entry.crc32);
+    }
+
+    // keep only what's needed
+    let bytes = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let wanted: Vec<_> = bytes
+        .stream_central_directory()
+        .filter_map(|entry| entry.ok().filter(|entry| entry.name.ends_with(".rs")))
+        .collect();
+    assert!(!wanted.is_empty());
+    assert_eq!(
+        wanted.len(),
+        bytes
+            .read_zip()
+            .unwrap()
+            .entries()
+            .filter(|entry| entry.name.ends_with(".rs"))
+            .count()
+    );
+
+    // errors end the stream
+    let truncated = &bytes[..bytes.len() - 10];
+    let mut stream = truncated.stream_central_directory();
+    assert!(stream.next().unwrap().is_err());
+    assert!(stream.next().is_none());
+}
+
 #[test]
 fn real_world_files() {
     corpus::install_test_subscriber();
@@ -82,41 +233,1869 @@ fn streaming() {
         let mut entry = file
             .stream_zip_entries_throwing_caution_to_the_wind()
             .unwrap();
+        let mut num_entries = 1;
         loop {
             let mut v = vec![];
             let n = entry.read_to_end(&mut v).unwrap();
             tracing::trace!("entry {} read {} bytes", entry.entry().name, n);
 
+            // reaching the central directory is a clean end, not an error
             match entry.finish().unwrap() {
-                Some(next) => entry = next,
+                Some(next) => {
+                    entry = next;
+                    num_entries += 1;
+                }
                 None => break,
             }
         }
+        assert_eq!(num_entries, case.files.len(), "{}", case.name);
 
         drop(guarded_path)
     }
 }
 
-// This helps find bugs in state machines!
+#[test]
+fn streaming_one_byte_reads() {
+    corpus::install_test_subscriber();
 
-struct OneByteReadWrapper<R>(R);
+    // data descriptors the flags don't announce are only buffered after the
+    // entry's data has been read, a byte at a time
+    for case in corpus::streaming_test_cases() {
+        let guarded_path = case.absolute_path();
+        let file = OneByteReadWrapper(File::open(&guarded_path.path).unwrap());
 
-impl<R> io::Read for OneByteReadWrapper<R>
-where
-    R: io::Read,
-{
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.0.read(&mut buf[..1])
+        let mut entry = file
+            .stream_zip_entries_throwing_caution_to_the_wind()
+            .unwrap();
+        let mut num_entries = 1;
+        loop {
+            entry.read_to_end(&mut vec![]).unwrap();
+            match entry.finish().unwrap() {
+                Some(next) => {
+                    entry = next;
+                    num_entries += 1;
+                }
+                None => break,
+            }
+        }
+        assert_eq!(num_entries, case.files.len(), "{}", case.name);
+
+        drop(guarded_path)
     }
 }
 
-impl<R> HasCursor for OneByteReadWrapper<R>
-where
-    R: HasCursor,
-{
-    type Cursor<'a> = OneByteReadWrapper<R::Cursor<'a>> where R: 'a;
+#[test]
+fn raw_archive() {
+    corpus::install_test_subscriber();
 
-    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        OneByteReadWrapper(self.0.cursor_at(offset))
+    fn layout(bytes: &[u8]) -> Vec<(&'static str, u64, u64)> {
+        let archive = bytes.read_zip().unwrap();
+        let raw = archive.raw_archive().unwrap();
+
+        // records are contiguous and cover the whole file
+        let end = raw.records().last().map(|r| r.offset + r.length);
+        assert_eq!(end, Some(bytes.len() as u64));
+
+        raw.records()
+            .map(|r| {
+                let kind = match r.kind {
+                    RawRecordKind::LocalFileHeader(_) => "local file header",
+                    RawRecordKind::FileData { .. } => "file data",
+                    RawRecordKind::DataDescriptor(_) => "data descriptor",
+                    RawRecordKind::CentralDirectoryFileHeader(_) => "central directory file header",
+                    RawRecordKind::EndOfCentralDirectory64Record(_) => "eocd64 record",
+                    RawRecordKind::EndOfCentralDirectory64Locator(_) => "eocd64 locator",
+                    RawRecordKind::EndOfCentralDirectoryRecord(_) => "eocd record",
+                };
+                (kind, r.offset, r.length)
+            })
+            .collect()
+    }
+
+    let bytes = std::fs::read(zips_dir().join("zip64.zip")).unwrap();
+    assert_eq!(
+        layout(&bytes),
+        vec![
+            ("local file header", 0, 36),
+            ("file data", 36, 36),
+            ("central directory file header", 72, 72),
+            ("eocd64 record", 144, 56),
+            ("eocd64 locator", 200, 20),
+            ("eocd record", 220, 22),
+        ]
+    );
+
+    // records missing from the archive are left out
+    let bytes = std::fs::read(zips_dir().join("zip64-without-eocd.zip")).unwrap();
+    assert_eq!(
+        layout(&bytes),
+        vec![
+            ("local file header", 0, 36),
+            ("file data", 36, 36),
+            ("central directory file header", 72, 72),
+            ("eocd64 record", 144, 56),
+            ("eocd64 locator", 200, 20),
+        ]
+    );
+
+    let bytes = std::fs::read(zips_dir().join("zip64-without-eocd-or-locator.zip")).unwrap();
+    assert_eq!(
+        layout(&bytes),
+        vec![
+            ("local file header", 0, 36),
+            ("file data", 36, 36),
+            ("central directory file header", 72, 72),
+            ("eocd64 record", 144, 56),
+        ]
+    );
+}
+
+#[test]
+fn extract_overwrite_policy() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let expected = archive.by_name("test.txt").unwrap().bytes().unwrap();
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let path = dir.path().join("test.txt");
+    let report = archive
+        .extract_all(dir.path(), &ExtractOptions::default())
+        .unwrap();
+    assert_eq!(report.files, 2);
+    assert_eq!(report.skipped, 0);
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+
+    std::fs::write(&path, "tampered").unwrap();
+    let options = ExtractOptions {
+        overwrite: Overwrite::Skip,
+        ..Default::default()
+    };
+    let report = archive.extract_all(dir.path(), &options).unwrap();
+    assert_eq!(report.files, 0);
+    assert_eq!(report.skipped, 2);
+    assert_eq!(std::fs::read(&path).unwrap(), b"tampered");
+
+    let options = ExtractOptions {
+        overwrite: Overwrite::Error,
+        ..Default::default()
+    };
+    let err = archive.extract_all(dir.path(), &options).unwrap_err();
+    assert!(
+        matches!(&err, Error::IO(e) if e.kind() == io::ErrorKind::AlreadyExists),
+        "unexpected error: {err:?}"
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), b"tampered");
+
+    let options = ExtractOptions {
+        overwrite: Overwrite::Overwrite,
+        ..Default::default()
+    };
+    let report = archive.extract_all(dir.path(), &options).unwrap();
+    assert_eq!(report.files, 2);
+    assert_eq!(report.skipped, 0);
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+}
+
+#[test]
+fn extract_skips_zip_slip() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ZipWriter::new(Vec::new());
+    for name in [
+        "../evil.txt",
+        "safe/../../evil.txt",
+        "..\\evil.txt",
+        "C:\\evil.txt",
+        "nul",
+        "/abs/ok.txt",
+        "win\\ok.txt",
+    ] {
+        writer.start_entry(name, &EntryOptions::default()).unwrap();
+        writer.write_all(name.as_bytes()).unwrap();
+        writer.finish_entry().unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let root = temp_dir::TempDir::new().unwrap();
+    let dir = root.path().join("out");
+    let report = archive
+        .extract_all(&dir, &ExtractOptions::default())
+        .unwrap();
+    assert_eq!(report.files, 2);
+    assert_eq!(report.skipped, 5);
+
+    assert_eq!(
+        std::fs::read(dir.join("abs").join("ok.txt")).unwrap(),
+        b"/abs/ok.txt"
+    );
+    assert_eq!(
+        std::fs::read(dir.join("win").join("ok.txt")).unwrap(),
+        b"win\\ok.txt"
+    );
+    assert!(!root.path().join("evil.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_skips_symlink_chains() {
+    corpus::install_test_subscriber();
+
+    // neither link escapes on its own, but `x/y` would resolve to the parent
+    // of the destination once `x` exists
+    let symlink = EntryOptions::default().mode(Mode::SYMLINK | Mode(0o777));
+    let mut writer = ZipWriter::new(Vec::new());
+    for (name, options, contents) in [
+        ("x", &symlink, "."),
+        ("x/y", &symlink, ".."),
+        ("x/y/evil.txt", &EntryOptions::default(), "evil"),
+    ] {
+        writer.start_entry(name, options).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+        writer.finish_entry().unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let root = temp_dir::TempDir::new().unwrap();
+    let dir = root.path().join("out");
+    let report = archive
+        .extract_all(&dir, &ExtractOptions::default())
+        .unwrap();
+    assert_eq!((report.symlinks, report.files, report.skipped), (1, 0, 2));
+    assert!(std::fs::symlink_metadata(dir.join("x"))
+        .unwrap()
+        .is_symlink());
+    assert!(!dir.join("y").exists());
+    assert!(!root.path().join("evil.txt").exists());
+}
+
+#[test]
+fn by_name_duplicates() {
+    corpus::install_test_subscriber();
+
+    let mut writer = ZipWriter::new(Vec::new());
+    for contents in ["first", "second"] {
+        writer
+            .start_entry("dup.txt", &EntryOptions::default())
+            .unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+        writer.finish_entry().unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 2);
+
+    // the first entry with a given name wins
+    let entry = archive.by_name("dup.txt").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"first");
+    let mut contents = vec![];
+    archive
+        .reader_by_name("dup.txt")
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+    assert_eq!(contents, b"first");
+}
+
+#[test]
+fn reader_at_and_by_name() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    for (index, entry) in archive.entries().enumerate() {
+        let expected = entry.bytes().unwrap();
+
+        let mut by_index = vec![];
+        archive
+            .reader_at(index)
+            .unwrap()
+            .read_to_end(&mut by_index)
+            .unwrap();
+        assert_eq!(by_index, expected);
+
+        let mut by_name = vec![];
+        archive
+            .reader_by_name(&entry.name)
+            .unwrap()
+            .read_to_end(&mut by_name)
+            .unwrap();
+        assert_eq!(by_name, expected);
+    }
+
+    assert!(archive.reader_at(archive.entries().count()).is_none());
+    assert!(archive.reader_by_name("nope.txt").is_none());
+}
+
+#[test]
+fn all_compression_methods() {
+    corpus::install_test_subscriber();
+
+    // every method with a decoder enabled has a `found-me-<method>.zip` case
+    // in the corpus, all with the same contents
+    let expected: Vec<(&str, Method)> = vec![
+        #[cfg(feature = "lzma")]
+        ("found-me-lzma.zip", Method::Lzma),
+        #[cfg(feature = "deflate64")]
+        ("found-me-deflate64.zip", Method::Deflate64),
+        #[cfg(feature = "bzip2")]
+        ("found-me-bzip2.zip", Method::Bzip2),
+        #[cfg(feature = "zstd")]
+        ("found-me-zstd.zip", Method::Zstd),
+        #[cfg(feature = "xz")]
+        ("found-me-xz.zip", Method::Xz),
+    ];
+
+    let cases: Vec<Case> = corpus::test_cases()
+        .into_iter()
+        .filter(|case| case.name.starts_with("found-me-"))
+        .collect();
+    assert_eq!(
+        cases.iter().map(|case| case.name).collect::<Vec<_>>(),
+        expected.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+    );
+
+    for (case, (_, method)) in cases.iter().zip(expected) {
+        tracing::info!("============ testing {}", case.name);
+
+        let bytes = case.bytes();
+        let archive = bytes.read_zip();
+        for entry in archive.as_ref().unwrap().entries() {
+            assert_eq!(entry.method, method, "{} uses the wrong method", case.name);
+        }
+        check_case(case, archive);
+    }
+}
+
+#[test]
+fn undecodable_name() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("shift-jis-bad-name.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.encoding(), Encoding::ShiftJis);
+
+    let bad: Vec<_> = archive
+        .entries()
+        .filter(|entry| entry.name.contains('\u{FFFD}'))
+        .collect();
+    assert_eq!(bad.len(), 1);
+    assert_eq!(bad[0].name_bytes(), b"should-be-jis/bad-\x81\x7f.txt");
+    assert_eq!(bad[0].bytes().unwrap(), b"hi\n");
+}
+
+#[test]
+fn read_from_subrange() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let case = cases.iter().find(|x| x.name == "test.zip").unwrap();
+    let zip = case.bytes();
+
+    // embed the zip in a larger blob, with data on both sides
+    let offset = 1000;
+    let mut blob = vec![0xAAu8; offset];
+    blob.extend_from_slice(&zip);
+    blob.extend(std::iter::repeat(0xBB).take(500));
+
+    let subrange = Subrange::new(&blob, offset as u64, zip.len() as u64);
+    let archive = subrange.read_zip();
+    check_case(case, archive);
+}
+
+#[test]
+fn read_zip_at() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let case = cases.iter().find(|x| x.name == "test.zip").unwrap();
+    let zip = case.bytes();
+    let other = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+
+    // another zip on each side, which the end of central directory record
+    // scan would find first if it looked outside the window
+    let mut pack = other.clone();
+    let offset = pack.len() as u64;
+    pack.extend_from_slice(&zip);
+    pack.extend_from_slice(&other);
+
+    let archive = pack.read_zip_at(offset, zip.len() as u64);
+    check_case(case, archive);
+
+    let archive = pack.read_zip_at(offset, zip.len() as u64).unwrap();
+    let standalone = zip.read_zip().unwrap();
+    for (entry, standalone_entry) in archive.entries().zip(standalone.entries()) {
+        assert_eq!(entry.header_offset, standalone_entry.header_offset);
+        assert_eq!(
+            entry.fast_reader(true).unwrap().bytes().count(),
+            standalone_entry.uncompressed_size as usize
+        );
+    }
+    let layout = |archive: &ArchiveHandle<'_, Vec<u8>>| {
+        let raw = archive.raw_archive().unwrap();
+        raw.records()
+            .map(|record| (record.offset, record.length))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(layout(&archive), layout(&standalone));
+}
+
+#[test]
+fn directory_without_slash() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("dir-without-slash.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    // `a` is empty, has no trailing slash, but `a/b.txt` lives under it
+    let a = archive.by_name("a").unwrap();
+    assert!(matches!(a.kind(), EntryKind::Directory));
+
+    // `empty` is just an empty file
+    let empty = archive.by_name("empty").unwrap();
+    assert!(empty.is_likely_directory_without_slash());
+    assert!(matches!(empty.kind(), EntryKind::File));
+
+    let dir = temp_dir::TempDir::new().unwrap();
+    let report = archive
+        .extract_all(dir.path(), &ExtractOptions::default())
+        .unwrap();
+    assert_eq!((report.files, report.dirs), (2, 1));
+    assert!(dir.path().join("a").is_dir());
+    assert_eq!(
+        std::fs::read(dir.path().join("a/b.txt")).unwrap(),
+        b"hello\n"
+    );
+}
+
+#[test]
+fn verify() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    bytes.read_zip().unwrap().verify().unwrap();
+
+    // corrupt the CRC32 of `test.txt` in the central directory
+    let mut bytes = bytes;
+    let header_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[header_offset + 16] ^= 0xff;
+
+    let archive = bytes.read_zip().unwrap();
+    let err = archive.verify().unwrap_err();
+    assert_eq!(err.entry_name, "test.txt");
+    assert!(
+        matches!(err.error, Error::Format(FormatError::WrongChecksum { .. })),
+        "unexpected error: {err}"
+    );
+
+    // the full report carries on past the bad entry
+    let report = archive.verify_all();
+    assert!(!report.is_ok());
+    assert_eq!(report.ok, vec!["gophercolor16x16.png"]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].entry_name, "test.txt");
+    assert!(matches!(
+        report.failed[0].error,
+        Error::Format(FormatError::WrongChecksum { .. })
+    ));
+
+    let bytes = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let report = bytes.read_zip().unwrap().verify_all();
+    assert!(report.is_ok());
+    assert_eq!(report.ok.len(), 33);
+}
+
+#[test]
+fn descriptor_central_mismatch() {
+    corpus::install_test_subscriber();
+
+    // corrupt the CRC32 in the data descriptor of dd.zip's only entry,
+    // leaving the one in the central directory alone
+    let mut bytes = std::fs::read(zips_dir().join("dd.zip")).unwrap();
+    let descriptor_offset = bytes.windows(4).position(|w| w == b"PK\x07\x08").unwrap();
+    bytes[descriptor_offset + 4] ^= 0xff;
+
+    // the central directory wins when reading
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.entries().next().unwrap();
+    assert_eq!(entry.bytes().unwrap().len() as u64, entry.uncompressed_size);
+
+    // but verification flags the contradiction
+    let err = archive.verify().unwrap_err();
+    assert!(
+        matches!(
+            err.error,
+            Error::Format(FormatError::DescriptorCentralMismatch { field: "crc32", .. })
+        ),
+        "unexpected error: {err}"
+    );
+    assert_eq!(archive.verify_all().failed.len(), 1);
+}
+
+#[test]
+fn disabled_methods() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("found-me-zstd.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert!(Method::Zstd.is_supported());
+    assert_eq!(Method::Zstd.is_enabled(), cfg!(feature = "zstd"));
+    if cfg!(feature = "zstd") {
+        assert_eq!(archive.disabled_methods(), vec![]);
+    } else {
+        assert_eq!(archive.disabled_methods(), vec![Method::Zstd]);
+    }
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.disabled_methods(), vec![]);
+}
+
+#[test]
+fn entries_by_kind() {
+    corpus::install_test_subscriber();
+
+    fn names<'a>(entries: impl Iterator<Item = &'a Entry>) -> Vec<&'a str> {
+        entries.map(|e| e.name.as_str()).collect()
+    }
+
+    let bytes = std::fs::read(zips_dir().join("symlink.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(names(archive.symlinks()), vec!["symlink"]);
+    assert!(names(archive.files()).is_empty());
+    assert!(names(archive.directories()).is_empty());
+
+    let bytes = std::fs::read(zips_dir().join("dir-without-slash.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(names(archive.files()), vec!["a/b.txt", "empty"]);
+    assert_eq!(names(archive.directories()), vec!["a"]);
+    assert!(names(archive.symlinks()).is_empty());
+}
+
+#[test]
+fn symlink_target() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("symlink.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("symlink").unwrap();
+    let target = entry.read_symlink_target().unwrap();
+    assert_eq!(target, Path::new("../target"));
+    assert_eq!(
+        read_symlink_target(entry.reader(), Encoding::Utf8).unwrap(),
+        target
+    );
+
+    // fine one level down, not at the top
+    assert!(target.is_escaping("symlink"));
+    assert!(!target.is_escaping("dir/symlink"));
+
+    for (target, link, escaping) in [
+        ("sibling", "link", false),
+        ("./a/b", "link", false),
+        ("a/../../b", "dir/link", false),
+        ("../../b", "dir/link", true),
+        ("a/../..", "link", true),
+        ("/etc/passwd", "dir/link", true),
+    ] {
+        assert_eq!(
+            Path::new(target).is_escaping(link),
+            escaping,
+            "{target} from {link}"
+        );
+    }
+}
+
+#[test]
+fn nested_zip() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("nested.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let nested = archive
+        .by_name("test.zip")
+        .unwrap()
+        .open_nested_zip()
+        .unwrap();
+    assert_eq!(nested.depth(), 1);
+
+    let nested_archive = nested.read_zip().unwrap();
+    let names: Vec<_> = nested_archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, vec!["test.txt", "gophercolor16x16.png"]);
+    let test_txt = nested_archive.by_name("test.txt").unwrap().bytes().unwrap();
+    assert_eq!(test_txt, b"This is a test text file.\n");
+
+    // not every entry is a zip
+    let err = archive
+        .by_name("readme.txt")
+        .unwrap()
+        .open_nested_zip()
+        .unwrap()
+        .read_zip()
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        Error::Format(FormatError::DirectoryEndSignatureNotFound)
+    ));
+}
+
+#[test]
+fn nested_zip_too_deep() {
+    corpus::install_test_subscriber();
+
+    /// Opens `inner.zip` entries recursively, returning the deepest level
+    /// reached.
+    fn open_innermost(entry: EntryHandle<'_, Vec<u8>>) -> Result<usize, Error> {
+        let nested = entry.open_nested_zip()?;
+        let archive = nested.read_zip()?;
+        match archive.by_name("inner.zip") {
+            Some(entry) => open_innermost(entry),
+            None => Ok(nested.depth()),
+        }
+    }
+
+    // ten levels of `inner.zip` within `inner.zip`
+    let bytes = std::fs::read(zips_dir().join("nested-too-deep.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("inner.zip").unwrap();
+    match open_innermost(entry) {
+        Err(Error::NestingTooDeep { max_depth }) => assert_eq!(max_depth, MAX_NESTING_DEPTH),
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(depth) => panic!("should have failed, reached depth {depth}"),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn extract_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    corpus::install_test_subscriber();
+
+    // `run.sh` is stored with mode 4755 (setuid)
+    let bytes = std::fs::read(zips_dir().join("setuid.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let extracted_mode = |options: &ExtractOptions| {
+        let dir = temp_dir::TempDir::new().unwrap();
+        archive.extract_all(dir.path(), options).unwrap();
+        let metadata = std::fs::metadata(dir.path().join("run.sh")).unwrap();
+        metadata.permissions().mode() & 0o7777
+    };
+
+    let default_mode = extracted_mode(&ExtractOptions::default());
+    assert_eq!(default_mode & 0o7000, 0, "no setuid by default");
+
+    let preserved = ExtractOptions {
+        preserve_permissions: true,
+        ..Default::default()
+    };
+    assert_eq!(extracted_mode(&preserved), 0o755, "setuid is masked");
+
+    let unmasked = ExtractOptions {
+        preserve_permissions: true,
+        mask_setuid: false,
+        ..Default::default()
+    };
+    assert_eq!(extracted_mode(&unmasked), 0o4755);
+}
+
+#[test]
+fn truncated_archive() {
+    corpus::install_test_subscriber();
+
+    // a partial download: we know how large the file should be, but only
+    // have part of it
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let size = bytes.len() as u64;
+    bytes.truncate(bytes.len() - 300);
+
+    match bytes.read_zip_with_size(size) {
+        Err(Error::Format(FormatError::TruncatedArchive {
+            expected_at,
+            file_size,
+        })) => {
+            assert_eq!(expected_at, bytes.len() as u64);
+            assert_eq!(file_size, size);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[test]
+#[cfg(feature = "deflate")]
+fn truncated_deflate_stream() {
+    corpus::install_test_subscriber();
+
+    // the deflate stream is missing its final block
+    let bytes = std::fs::read(zips_dir().join("truncated-deflate.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("truncated.txt").unwrap();
+    match entry.bytes().map_err(into_zip_error) {
+        Err(Error::Format(FormatError::TruncatedDeflateStream)) => {}
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[test]
+fn fuzz_regressions() {
+    corpus::install_test_subscriber();
+
+    for artifact in corpus::fuzz_artifacts() {
+        let bytes = artifact.bytes();
+        let res = bytes.read_zip().and_then(|archive| {
+            archive.entries().try_for_each(|entry| {
+                entry.bytes()?;
+                Ok(())
+            })
+        });
+        assert!(
+            res.is_err(),
+            "{}: reading the archive should have failed",
+            artifact.name
+        );
+    }
+}
+
+#[test]
+fn buf_reader() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("test.txt").unwrap();
+    let read: Vec<String> = entry.buf_reader().lines().map(Result::unwrap).collect();
+    assert_eq!(read, vec!["This is a test text file."]);
+}
+
+#[test]
+fn tee_reader() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let good_crc = bytes.read_zip().unwrap().by_name("test.txt").unwrap().crc32;
+
+    // corrupt the CRC32 of `test.txt` in the central directory
+    let mut corrupted = bytes.clone();
+    let header_offset = corrupted
+        .windows(4)
+        .position(|w| w == b"PK\x01\x02")
+        .unwrap();
+    corrupted[header_offset + 16] ^= 0xff;
+
+    for bytes in [bytes, corrupted] {
+        let archive = bytes.read_zip().unwrap();
+        let entry = archive.by_name("test.txt").unwrap();
+
+        let mut reported = None;
+        let mut contents = vec![];
+        entry
+            .tee_reader(|crc32, size| reported = Some((crc32, size)))
+            .read_to_end(&mut contents)
+            .unwrap();
+        assert_eq!(contents, b"This is a test text file.\n");
+        assert_eq!(reported, Some((good_crc, contents.len() as u64)));
+    }
+}
+
+#[test]
+fn reader_with_progress() {
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("test.txt").unwrap();
+    assert_eq!(entry.method, Method::Deflate);
+
+    let mut updates = Vec::new();
+    let mut data = Vec::new();
+    entry
+        .reader_with_progress(|compressed, uncompressed| updates.push((compressed, uncompressed)))
+        .read_to_end(&mut data)
+        .unwrap();
+    assert_eq!(data, entry.bytes().unwrap());
+
+    // counters only go up, and end up at the entry's sizes
+    assert!(updates
+        .windows(2)
+        .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1));
+    assert_eq!(
+        updates.last().copied(),
+        Some((entry.compressed_size, entry.uncompressed_size))
+    );
+}
+
+#[test]
+fn read_budget() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let size = bytes.len() as u64;
+
+    // looking for the end of central directory record reads the whole file,
+    // reading the central directory then goes over budget
+    let limits = ArchiveLimits {
+        max_bytes_read: Some(size),
+        ..Default::default()
+    };
+    match bytes.read_zip_with_limits(size, limits) {
+        Err(Error::Budget { bytes_read }) => assert!(bytes_read > size),
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have gone over budget"),
+    }
+
+    // a deadline that has already passed stops reading right away
+    let limits = ArchiveLimits {
+        deadline: Some(std::time::Instant::now()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        bytes.read_zip_with_limits(size, limits),
+        Err(Error::Budget { .. })
+    ));
+
+    let limits = ArchiveLimits {
+        max_bytes_read: Some(4 * size),
+        ..Default::default()
+    };
+    let archive = bytes.read_zip_with_limits(size, limits).unwrap();
+    assert_eq!(archive.len(), 33);
+}
+
+#[test]
+fn owned_reader() {
+    corpus::install_test_subscriber();
+
+    let data: std::sync::Arc<[u8]> = std::fs::read(zips_dir().join("test.zip")).unwrap().into();
+    let mut reader = {
+        let archive = data.read_zip().unwrap();
+        let entry = archive.by_name("test.txt").unwrap();
+        entry.owned_reader()
+    };
+    drop(data);
+
+    // the reader outlives both the archive handle and our copy of the data
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents).unwrap();
+    assert!(contents.starts_with("This is a test text file."));
+}
+
+#[test]
+fn fast_reader() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let expected = std::fs::read(zips_dir().join("gophercolor16x16.png")).unwrap();
+
+    let read_all = |mut rd: Box<dyn Read + '_>| {
+        let mut v = Vec::new();
+        rd.read_to_end(&mut v).map(|_| v)
+    };
+
+    {
+        let archive = bytes.read_zip().unwrap();
+        let entry = archive.by_name("gophercolor16x16.png").unwrap();
+        assert_eq!(entry.method, Method::Store);
+        for verify in [true, false] {
+            let actual = read_all(Box::new(entry.fast_reader(verify).unwrap())).unwrap();
+            assert_eq!(actual, expected);
+        }
+
+        // compressed entries go through the state machine as usual
+        let entry = archive.by_name("test.txt").unwrap();
+        let actual = read_all(Box::new(entry.fast_reader(true).unwrap())).unwrap();
+        assert_eq!(actual, entry.bytes().unwrap());
+    }
+
+    // tamper with the CRC-32 of the stored entry in the central directory
+    let header_offset = bytes.windows(4).rposition(|w| w == b"PK\x01\x02").unwrap();
+    bytes[header_offset + 16] ^= 0xff;
+
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    let err = read_all(Box::new(entry.fast_reader(true).unwrap())).unwrap_err();
+    assert!(err.to_string().contains("checksum didn't match"), "{err}");
+    let actual = read_all(Box::new(entry.fast_reader(false).unwrap())).unwrap();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn seek_stored_entry() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let expected = std::fs::read(zips_dir().join("gophercolor16x16.png")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    let mut rd = entry.fast_reader(true).unwrap();
+
+    let mut buf = [0u8; 50];
+    assert_eq!(rd.seek(SeekFrom::Start(100)).unwrap(), 100);
+    rd.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, expected[100..150]);
+
+    assert_eq!(rd.seek(SeekFrom::Current(-100)).unwrap(), 50);
+    rd.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, expected[50..100]);
+
+    // reading to the end after seeking around doesn't fail the checksum
+    let end = expected.len() as u64;
+    assert_eq!(rd.seek(SeekFrom::End(-10)).unwrap(), end - 10);
+    let mut rest = Vec::new();
+    rd.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, expected[expected.len() - 10..]);
+
+    // past the end, there's nothing left to read
+    assert_eq!(rd.seek(SeekFrom::End(10)).unwrap(), end + 10);
+    assert_eq!(rd.read(&mut buf).unwrap(), 0);
+    assert!(rd.seek(SeekFrom::Current(-(end as i64) - 20)).is_err());
+
+    // compressed entries can't be seeked through
+    let entry = archive.by_name("test.txt").unwrap();
+    let mut rd = entry.fast_reader(true).unwrap();
+    let err = rd.seek(SeekFrom::Start(1)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn read_range() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let expected = std::fs::read(zips_dir().join("gophercolor16x16.png")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    let size = entry.uncompressed_size;
+    for range in [0..size, 10..20, 100..100, size - 1..size] {
+        let mut actual = Vec::new();
+        entry
+            .read_range(range.clone())
+            .unwrap()
+            .read_to_end(&mut actual)
+            .unwrap();
+        assert_eq!(actual, expected[range.start as usize..range.end as usize]);
+    }
+
+    #[allow(clippy::reversed_empty_ranges)]
+    for range in [0..size + 1, 20..10] {
+        assert!(matches!(
+            entry.read_range(range),
+            Err(Error::IO(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+    }
+
+    let entry = archive.by_name("test.txt").unwrap();
+    assert!(matches!(
+        entry.read_range(0..1),
+        Err(Error::Unsupported(
+            UnsupportedError::RandomAccessUnsupported(Method::Deflate)
+        ))
+    ));
+
+    // stored entries whose data can't be read straight from the file
+    let mut writer = ZipWriter::new(Vec::new());
+    writer
+        .start_entry("a.txt", &EntryOptions::default())
+        .unwrap();
+    writer.write_all(b"not so secret").unwrap();
+    writer.finish_entry().unwrap();
+    let bytes = writer.finish().unwrap();
+    let header = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+
+    let mut encrypted = bytes.clone();
+    encrypted[header + 8] |= 0x1;
+    let archive = encrypted.read_zip().unwrap();
+    let entry = archive.by_name("a.txt").unwrap();
+    assert!(matches!(
+        entry.read_range(0..1),
+        Err(Error::Unsupported(UnsupportedError::EncryptedEntry))
+    ));
+    assert!(matches!(
+        entry.fast_reader(false),
+        Err(Error::Unsupported(UnsupportedError::EncryptedEntry))
+    ));
+
+    // the uncompressed size runs past the data
+    let mut mismatched = bytes.clone();
+    mismatched[header + 24..header + 28].copy_from_slice(&100u32.to_le_bytes());
+    let archive = mismatched.read_zip().unwrap();
+    let entry = archive.by_name("a.txt").unwrap();
+    assert!(matches!(
+        entry.read_range(0..100),
+        Err(Error::Format(FormatError::WrongSize {
+            expected: 100,
+            actual: 13
+        }))
+    ));
+    assert!(matches!(
+        entry.fast_reader(false),
+        Err(Error::Format(FormatError::WrongSize { .. }))
+    ));
+}
+
+#[test]
+fn owned_archive() {
+    corpus::install_test_subscriber();
+
+    let file = File::open(zips_dir().join("zip64.zip")).unwrap();
+    let archive = OwnedArchive::from_reader(file).unwrap();
+    assert_eq!(archive.len(), 1);
+
+    let mut readme = archive.reader_by_name("README").unwrap();
+    let mut first = archive.reader_at(0).unwrap();
+    assert!(archive.reader_by_name("nope").is_none());
+
+    // readers keep using the file once the archive is gone
+    assert!(archive.into_inner().is_none());
+    for reader in [&mut readme as &mut dyn Read, &mut first] {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "This small file is in ZIP64 format.\n");
+    }
+}
+
+#[test]
+fn extract_reuses_buffer() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let dir = temp_dir::TempDir::new().unwrap();
+
+    let before = LARGE_ALLOCATIONS.with(Cell::get);
+    let report = archive
+        .extract_all(dir.path(), &ExtractOptions::default())
+        .unwrap();
+    let after = LARGE_ALLOCATIONS.with(Cell::get);
+
+    assert_eq!(report.files, 26);
+    assert_eq!(after - before, 1, "one buffer for all entries");
+}
+
+#[test]
+fn small_buffers() {
+    corpus::install_test_subscriber();
+
+    const READERS: usize = 2000;
+    const BUFFER_SIZE: usize = 32 * 1024;
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes
+        .read_zip_with_options(
+            bytes.len() as u64,
+            ReadZipOptions {
+                buffer_size: BUFFER_SIZE,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let entry = archive
+        .entries()
+        .find(|entry| entry.method == Method::Store)
+        .unwrap();
+
+    let baseline = LIVE_BYTES.with(Cell::get);
+    PEAK_BYTES.with(|peak| peak.set(baseline));
+
+    let mut readers = (0..READERS).map(|_| entry.reader()).collect::<Vec<_>>();
+    for reader in &mut readers {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte).unwrap();
+    }
+    let peak = PEAK_BYTES.with(Cell::get) - baseline;
+
+    // the buffers themselves, plus some room for the rest of the readers
+    assert!(
+        peak < READERS * (BUFFER_SIZE + 8 * 1024),
+        "{READERS} readers peaked at {peak} bytes"
+    );
+
+    let mut contents = Vec::new();
+    readers.pop().unwrap().read_to_end(&mut contents).unwrap();
+    assert_eq!(contents.len() as u64 + 1, entry.uncompressed_size);
+}
+
+#[test]
+fn local_header_too_large() {
+    corpus::install_test_subscriber();
+
+    let name = "a".repeat(2 * EntryFsm::MIN_BUFFER_CAPACITY);
+    let mut writer = ZipWriter::new(Vec::new());
+    writer.start_entry(&name, &EntryOptions::default()).unwrap();
+    writer.write_all(b"hello").unwrap();
+    writer.finish_entry().unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes
+        .read_zip_with_options(
+            bytes.len() as u64,
+            ReadZipOptions {
+                buffer_size: EntryFsm::MIN_BUFFER_CAPACITY,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let entry = archive.by_name(&name).unwrap();
+    let err = entry.bytes().map_err(into_zip_error).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            Error::Format(FormatError::LocalHeaderTooLarge { len, capacity })
+                if len == 30 + name.len() && capacity == EntryFsm::MIN_BUFFER_CAPACITY
+        ),
+        "unexpected error: {err:?}"
+    );
+}
+
+#[test]
+fn presized_central_directory() {
+    corpus::install_test_subscriber();
+
+    const ENTRIES: usize = 5000;
+    let mut writer = ZipWriter::new(Vec::new());
+    for i in 0..ENTRIES {
+        writer
+            .start_entry(&format!("{i}.txt"), &EntryOptions::default())
+            .unwrap();
+        writer.finish_entry().unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    // room for all the central directory headers is reserved at once, rather
+    // than grown to a power of two as they get parsed
+    WATCHED_SIZE.with(|size| size.set(ENTRIES * mem::size_of::<CentralDirectoryFileHeader>()));
+    let archive = bytes.read_zip().unwrap();
+    WATCHED_SIZE.with(|size| size.set(0));
+
+    assert_eq!(WATCHED_ALLOCATIONS.with(Cell::get), 1);
+    assert_eq!(archive.entries().count(), ENTRIES);
+}
+
+#[test]
+fn buffer_pool() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let pool = BufferPool::new(EntryFsm::DEFAULT_BUFFER_CAPACITY, 2);
+    let archive = bytes.read_zip_with_pool(&pool).unwrap();
+    let files = archive
+        .entries()
+        .filter(|entry| matches!(entry.kind(), EntryKind::File))
+        .collect::<Vec<_>>();
+
+    // entries read one after the other share a single buffer
+    let before = LARGE_ALLOCATIONS.with(Cell::get);
+    for entry in &files {
+        let contents = entry.bytes().unwrap();
+        assert_eq!(contents.len() as u64, entry.uncompressed_size);
+    }
+    let after = LARGE_ALLOCATIONS.with(Cell::get);
+    assert_eq!(after - before, 1, "one buffer for all entries");
+    assert_eq!(pool.len(), 1);
+
+    // readers running in parallel get buffers of their own, but the pool
+    // only keeps so many around
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for entry in &files {
+                    entry.bytes().unwrap();
+                }
+            });
+        }
+    });
+    assert!(pool.len() <= 2);
+
+    for _ in 0..5 {
+        pool.put(oval::Buffer::with_capacity(
+            EntryFsm::DEFAULT_BUFFER_CAPACITY,
+        ));
+    }
+    assert_eq!(pool.len(), 2);
+}
+
+#[test]
+fn forced_encoding() {
+    corpus::install_test_subscriber();
+
+    let read_with = |name: &str, encoding: Encoding| -> Vec<String> {
+        let bytes = std::fs::read(zips_dir().join(name)).unwrap();
+        let options = ReadZipOptions {
+            encoding: Some(encoding),
+            ..Default::default()
+        };
+        let archive = bytes
+            .read_zip_with_options(bytes.len() as u64, options)
+            .unwrap();
+        assert_eq!(archive.encoding(), encoding);
+        archive.entries().map(|entry| entry.name.clone()).collect()
+    };
+
+    let names = read_with("shift-jis.zip", Encoding::ShiftJis);
+    assert!(names
+        .contains(&"should-be-jis/ot_運命のワルツﾈぞなぞ小さな楽しみ遊びま.longboi".to_string()));
+
+    // no detection going on: the names come out as mojibake
+    let names = read_with("shift-jis.zip", Encoding::Cp437);
+    assert_eq!(
+        names,
+        [
+            "should-be-jis/ot_ë^û╜é╠âÅâïâc╚é╝é╚é╝Å¼é│é╚èyé╡é▌ùVé╤é▄.longboi",
+            "should-be-jis/"
+        ]
+    );
+
+    // entries flagged as UTF-8 are decoded with the given encoding too
+    let names = read_with("utf8-winrar.zip", Encoding::Cp437);
+    assert_eq!(names, ["Σ╕ûτòî"]);
+}
+
+#[test]
+fn decompress_limits() {
+    corpus::install_test_subscriber();
+
+    // 1MiB of zeroes, deflated to about 1KiB
+    let bytes = std::fs::read(zips_dir().join("zeroes.zip")).unwrap();
+    let read_with = |limits: DecompressLimits| {
+        let options = ReadZipOptions {
+            decompress_limits: limits,
+            ..Default::default()
+        };
+        let archive = bytes
+            .read_zip_with_options(bytes.len() as u64, options)
+            .unwrap();
+        let entry = archive.entries().next().unwrap();
+        entry.bytes().map_err(into_zip_error)
+    };
+
+    // no limits by default
+    assert_eq!(read_with(Default::default()).unwrap().len(), 1024 * 1024);
+
+    let res = read_with(DecompressLimits {
+        max_output_bytes: Some(64 * 1024),
+        ..Default::default()
+    });
+    assert!(
+        matches!(res, Err(Error::LimitExceeded { uncompressed_bytes, .. }) if uncompressed_bytes > 64 * 1024),
+        "expected output limit to be hit, got {res:?}"
+    );
+
+    let res = read_with(DecompressLimits {
+        max_ratio: Some(100),
+        ..Default::default()
+    });
+    assert!(
+        matches!(res, Err(Error::LimitExceeded { .. })),
+        "expected ratio limit to be hit, got {res:?}"
+    );
+
+    let res = read_with(DecompressLimits {
+        max_output_bytes: Some(2 * 1024 * 1024),
+        max_ratio: Some(2000),
+        ..Default::default()
+    });
+    assert_eq!(res.unwrap().len(), 1024 * 1024);
+
+    // stored entries are capped too, even through the fast path
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let options = ReadZipOptions {
+        decompress_limits: DecompressLimits {
+            max_output_bytes: Some(100),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let archive = bytes
+        .read_zip_with_options(bytes.len() as u64, options)
+        .unwrap();
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    let mut contents = Vec::new();
+    let res = entry.fast_reader(true).unwrap().read_to_end(&mut contents);
+    assert!(matches!(
+        res.map_err(into_zip_error),
+        Err(Error::LimitExceeded { .. })
+    ));
+
+    // they can still be seeked through, up to the cap
+    let expected = bytes
+        .read_zip()
+        .unwrap()
+        .by_name("gophercolor16x16.png")
+        .unwrap()
+        .bytes()
+        .unwrap();
+    let mut rd = entry.fast_reader(true).unwrap();
+    rd.seek(SeekFrom::Start(90)).unwrap();
+    let mut buf = [0u8; 10];
+    rd.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, expected[90..100]);
+    assert!(matches!(
+        rd.read(&mut buf).map_err(into_zip_error),
+        Err(Error::LimitExceeded { .. })
+    ));
+}
+
+#[cfg(feature = "ppmd")]
+#[test]
+fn ppmd_limits() {
+    corpus::install_test_subscriber();
+
+    // 100KB of text, in 69 bytes of PPMd data
+    let bytes = std::fs::read(zips_dir().join("ppmd.zip")).unwrap();
+    let read_with = |limits: DecompressLimits| {
+        let options = ReadZipOptions {
+            decompress_limits: limits,
+            ..Default::default()
+        };
+        let archive = bytes
+            .read_zip_with_options(bytes.len() as u64, options)
+            .unwrap();
+        let entry = archive.by_name("found-me.txt").unwrap();
+        entry.bytes().map_err(into_zip_error)
+    };
+
+    assert_eq!(read_with(Default::default()).unwrap().len(), 100_000);
+
+    // the output is decoded as it's read, the limit is hit long before the
+    // whole entry is decoded
+    let res = read_with(DecompressLimits {
+        max_output_bytes: Some(1024),
+        ..Default::default()
+    });
+    assert!(
+        matches!(res, Err(Error::LimitExceeded { uncompressed_bytes, .. }) if uncompressed_bytes < 100_000),
+        "expected output limit to be hit, got {res:?}"
+    );
+
+    // the entry's model takes 16MiB
+    let res = read_with(DecompressLimits {
+        max_ppmd_memory: Some(8 * 1024 * 1024),
+        ..Default::default()
+    });
+    assert!(
+        matches!(
+            res,
+            Err(Error::Decompression {
+                method: Method::Ppmd,
+                ..
+            })
+        ),
+        "expected model memory limit to be hit, got {res:?}"
+    );
+    assert_eq!(
+        read_with(DecompressLimits {
+            max_ppmd_memory: Some(16 * 1024 * 1024),
+            ..Default::default()
+        })
+        .unwrap()
+        .len(),
+        100_000
+    );
+}
+
+#[test]
+fn write_stored() {
+    let modified = Utc.with_ymd_and_hms(2024, 2, 29, 13, 37, 42).unwrap();
+    let options = EntryOptions::default().modified(modified);
+
+    let mut writer = ZipWriter::new(Vec::new());
+    writer.start_entry("hello.txt", &options).unwrap();
+    writer.write_all(b"Hello, ").unwrap();
+    writer.write_all(b"world!\n").unwrap();
+    writer.finish_entry().unwrap();
+
+    writer.start_entry("dir/", &options).unwrap();
+    writer.finish_entry().unwrap();
+
+    let script = EntryOptions::default()
+        .modified(modified)
+        .mode(Mode(0o755))
+        .comment("run me");
+    writer.start_entry("dir/héllo.sh", &script).unwrap();
+    writer.write_all(b"#!/bin/sh\necho hi\n").unwrap();
+    writer.finish_entry().unwrap();
+
+    // misuse is reported rather than producing a broken archive
+    assert!(matches!(
+        writer.finish_entry(),
+        Err(Error::Write(WriteError::NoEntryInProgress))
+    ));
+    assert!(matches!(
+        writer.start_entry("bzipped", &EntryOptions::default().method(Method::Bzip2)),
+        Err(Error::Unsupported(UnsupportedError::MethodNotSupported(
+            Method::Bzip2
+        )))
+    ));
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 3);
+
+    let entry = archive.by_name("hello.txt").unwrap();
+    assert!(matches!(entry.kind(), EntryKind::File));
+    assert_eq!(entry.method, Method::Store);
+    assert_eq!(entry.modified, modified);
+    assert_eq!(entry.bytes().unwrap(), b"Hello, world!\n");
+
+    let entry = archive.by_name("dir/").unwrap();
+    assert!(matches!(entry.kind(), EntryKind::Directory));
+
+    let entry = archive.by_name("dir/héllo.sh").unwrap();
+    assert_eq!(entry.comment, "run me");
+    assert_eq!(entry.mode.0 & 0o777, 0o755);
+    assert_eq!(entry.bytes().unwrap(), b"#!/bin/sh\necho hi\n");
+}
+
+#[test]
+#[cfg(feature = "deflate")]
+fn write_deflate() {
+    let mut text = Vec::new();
+    for i in 0.. {
+        if text.len() >= 1024 * 1024 {
+            break;
+        }
+        writeln!(
+            text,
+            "line {i}: the quick brown fox jumps over the lazy dog"
+        )
+        .unwrap();
+    }
+
+    let write_with = |level: u8| {
+        let options = EntryOptions::default().method(Method::Deflate).level(level);
+        let mut writer = ZipWriter::new(Vec::new());
+        writer.start_entry("big.txt", &options).unwrap();
+        // uneven chunks, to exercise the compressor's streaming
+        for chunk in text.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish_entry().unwrap();
+        writer.finish().unwrap()
+    };
+
+    let bytes = write_with(EntryOptions::DEFAULT_LEVEL);
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("big.txt").unwrap();
+    assert_eq!(entry.method, Method::Deflate);
+    assert_eq!(entry.uncompressed_size, text.len() as u64);
+    assert!(entry.compressed_size < entry.uncompressed_size / 2);
+    assert!(entry.bytes().unwrap() == text);
+
+    // level 0 still produces valid deflate data, just (much) larger
+    let bytes = write_with(0);
+    let archive = bytes.read_zip().unwrap();
+    let stored = archive.by_name("big.txt").unwrap();
+    assert!(stored.compressed_size > entry.compressed_size);
+    assert!(stored.bytes().unwrap() == text);
+}
+
+#[test]
+#[cfg(feature = "deflate")]
+fn write_streaming() {
+    let contents = [
+        ("a.txt", b"hello ".repeat(1000)),
+        ("empty.txt", vec![]),
+        ("b/c.txt", b"world ".repeat(5000)),
+    ];
+    let options = EntryOptions::default().method(Method::Deflate);
+
+    let mut writer = ZipWriter::new(Vec::new()).with_streaming(true);
+    for (name, data) in &contents {
+        writer.start_entry(name, &options).unwrap();
+        // the local header goes out right away, with placeholder sizes
+        writer.flush().unwrap();
+        for chunk in data.chunks(777) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish_entry().unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    // the central directory has the actual sizes
+    let archive = bytes.read_zip().unwrap();
+    for (name, data) in &contents {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.uncompressed_size, data.len() as u64);
+        assert!(&entry.bytes().unwrap() == data);
+    }
+
+    // and streaming readers find them in the data descriptors
+    let mut next = Some(
+        bytes
+            .as_slice()
+            .stream_zip_entries_throwing_caution_to_the_wind()
+            .unwrap(),
+    );
+    for (name, data) in &contents {
+        let mut entry = next.take().expect("entry should be there");
+        assert_eq!(entry.entry().name, *name);
+        let mut v = vec![];
+        entry.read_to_end(&mut v).unwrap();
+        assert!(&v == data);
+        next = entry.finish().unwrap();
+    }
+    assert!(next.is_none());
+
+    // stored entries can't be streamed back, as nothing marks their end
+    let mut writer = ZipWriter::new(Vec::new()).with_streaming(true);
+    writer
+        .start_entry("stored.txt", &EntryOptions::default())
+        .unwrap();
+    writer.write_all(b"stored").unwrap();
+    writer.finish_entry().unwrap();
+    let bytes = writer.finish().unwrap();
+    assert_eq!(
+        bytes
+            .read_zip()
+            .unwrap()
+            .by_name("stored.txt")
+            .unwrap()
+            .bytes()
+            .unwrap(),
+        b"stored"
+    );
+    assert!(matches!(
+        bytes
+            .as_slice()
+            .stream_zip_entries_throwing_caution_to_the_wind(),
+        Err(Error::Unsupported(UnsupportedError::UnknownStoredSize))
+    ));
+}
+
+#[test]
+#[cfg(feature = "deflate")]
+fn recover_from_local_headers_without_directory() {
+    corpus::install_test_subscriber();
+
+    // sizes in the local headers
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let directory_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes.truncate(directory_offset);
+    assert!(bytes.read_zip().is_err());
+
+    let archive = recover_from_local_headers(&bytes, bytes.len() as u64).unwrap();
+    assert!(archive.truncated());
+    let names: Vec<_> = archive.entries().map(|entry| entry.name.clone()).collect();
+    assert_eq!(names, ["test.txt", "gophercolor16x16.png"]);
+    assert_eq!(
+        archive.by_name("test.txt").unwrap().bytes().unwrap(),
+        b"This is a test text file.\n"
+    );
+
+    // sizes in data descriptors, and junk where the central directory was
+    let contents = [
+        ("a.txt", b"hello ".repeat(1000)),
+        ("b.txt", b"world".to_vec()),
+    ];
+    let options = EntryOptions::default().method(Method::Deflate);
+    let mut writer = ZipWriter::new(Vec::new()).with_streaming(true);
+    for (name, data) in &contents {
+        writer.start_entry(name, &options).unwrap();
+        writer.write_all(data).unwrap();
+        writer.finish_entry().unwrap();
+    }
+    let mut bytes = writer.finish().unwrap();
+    let directory_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes.truncate(directory_offset);
+    bytes.extend_from_slice(&[0xff; 100]);
+
+    let archive = recover_from_local_headers(&bytes, bytes.len() as u64).unwrap();
+    assert_eq!(archive.entries().count(), contents.len());
+    for (name, data) in &contents {
+        let entry = archive.by_name(name).unwrap();
+        assert_eq!(entry.uncompressed_size, data.len() as u64);
+        assert_eq!(&entry.bytes().unwrap(), data);
+    }
+}
+
+#[test]
+fn write_many_entries() {
+    // more than fit in the regular end of central directory record
+    const NUM_ENTRIES: usize = 65_600;
+
+    let options = EntryOptions::default();
+    let mut writer = ZipWriter::new(Vec::new());
+    for i in 0..NUM_ENTRIES {
+        writer.start_entry(&format!("{i}.txt"), &options).unwrap();
+        write!(writer, "{i}").unwrap();
+        writer.finish_entry().unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let archive = bytes.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), NUM_ENTRIES);
+    for i in [0, 65_534, 65_535, NUM_ENTRIES - 1] {
+        let entry = archive.by_name(format!("{i}.txt")).unwrap();
+        assert_eq!(entry.bytes().unwrap(), i.to_string().as_bytes());
+    }
+}
+
+#[test]
+fn copy_raw() {
+    let source = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let source = source.read_zip().unwrap();
+
+    let mut writer = ZipWriter::new(Vec::new());
+    writer
+        .start_entry("README.txt", &EntryOptions::default())
+        .unwrap();
+    writer.write_all(b"copied from meta.zip").unwrap();
+    writer.finish_entry().unwrap();
+    for entry in source.entries() {
+        writer.copy_raw(&entry).unwrap();
+    }
+    let bytes = writer.finish().unwrap();
+
+    let copy = bytes.read_zip().unwrap();
+    assert_eq!(copy.entries().count(), source.entries().count() + 1);
+    for entry in source.entries() {
+        let copied = copy.by_name(&entry.name).unwrap();
+        assert_eq!(copied.method, entry.method);
+        assert_eq!(copied.crc32, entry.crc32);
+        assert_eq!(copied.compressed_size, entry.compressed_size);
+        assert_eq!(copied.uncompressed_size, entry.uncompressed_size);
+        assert_eq!(copied.modified_local(), entry.modified_local());
+        assert!(copied.bytes().unwrap() == entry.bytes().unwrap());
+    }
+}
+
+#[test]
+fn raw_reader() {
+    // stored entries are the same raw or not
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    assert_eq!(entry.method, Method::Store);
+    let mut raw = Vec::new();
+    entry.raw_reader().unwrap().read_to_end(&mut raw).unwrap();
+    assert!(raw == entry.bytes().unwrap());
+
+    // compressed entries come out compressed, and the data descriptor that
+    // follows isn't part of them
+    let bytes = std::fs::read(zips_dir().join("dd.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.entries().next().unwrap();
+    assert_eq!(entry.method, Method::Deflate);
+    let mut raw = Vec::new();
+    entry.raw_reader().unwrap().read_to_end(&mut raw).unwrap();
+    assert_eq!(raw.len() as u64, entry.compressed_size);
+
+    let pos = bytes
+        .windows(raw.len())
+        .position(|window| window == raw)
+        .unwrap();
+    assert!(bytes[pos + raw.len()..].starts_with(b"PK\x07\x08"));
+}
+
+#[test]
+fn split_archive() {
+    // made with `zip -0 -s 64k`: found-me.txt spans both disks, second.txt
+    // and the central directory are on the last one
+    let paths = [zips_dir().join("split.z01"), zips_dir().join("split.zip")];
+    let split = rc_zip_sync::read_split_zip(&paths).unwrap();
+    let archive = split.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 2);
+
+    let entry = archive.by_name("found-me.txt").unwrap();
+    let expected = std::fs::read(zips_dir().join("found-me.txt")).unwrap();
+    assert!(entry.bytes().unwrap() == expected);
+
+    let entry = archive.by_name("second.txt").unwrap();
+    assert_eq!(
+        entry.bytes().unwrap(),
+        b"This entry starts on the second disk.\n"
+    );
+
+    let names: Vec<_> = split
+        .stream_central_directory()
+        .map(|entry| entry.unwrap().name)
+        .collect();
+    assert_eq!(names, ["found-me.txt", "second.txt"]);
+
+    let disks: Vec<_> = archive
+        .entries()
+        .map(|entry| (entry.disk_nbr_start, entry.header_offset))
+        .collect();
+    // disk 0 starts with a spanning marker, offsets are relative to the disk
+    assert_eq!(disks, [(0, 4), (1, 65536 + 34510)]);
+
+    // disks that don't exist are an error, not a panic
+    let last = File::open(&paths[1]).unwrap();
+    let len = last.metadata().unwrap().len();
+    let split = SplitReader::new(vec![(last, len)]);
+    assert!(matches!(
+        split.read_zip(),
+        Err(Error::Format(FormatError::InvalidDiskNumber {
+            disk: 1,
+            total_disks: 1
+        }))
+    ));
+}
+
+#[test]
+fn data_offset() {
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().unwrap();
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    let offset = entry.data_offset().unwrap() as usize;
+    let data = &bytes[offset..offset + entry.compressed_size as usize];
+    assert!(data == entry.bytes().unwrap());
+
+    // the same, from the local header alone
+    let header = &bytes[entry.header_offset as usize..];
+    let entry: &Entry = &entry;
+    assert_eq!(
+        entry.data_offset(&header[..20], archive.size()).unwrap(),
+        None
+    );
+    assert_eq!(
+        entry.data_offset(header, archive.size()).unwrap(),
+        Some(offset as u64)
+    );
+
+    // data past the end of the archive
+    let truncated = offset as u64 + entry.compressed_size - 1;
+    assert!(matches!(
+        entry.data_offset(header, truncated),
+        Err(Error::Format(FormatError::DataOutOfBounds { data_end, file_size }))
+            if data_end == truncated + 1 && file_size == truncated
+    ));
+
+    // offsets are in the file, not the archive
+    let mut padded = vec![0u8; 100];
+    padded.extend_from_slice(&bytes);
+    let archive = padded.read_zip_at(100, bytes.len() as u64).unwrap();
+    let entry = archive.by_name("gophercolor16x16.png").unwrap();
+    assert_eq!(entry.data_offset().unwrap(), offset as u64 + 100);
+}
+
+#[test]
+fn range_source() {
+    // a large entry, followed by a small one
+    let options = EntryOptions::default();
+    let mut writer = ZipWriter::new(Vec::new());
+    writer.start_entry("large.bin", &options).unwrap();
+    writer.write_all(&vec![0xab; 4 * 1024 * 1024]).unwrap();
+    writer.finish_entry().unwrap();
+    writer.start_entry("small.txt", &options).unwrap();
+    writer.write_all(b"just this, please\n").unwrap();
+    writer.finish_entry().unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let fetched = Cell::new(0u64);
+    let requests = Cell::new(0usize);
+    let source = RangeSource::new(|range| {
+        requests.set(requests.get() + 1);
+        let len = bytes.len() as u64;
+        let range = match range {
+            ByteRange::Suffix(n) => len.saturating_sub(n)..len,
+            ByteRange::Bounded(range) => range.start.min(len)..range.end.min(len),
+        };
+        fetched.set(fetched.get() + (range.end - range.start));
+        Ok(RangeResponse {
+            data: bytes[range.start as usize..range.end as usize].to_vec(),
+            total_size: len,
+        })
+    })
+    .unwrap();
+    assert_eq!(source.size(), bytes.len() as u64);
+
+    let archive = source.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 2);
+    let entry = archive.by_name("small.txt").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"just this, please\n");
+
+    // the tail holds the central directory, and the small entry
+    assert_eq!(requests.get(), 1);
+    assert!(fetched.get() <= 256 * 1024);
+
+    // the large entry is fetched in chunks, as it's read
+    let entry = archive.by_name("large.bin").unwrap();
+    let mut rd = entry.reader();
+    let mut buf = vec![0u8; 1024];
+    rd.read_exact(&mut buf).unwrap();
+    assert!(buf.iter().all(|&b| b == 0xab));
+    assert!(requests.get() > 1);
+    assert!(fetched.get() < 1024 * 1024);
+
+    assert_eq!(entry.bytes().unwrap().len(), 4 * 1024 * 1024);
+}
+
+#[test]
+fn range_source_ignoring_ranges() {
+    // larger than the tail, so only its end should be kept
+    let options = EntryOptions::default();
+    let mut writer = ZipWriter::new(Vec::new());
+    writer.start_entry("large.bin", &options).unwrap();
+    writer.write_all(&vec![0xab; 1024 * 1024]).unwrap();
+    writer.finish_entry().unwrap();
+    writer.start_entry("small.txt", &options).unwrap();
+    writer.write_all(b"just this, please\n").unwrap();
+    writer.finish_entry().unwrap();
+    let bytes = writer.finish().unwrap();
+
+    // like a server that answers every range request with the whole resource
+    let source = RangeSource::new(|_range| {
+        Ok(RangeResponse {
+            data: bytes.clone(),
+            total_size: bytes.len() as u64,
+        })
+    })
+    .unwrap();
+    assert_eq!(source.size(), bytes.len() as u64);
+
+    let archive = source.read_zip().unwrap();
+    assert_eq!(archive.entries().count(), 2);
+    let entry = archive.by_name("small.txt").unwrap();
+    assert_eq!(entry.bytes().unwrap(), b"just this, please\n");
+
+    // past the tail, ranges are taken out of the whole resource
+    let entry = archive.by_name("large.bin").unwrap();
+    assert_eq!(entry.bytes().unwrap(), vec![0xab; 1024 * 1024]);
+    let data_offset = entry.data_offset().unwrap();
+    let compressed = entry.raw_reader().unwrap();
+    let mut raw = Vec::new();
+    compressed.take(16).read_to_end(&mut raw).unwrap();
+    assert_eq!(
+        raw,
+        bytes[data_offset as usize..data_offset as usize + 16].to_vec()
+    );
+}
+
+// This helps find bugs in state machines!
+
+struct OneByteReadWrapper<R>(R);
+
+impl<R> io::Read for OneByteReadWrapper<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(&mut buf[..1])
+    }
+}
+
+impl<R> HasCursor for OneByteReadWrapper<R>
+where
+    R: HasCursor,
+{
+    type Cursor<'a> = OneByteReadWrapper<R::Cursor<'a>> where R: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        OneByteReadWrapper(self.0.cursor_at(offset))
+    }
+}
+
+/// Recovers the zip error from an I/O error returned by an entry reader
+fn into_zip_error(e: io::Error) -> Error {
+    match e.get_ref() {
+        Some(inner) if inner.is::<Error>() => *e.into_inner().unwrap().downcast::<Error>().unwrap(),
+        _ => Error::IO(e),
     }
 }
@@ -2,7 +2,7 @@ use std::{io, pin::Pin, task};
 
 use pin_project_lite::pin_project;
 use rc_zip::{
-    fsm::{EntryFsm, FsmResult},
+    fsm::{BufferPool, DecompressLimits, EntryFsm, FsmResult, Validation},
     parse::Entry,
 };
 use tokio::io::{AsyncRead, ReadBuf};
@@ -15,6 +15,7 @@ pin_project! {
         #[pin]
         rd: R,
         fsm: Option<EntryFsm>,
+        pool: Option<BufferPool>,
     }
 }
 
@@ -22,15 +23,49 @@ impl<R> EntryReader<R>
 where
     R: AsyncRead,
 {
-    pub(crate) fn new<F>(entry: &Entry, get_reader: F) -> Self
+    /// The state machine gets an internal buffer of `capacity` bytes (see
+    /// [EntryFsm::with_capacity]).
+    pub(crate) fn new<F>(entry: &Entry, get_reader: F, capacity: usize) -> Self
     where
         F: Fn(u64) -> R,
     {
         Self {
             rd: get_reader(entry.header_offset),
-            fsm: Some(EntryFsm::new(Some(entry.clone()), None)),
+            fsm: Some(EntryFsm::with_capacity(Some(entry.clone()), None, capacity)),
+            pool: None,
         }
     }
+
+    /// Like [Self::new], but takes a buffer from `pool`, and gives it back
+    /// once the entry has been read to the end.
+    pub(crate) fn with_pool<F>(entry: &Entry, get_reader: F, pool: BufferPool) -> Self
+    where
+        F: Fn(u64) -> R,
+    {
+        Self {
+            rd: get_reader(entry.header_offset),
+            fsm: Some(EntryFsm::new(Some(entry.clone()), Some(pool.get()))),
+            pool: Some(pool),
+        }
+    }
+
+    /// See [EntryFsm::with_validation]
+    pub(crate) fn with_validation(mut self, validation: Validation) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_validation(validation));
+        self
+    }
+
+    /// See [EntryFsm::with_strict_descriptors]
+    pub(crate) fn with_strict_descriptors(mut self, strict: bool) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_strict_descriptors(strict));
+        self
+    }
+
+    /// See [EntryFsm::with_limits]
+    pub(crate) fn with_limits(mut self, limits: DecompressLimits) -> Self {
+        self.fsm = self.fsm.map(|fsm| fsm.with_limits(limits));
+        self
+    }
 }
 
 impl<R> AsyncRead for EntryReader<R>
@@ -88,11 +123,68 @@ where
                         .into();
                     }
                 }
-                FsmResult::Done(_) => {
+                FsmResult::Done(buffer) => {
                     // neat!
+                    if let Some(pool) = this.pool.as_ref() {
+                        pool.put(buffer);
+                    }
                 }
             }
             return Ok(()).into();
         }
     }
 }
+
+pin_project! {
+    /// Hashes everything read through it, and hands the CRC-32 and number of
+    /// bytes read to a callback once the inner reader reaches EOF.
+    pub(crate) struct TeeReader<R, F> {
+        #[pin]
+        rd: R,
+        hasher: crc32fast::Hasher,
+        bytes_read: u64,
+        on_eof: Option<F>,
+    }
+}
+
+impl<R, F> TeeReader<R, F>
+where
+    R: AsyncRead,
+    F: FnOnce(u32, u64),
+{
+    pub(crate) fn new(rd: R, on_eof: F) -> Self {
+        Self {
+            rd,
+            hasher: crc32fast::Hasher::new(),
+            bytes_read: 0,
+            on_eof: Some(on_eof),
+        }
+    }
+}
+
+impl<R, F> AsyncRead for TeeReader<R, F>
+where
+    R: AsyncRead,
+    F: FnOnce(u32, u64),
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> task::Poll<std::io::Result<()>> {
+        let this = self.project();
+
+        let before = buf.filled().len();
+        futures_util::ready!(this.rd.poll_read(cx, buf))?;
+        let read = &buf.filled()[before..];
+
+        if read.is_empty() && buf.remaining() > 0 {
+            if let Some(on_eof) = this.on_eof.take() {
+                on_eof(this.hasher.clone().finalize(), *this.bytes_read);
+            }
+        }
+        this.hasher.update(read);
+        *this.bytes_read += read.len() as u64;
+        Ok(()).into()
+    }
+}
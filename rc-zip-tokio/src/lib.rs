@@ -10,11 +10,15 @@
 mod entry_reader;
 mod read_zip;
 
+mod verify;
+pub use verify::VerifyError;
+
 mod streaming_entry_reader;
-pub use streaming_entry_reader::StreamingEntryReader;
+pub use streaming_entry_reader::{StreamedEntry, StreamingEntryReader};
 
 // re-exports
 pub use rc_zip;
 pub use read_zip::{
-    ArchiveHandle, EntryHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize,
+    ArchiveHandle, EntryHandle, HasCursor, NestedZip, ReadZip, ReadZipOptions, ReadZipStreaming,
+    ReadZipWithSize, Subrange, MAX_NESTING_DEPTH,
 };
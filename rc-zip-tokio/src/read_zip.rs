@@ -6,18 +6,26 @@ use std::{
     task::{Context, Poll},
 };
 
-use futures_util::future::BoxFuture;
+use futures_util::{future::BoxFuture, Stream};
 use positioned_io::{RandomAccessFile, ReadAt, Size};
-use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, BufReader, ReadBuf};
 
 use rc_zip::{
-    error::Error,
-    fsm::{ArchiveFsm, EntryFsm, FsmResult},
-    parse::{Archive, Entry},
+    encoding::Encoding,
+    error::{Error, FormatError},
+    fsm::{
+        ArchiveFsm, ArchiveLimits, BufferPool, DecompressLimits, EntryFsm, FsmResult, RecoveryMode,
+        Validation,
+    },
+    parse::{Archive, Entry, SortKey},
 };
 use tracing::trace;
 
-use crate::{entry_reader::EntryReader, StreamingEntryReader};
+use crate::{
+    entry_reader::{EntryReader, TeeReader},
+    verify::into_zip_error,
+    StreamedEntry, StreamingEntryReader,
+};
 
 /// A trait for reading something as a zip archive.
 ///
@@ -27,8 +35,73 @@ pub trait ReadZipWithSize {
     type File: HasCursor;
 
     /// Reads self as a zip archive.
+    ///
+    /// This yields to the runtime regularly while parsing the central
+    /// directory, so that opening huge archives doesn't starve other tasks.
     #[allow(async_fn_in_trait)]
     async fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive, enforcing the given limits while reading
+    /// its metadata.
+    ///
+    /// Use this to bound how much an untrusted archive can make us read
+    /// (see [ArchiveLimits::max_bytes_read] and [ArchiveLimits::deadline]):
+    /// going over budget fails with [Error::Budget].
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_limits(
+        &self,
+        size: u64,
+        limits: ArchiveLimits,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive, with the given options, which also
+    /// apply to the readers of its entries.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_options(
+        &self,
+        size: u64,
+        options: ReadZipOptions,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+}
+
+/// Options for [ReadZipWithSize::read_zip_with_options].
+#[derive(Debug, Clone, Copy)]
+pub struct ReadZipOptions {
+    /// Limits enforced while reading the archive's metadata, see
+    /// [ReadZipWithSize::read_zip_with_limits].
+    pub limits: ArchiveLimits,
+
+    /// Size of the buffer used to read the archive's metadata, and of the
+    /// buffer each entry reader allocates, in bytes.
+    ///
+    /// Lowering this makes it cheaper to have many entry readers open at
+    /// once. It is rounded up to [ArchiveFsm::MIN_BUFFER_CAPACITY] for the
+    /// archive, and to [EntryFsm::MIN_BUFFER_CAPACITY] for entries.
+    pub buffer_size: usize,
+
+    /// Encoding of the entry names and comments, detected when `None` (the
+    /// default). See [ArchiveFsm::with_encoding].
+    pub encoding: Option<Encoding>,
+
+    /// Limits enforced by entry readers while decompressing, see
+    /// [DecompressLimits]. Off by default.
+    pub decompress_limits: DecompressLimits,
+
+    /// What to do with a damaged central directory, see
+    /// [ArchiveFsm::with_recovery]. Strict by default.
+    pub recovery: RecoveryMode,
+}
+
+impl Default for ReadZipOptions {
+    fn default() -> Self {
+        Self {
+            limits: Default::default(),
+            buffer_size: EntryFsm::DEFAULT_BUFFER_CAPACITY,
+            encoding: None,
+            decompress_limits: Default::default(),
+            recovery: Default::default(),
+        }
+    }
 }
 
 /// A zip archive, read asynchronously from a file or other I/O resource.
@@ -43,6 +116,22 @@ pub trait ReadZip {
     /// Reads self as a zip archive.
     #[allow(async_fn_in_trait)]
     async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error>;
+
+    /// Reads self as a zip archive, whose entry readers take their buffers
+    /// from `pool`, and give them back once they've read their entry to the
+    /// end.
+    ///
+    /// This keeps memory usage down when reading many entries, in sequence
+    /// or concurrently.
+    #[allow(async_fn_in_trait)]
+    async fn read_zip_with_pool(
+        &self,
+        pool: &BufferPool,
+    ) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let mut archive = self.read_zip().await?;
+        archive.pool = Some(pool.clone());
+        Ok(archive)
+    }
 }
 
 impl<F> ReadZipWithSize for F
@@ -52,13 +141,41 @@ where
     type File = F;
 
     async fn read_zip_with_size(&self, size: u64) -> Result<ArchiveHandle<'_, F>, Error> {
+        self.read_zip_with_limits(size, Default::default()).await
+    }
+
+    async fn read_zip_with_limits(
+        &self,
+        size: u64,
+        limits: ArchiveLimits,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
+        self.read_zip_with_options(
+            size,
+            ReadZipOptions {
+                limits,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    async fn read_zip_with_options(
+        &self,
+        size: u64,
+        options: ReadZipOptions,
+    ) -> Result<ArchiveHandle<'_, F>, Error> {
         struct CursorState<'a, F: HasCursor + 'a> {
             cursor: <F as HasCursor>::Cursor<'a>,
             offset: u64,
         }
         let mut cstate: Option<CursorState<'_, F>> = None;
 
-        let mut fsm = ArchiveFsm::new(size);
+        let mut fsm = ArchiveFsm::with_capacity(size, options.buffer_size)
+            .with_limits(options.limits)
+            .with_recovery(options.recovery);
+        if let Some(encoding) = options.encoding {
+            fsm = fsm.with_encoding(encoding);
+        }
         loop {
             if let Some(offset) = fsm.wants_read() {
                 let mut cstate_next = match cstate.take() {
@@ -90,7 +207,11 @@ where
 
                         trace!(%read_bytes, "filling fsm");
                         if read_bytes == 0 {
-                            return Err(Error::IO(io::ErrorKind::UnexpectedEof.into()));
+                            return Err(FormatError::TruncatedArchive {
+                                expected_at: offset,
+                                file_size: size,
+                            }
+                            .into());
                         }
                         fsm.fill(read_bytes);
                     }
@@ -103,10 +224,20 @@ where
                     return Ok(ArchiveHandle {
                         file: self,
                         archive,
+                        depth: 0,
+                        buffer_size: options.buffer_size,
+                        decompress_limits: options.decompress_limits,
+                        pool: None,
                     })
                 }
                 FsmResult::Continue(fsm) => fsm,
-            }
+            };
+
+            // each call to process parses at most a buffer's worth of central
+            // directory headers, but reads from memory (or the page cache)
+            // complete right away: without this, opening an archive with
+            // millions of entries would hog the runtime for seconds.
+            tokio::task::yield_now().await;
         }
     }
 }
@@ -127,6 +258,23 @@ impl ReadZip for Vec<u8> {
     }
 }
 
+impl ReadZip for io::Cursor<Vec<u8>> {
+    type File = Self;
+
+    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.get_ref().len() as u64).await
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ReadZip for bytes::Bytes {
+    type File = Self;
+
+    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.len() as u64).await
+    }
+}
+
 impl ReadZip for Arc<RandomAccessFile> {
     type File = Self;
 
@@ -143,6 +291,10 @@ where
 {
     file: &'a F,
     archive: Archive,
+    depth: usize,
+    buffer_size: usize,
+    decompress_limits: DecompressLimits,
+    pool: Option<BufferPool>,
 }
 
 impl<F> Deref for ArchiveHandle<'_, F>
@@ -160,24 +312,61 @@ impl<F> ArchiveHandle<'_, F>
 where
     F: HasCursor,
 {
-    /// Iterate over all files in this zip, read from the central directory.
-    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
-        self.archive.entries().map(move |entry| EntryHandle {
+    /// Gives access to the data of `entry`, one of this archive's entries.
+    fn handle<'h>(&'h self, entry: &'h Entry) -> EntryHandle<'h, F> {
+        EntryHandle {
             file: self.file,
             entry,
-        })
+            depth: self.depth,
+            buffer_size: self.buffer_size,
+            decompress_limits: self.decompress_limits,
+            pool: self.pool.as_ref(),
+        }
+    }
+
+    /// Iterate over all files in this zip, read from the central directory.
+    pub fn entries(&self) -> impl Iterator<Item = EntryHandle<'_, F>> {
+        self.archive.entries().map(move |entry| self.handle(entry))
+    }
+
+    /// Returns all entries, ordered by `key`, see [Archive::entries_sorted_by].
+    pub fn entries_sorted_by(&self, key: SortKey) -> Vec<EntryHandle<'_, F>> {
+        self.archive
+            .entries_sorted_by(key)
+            .into_iter()
+            .map(|entry| self.handle(entry))
+            .collect()
     }
 
     /// Attempts to look up an entry by name. This is usually a bad idea,
     /// as names aren't necessarily normalized in zip archives.
+    ///
+    /// See [Archive::by_name] for how duplicate names are handled.
     pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<EntryHandle<'_, F>> {
+        self.archive.by_name(name).map(|entry| self.handle(entry))
+    }
+
+    /// Returns the first entry (in central directory order) for which
+    /// `predicate` returns true, if any.
+    pub fn find_first<P>(&self, mut predicate: P) -> Option<EntryHandle<'_, F>>
+    where
+        P: FnMut(&Entry) -> bool,
+    {
         self.archive
-            .entries()
-            .find(|&x| x.name == name.as_ref())
-            .map(|entry| EntryHandle {
-                file: self.file,
-                entry,
-            })
+            .find_first(|entry| predicate(entry))
+            .map(|entry| self.handle(entry))
+    }
+
+    /// Returns a reader for the entry at the given index (in central
+    /// directory order), if any.
+    pub fn reader_at(&self, index: usize) -> Option<impl AsyncRead + Unpin + '_> {
+        self.entries().nth(index).map(|entry| entry.entry_reader())
+    }
+
+    /// Returns a reader for the first entry with the given name, if any.
+    /// See [Self::by_name] for caveats.
+    pub fn reader_by_name<N: AsRef<str>>(&self, name: N) -> Option<impl AsyncRead + Unpin + '_> {
+        self.by_name(name).map(|entry| entry.entry_reader())
     }
 }
 
@@ -185,6 +374,10 @@ where
 pub struct EntryHandle<'a, F> {
     file: &'a F,
     entry: &'a Entry,
+    depth: usize,
+    buffer_size: usize,
+    decompress_limits: DecompressLimits,
+    pool: Option<&'a BufferPool>,
 }
 
 impl<F> Deref for EntryHandle<'_, F> {
@@ -201,7 +394,36 @@ where
 {
     /// Returns a reader for the entry.
     pub fn reader(&self) -> impl AsyncRead + Unpin + '_ {
-        EntryReader::new(self.entry, |offset| self.file.cursor_at(offset))
+        self.entry_reader()
+    }
+
+    pub(crate) fn entry_reader(&self) -> EntryReader<F::Cursor<'a>> {
+        let get_reader = |offset| self.file.cursor_at(offset);
+        let reader = match self.pool {
+            Some(pool) => EntryReader::with_pool(self.entry, get_reader, pool.clone()),
+            None => EntryReader::new(self.entry, get_reader, self.buffer_size),
+        };
+        reader.with_limits(self.decompress_limits)
+    }
+
+    /// Returns a reader for the entry that doesn't check its size and CRC-32
+    /// checksum, and instead passes the CRC-32 and size of what was read to
+    /// `on_eof` once the end of the entry is reached.
+    ///
+    /// This is for callers that would rather report a mismatch than fail.
+    /// Other errors (like corrupted compressed data) are still returned.
+    pub fn tee_reader<C>(&self, on_eof: C) -> impl AsyncRead + Unpin + '_
+    where
+        C: FnOnce(u32, u64) + Unpin + 'a,
+    {
+        let rd = self.entry_reader().with_validation(Validation::Skip);
+        TeeReader::new(rd, on_eof)
+    }
+
+    /// Returns a buffered reader for the entry, which is handy for
+    /// line-oriented text entries (see [tokio::io::AsyncBufReadExt::lines]).
+    pub fn buf_reader(&self) -> impl AsyncBufRead + Unpin + '_ {
+        BufReader::new(self.reader())
     }
 
     /// Reads the entire entry into a vector.
@@ -210,6 +432,25 @@ where
         self.reader().read_to_end(&mut v).await?;
         Ok(v)
     }
+
+    /// Reads the entire entry into memory, so that it can be read as a zip
+    /// archive of its own with [ReadZip::read_zip].
+    ///
+    /// Archives opened this way keep track of how deeply they're nested:
+    /// going more than [MAX_NESTING_DEPTH] levels deep returns
+    /// [Error::NestingTooDeep], to guard against zip bombs made of nested
+    /// archives.
+    pub async fn open_nested_zip(&self) -> Result<NestedZip, Error> {
+        let depth = self.depth + 1;
+        if depth > MAX_NESTING_DEPTH {
+            return Err(Error::NestingTooDeep {
+                max_depth: MAX_NESTING_DEPTH,
+            });
+        }
+
+        let data = self.bytes().await.map_err(into_zip_error)?;
+        Ok(NestedZip { data, depth })
+    }
 }
 
 /// A sliceable I/O resource: we can ask for an [AsyncRead] at a given offset.
@@ -229,7 +470,9 @@ impl HasCursor for &[u8] {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
     }
 }
 
@@ -239,7 +482,37 @@ impl HasCursor for Vec<u8> {
         Self: 'a;
 
     fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
-        &self[offset.try_into().unwrap()..]
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
+    }
+}
+
+/// The whole vector is read as a zip archive, regardless of the cursor's
+/// position.
+impl HasCursor for io::Cursor<Vec<u8>> {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // reading past the end yields nothing, rather than panicking
+        self.get_ref()
+            .get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl HasCursor for bytes::Bytes {
+    type Cursor<'a> = &'a [u8]
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        // reading past the end yields nothing, rather than panicking
+        self.get(offset.try_into().unwrap_or(usize::MAX)..)
+            .unwrap_or_default()
     }
 }
 
@@ -262,6 +535,92 @@ impl HasCursor for Arc<RandomAccessFile> {
     }
 }
 
+/// A window `[offset, offset + length)` into a larger [HasCursor], which can
+/// be read as a zip archive of its own: offsets recorded in the archive are
+/// resolved relative to `offset`.
+///
+/// This is useful for container formats that embed a zip file at a known
+/// position, like asset packs.
+pub struct Subrange<'a, F> {
+    file: &'a F,
+    offset: u64,
+    length: u64,
+}
+
+impl<'a, F> Subrange<'a, F>
+where
+    F: HasCursor,
+{
+    /// Creates a view of `length` bytes of `file`, starting at `offset`.
+    pub fn new(file: &'a F, offset: u64, length: u64) -> Self {
+        Self {
+            file,
+            offset,
+            length,
+        }
+    }
+}
+
+impl<F> HasCursor for Subrange<'_, F>
+where
+    F: HasCursor,
+{
+    type Cursor<'a> = tokio::io::Take<F::Cursor<'a>>
+    where
+        Self: 'a;
+
+    fn cursor_at(&self, offset: u64) -> Self::Cursor<'_> {
+        self.file
+            .cursor_at(self.offset + offset)
+            .take(self.length.saturating_sub(offset))
+    }
+}
+
+impl<F> ReadZip for Subrange<'_, F>
+where
+    F: HasCursor,
+{
+    type File = Self;
+
+    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        self.read_zip_with_size(self.length).await
+    }
+}
+
+/// How many levels of zip files within zip files [EntryHandle::open_nested_zip]
+/// is willing to open.
+pub const MAX_NESTING_DEPTH: usize = 8;
+
+/// A zip file stored as an entry of another zip file, read into memory by
+/// [EntryHandle::open_nested_zip].
+pub struct NestedZip {
+    data: Vec<u8>,
+    depth: usize,
+}
+
+impl NestedZip {
+    /// How deeply this zip file is nested: 1 for an entry of a top-level
+    /// archive, 2 for an entry of that entry, etc.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The raw contents of the nested zip file
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl ReadZip for NestedZip {
+    type File = Vec<u8>;
+
+    async fn read_zip(&self) -> Result<ArchiveHandle<'_, Self::File>, Error> {
+        let mut archive = self.data.read_zip().await?;
+        archive.depth = self.depth;
+        Ok(archive)
+    }
+}
+
 struct ARAFCCore {
     // offset we're reading from in the file
     file_offset: u64,
@@ -379,6 +738,39 @@ where
     async fn stream_zip_entries_throwing_caution_to_the_wind(
         self,
     ) -> Result<StreamingEntryReader<R>, Error>;
+
+    /// Like [Self::stream_zip_entries_throwing_caution_to_the_wind], but as
+    /// a [Stream] of entries, to be used with [StreamExt](futures_util::StreamExt)
+    /// and friends:
+    ///
+    /// ```no_run
+    /// # async fn f(rd: impl tokio::io::AsyncRead + Unpin) -> Result<(), rc_zip_tokio::rc_zip::error::Error> {
+    /// use futures_util::StreamExt;
+    /// use rc_zip_tokio::{rc_zip::fsm::DecompressLimits, ReadZipStreaming};
+    ///
+    /// let limits = DecompressLimits {
+    ///     max_output_bytes: Some(64 * 1024 * 1024),
+    ///     ..Default::default()
+    /// };
+    /// let mut entries = rd.stream_zip_entries_as_stream(limits);
+    /// while let Some(entry) = entries.next().await {
+    ///     let entry = entry?;
+    ///     println!("{}: {} bytes", entry.name, entry.data().len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Since an entry must be read to the end before moving on to the next
+    /// one, each entry is read into memory as a whole: use a
+    /// [StreamingEntryReader] directly for large entries. `limits` apply to
+    /// every entry, and are what keeps an untrusted stream from making this
+    /// allocate as much as it likes: going over them ends the stream with
+    /// [Error::LimitExceeded]. The stream ends after the first error.
+    fn stream_zip_entries_as_stream(
+        self,
+        limits: DecompressLimits,
+    ) -> impl Stream<Item = Result<StreamedEntry, Error>> + Unpin;
 }
 
 impl<R> ReadZipStreaming<R> for R
@@ -386,21 +778,66 @@ where
     R: AsyncRead + Unpin,
 {
     async fn stream_zip_entries_throwing_caution_to_the_wind(
-        mut self,
+        self,
     ) -> Result<StreamingEntryReader<Self>, Error> {
-        let mut fsm = EntryFsm::new(None, None);
+        first_streaming_entry(self, Default::default()).await
+    }
 
-        loop {
-            if fsm.wants_read() {
-                let n = self.read(fsm.space()).await?;
-                trace!("read {} bytes into buf for first zip entry", n);
-                fsm.fill(n);
-            }
+    fn stream_zip_entries_as_stream(
+        self,
+        limits: DecompressLimits,
+    ) -> impl Stream<Item = Result<StreamedEntry, Error>> + Unpin {
+        enum State<R> {
+            Start(R),
+            Next(Box<StreamingEntryReader<R>>),
+            Done,
+        }
 
-            if let Some(entry) = fsm.process_till_header()? {
-                let entry = entry.clone();
-                return Ok(StreamingEntryReader::new(fsm, entry, self));
-            }
+        let stream =
+            futures_util::stream::try_unfold(State::Start(self), move |state| async move {
+                let mut reader = match state {
+                    State::Start(rd) => first_streaming_entry(rd, limits).await?,
+                    State::Next(reader) => *reader,
+                    State::Done => return Ok(None),
+                };
+
+                let mut data = Vec::new();
+                reader
+                    .read_to_end(&mut data)
+                    .await
+                    .map_err(into_zip_error)?;
+                let entry = reader.entry().clone();
+                let next = match reader.finish().await? {
+                    Some(reader) => State::Next(Box::new(reader)),
+                    None => State::Done,
+                };
+                Ok::<_, Error>(Some((StreamedEntry::new(entry, data), next)))
+            });
+        Box::pin(stream)
+    }
+}
+
+/// Reads up to the first local header of `rd`, for a [StreamingEntryReader]
+/// that enforces `limits` on every entry.
+async fn first_streaming_entry<R>(
+    mut rd: R,
+    limits: DecompressLimits,
+) -> Result<StreamingEntryReader<R>, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut fsm = EntryFsm::new(None, None).with_limits(limits);
+
+    loop {
+        if fsm.wants_read() {
+            let n = rd.read(fsm.space()).await?;
+            trace!("read {} bytes into buf for first zip entry", n);
+            fsm.fill(n);
+        }
+
+        if let Some(entry) = fsm.process_till_header()? {
+            let entry = entry.clone();
+            return Ok(StreamingEntryReader::new(fsm, entry, rd, limits));
         }
     }
 }
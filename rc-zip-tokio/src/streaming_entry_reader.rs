@@ -1,11 +1,11 @@
 use oval::Buffer;
 use pin_project_lite::pin_project;
 use rc_zip::{
-    error::{Error, FormatError},
-    fsm::{EntryFsm, FsmResult},
+    error::Error,
+    fsm::{DecompressLimits, EntryFsm, FsmResult},
     parse::Entry,
 };
-use std::{io, pin::Pin, task};
+use std::{io, ops::Deref, pin::Pin, task};
 use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
 use tracing::trace;
 
@@ -20,6 +20,8 @@ pin_project! {
         #[pin]
         rd: R,
         state: State,
+        // applied to the following entries too
+        limits: DecompressLimits,
     }
 }
 
@@ -41,11 +43,12 @@ impl<R> StreamingEntryReader<R>
 where
     R: AsyncRead,
 {
-    pub(crate) fn new(fsm: EntryFsm, entry: Entry, rd: R) -> Self {
+    pub(crate) fn new(fsm: EntryFsm, entry: Entry, rd: R, limits: DecompressLimits) -> Self {
         Self {
             entry,
             rd,
             state: State::Reading { fsm },
+            limits,
         }
     }
 }
@@ -65,6 +68,7 @@ where
 
         match std::mem::take(this.state) {
             State::Reading { mut fsm } => {
+                let mut filled = 0;
                 if fsm.wants_read() {
                     trace!("fsm wants read");
                     let mut buf = ReadBuf::new(fsm.space());
@@ -79,6 +83,7 @@ where
 
                     trace!("giving fsm {} bytes from rd", n);
                     fsm.fill(n);
+                    filled = n;
                 } else {
                     trace!("fsm does not want read");
                 }
@@ -91,7 +96,7 @@ where
                         if outcome.bytes_written > 0 {
                             trace!("bytes have been written");
                             buf.advance(outcome.bytes_written);
-                        } else if outcome.bytes_read == 0 {
+                        } else if outcome.bytes_read == 0 && filled == 0 {
                             trace!("no bytes have been written or read");
                             // that's EOF, baby!
                         } else {
@@ -145,31 +150,41 @@ where
             }
             State::Finished { remain } => {
                 // parse the next entry, if any
-                let mut fsm = EntryFsm::new(None, Some(remain));
+                let mut fsm = EntryFsm::new(None, Some(remain)).with_limits(self.limits);
 
                 loop {
+                    let mut eof = false;
                     if fsm.wants_read() {
                         let n = self.rd.read(fsm.space()).await?;
                         trace!("read {} bytes into buf for first zip entry", n);
                         fsm.fill(n);
+                        eof = n == 0;
                     }
 
-                    match fsm.process_till_header() {
-                        Ok(Some(entry)) => {
+                    if fsm.reached_end_of_entries() {
+                        trace!("reached the central directory, no entries left");
+                        return Ok(None);
+                    }
+
+                    match fsm.process_till_header()? {
+                        Some(entry) => {
                             let entry = entry.clone();
-                            return Ok(Some(StreamingEntryReader::new(fsm, entry, self.rd)));
+                            return Ok(Some(StreamingEntryReader::new(
+                                fsm,
+                                entry,
+                                self.rd,
+                                self.limits,
+                            )));
+                        }
+                        None if eof => {
+                            // the stream ended without a central directory:
+                            // there's nothing left to read either way.
+                            trace!("reached end of stream, no entries left");
+                            return Ok(None);
                         }
-                        Ok(None) => {
+                        None => {
                             // needs more turns
                         }
-                        Err(e) => match e {
-                            Error::Format(FormatError::InvalidLocalHeader) => {
-                                // we probably reached the end of central directory!
-                                // TODO: we should probably check for the end of central directory
-                                return Ok(None);
-                            }
-                            _ => return Err(e),
-                        },
                     }
                 }
             }
@@ -177,3 +192,41 @@ where
         }
     }
 }
+
+/// An entry read from a [Stream](futures_util::Stream) of entries, along
+/// with its data: see
+/// [ReadZipStreaming::stream_zip_entries_as_stream](crate::ReadZipStreaming::stream_zip_entries_as_stream).
+pub struct StreamedEntry {
+    entry: Entry,
+    data: Vec<u8>,
+}
+
+impl StreamedEntry {
+    pub(crate) fn new(entry: Entry, data: Vec<u8>) -> Self {
+        Self { entry, data }
+    }
+
+    /// Return entry information
+    #[inline(always)]
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    /// The entry's (decompressed) data
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consumes the entry, returning its (decompressed) data
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl Deref for StreamedEntry {
+    type Target = Entry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
@@ -0,0 +1,75 @@
+use futures_util::stream::{self, StreamExt};
+use rc_zip::error::Error;
+use std::fmt;
+use tokio::io;
+
+use crate::{ArchiveHandle, HasCursor};
+
+/// How many entries [ArchiveHandle::verify] checks at the same time.
+const VERIFY_CONCURRENCY: usize = 8;
+
+/// An error found by [ArchiveHandle::verify].
+#[derive(Debug)]
+pub struct VerifyError {
+    /// Name of the entry that failed verification
+    pub entry_name: String,
+
+    /// What went wrong
+    pub error: Error,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.entry_name, self.error)
+    }
+}
+
+impl std::error::Error for VerifyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<F> ArchiveHandle<'_, F>
+where
+    F: HasCursor,
+{
+    /// Decompresses every entry of the archive, discarding the output, to
+    /// check their sizes and CRC32 checksums.
+    ///
+    /// Data descriptors that contradict the central directory are errors
+    /// too, see
+    /// [EntryFsm::with_strict_descriptors](rc_zip::fsm::EntryFsm::with_strict_descriptors).
+    ///
+    /// Up to 8 entries are checked concurrently. Returns the first error
+    /// found, along with the name of the entry it was found in.
+    pub async fn verify(&self) -> Result<(), VerifyError> {
+        let mut results = stream::iter(self.entries())
+            .map(|entry| async move {
+                let mut reader = entry.entry_reader().with_strict_descriptors(true);
+                io::copy(&mut reader, &mut io::sink())
+                    .await
+                    .map_err(|e| VerifyError {
+                        entry_name: entry.name.clone(),
+                        error: into_zip_error(e),
+                    })
+            })
+            .buffer_unordered(VERIFY_CONCURRENCY);
+
+        while let Some(res) = results.next().await {
+            res?;
+        }
+        Ok(())
+    }
+}
+
+/// Entry readers report zip errors wrapped in [std::io::Error]: unwrap them.
+pub(crate) fn into_zip_error(e: std::io::Error) -> Error {
+    match e.get_ref() {
+        Some(inner) if inner.is::<Error>() => {
+            let inner = e.into_inner().unwrap();
+            *inner.downcast::<Error>().unwrap()
+        }
+        _ => Error::IO(e),
+    }
+}
@@ -1,13 +1,25 @@
 use positioned_io::{RandomAccessFile, Size};
 use rc_zip::{
     corpus::{self, zips_dir, Case, Files},
-    error::Error,
-    parse::Archive,
+    error::{Error, FormatError},
+    fsm::{ArchiveLimits, BufferPool, DecompressLimits},
+    parse::{Archive, Method},
 };
-use rc_zip_tokio::{ArchiveHandle, HasCursor, ReadZip, ReadZipStreaming, ReadZipWithSize};
-use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use rc_zip_tokio::{
+    ArchiveHandle, EntryHandle, HasCursor, ReadZip, ReadZipOptions, ReadZipStreaming,
+    ReadZipWithSize, Subrange, MAX_NESTING_DEPTH,
+};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, ReadBuf};
 
-use std::{pin::Pin, sync::Arc, task};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task,
+};
 
 async fn check_case<F: HasCursor>(test: &Case, archive: Result<ArchiveHandle<'_, F>, Error>) {
     corpus::check_case(test, archive.as_ref().map(|ar| -> &Archive { ar }));
@@ -37,6 +49,24 @@ async fn read_from_slice() {
     assert_eq!(archive.entries().count(), 2);
 }
 
+#[tokio::test]
+async fn read_from_memory() {
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+
+    // the cursor's position doesn't matter
+    let mut cursor = std::io::Cursor::new(bytes.clone());
+    cursor.set_position(100);
+    let archive = cursor.read_zip().await.unwrap();
+    assert_eq!(archive.entries().count(), 2);
+
+    #[cfg(feature = "bytes")]
+    {
+        let bytes = bytes::Bytes::from(bytes);
+        let archive = bytes.read_zip().await.unwrap();
+        assert_eq!(archive.entries().count(), 2);
+    }
+}
+
 #[tokio::test]
 async fn read_from_file() {
     corpus::install_test_subscriber();
@@ -80,21 +110,140 @@ async fn streaming() {
             .stream_zip_entries_throwing_caution_to_the_wind()
             .await
             .unwrap();
+        let mut num_entries = 1;
         loop {
             let mut v = vec![];
             let n = entry.read_to_end(&mut v).await.unwrap();
             tracing::trace!("entry {} read {} bytes", entry.entry().name, n);
 
+            // reaching the central directory is a clean end, not an error
             match entry.finish().await.unwrap() {
-                Some(next) => entry = next,
+                Some(next) => {
+                    entry = next;
+                    num_entries += 1;
+                }
                 None => break,
             }
         }
+        assert_eq!(num_entries, case.files.len(), "{}", case.name);
+
+        drop(guarded_path)
+    }
+}
+
+#[tokio::test]
+async fn streaming_as_stream() {
+    use futures_util::StreamExt;
+
+    corpus::install_test_subscriber();
+
+    for case in corpus::streaming_test_cases() {
+        let guarded_path = case.absolute_path();
+        let file = tokio::fs::File::open(&guarded_path.path).await.unwrap();
+
+        let entries: Vec<_> = file
+            .stream_zip_entries_as_stream(Default::default())
+            .map(|entry| entry.unwrap())
+            .collect()
+            .await;
+        assert_eq!(entries.len(), case.files.len(), "{}", case.name);
+
+        // every entry should have the same contents as when read through
+        // the central directory
+        let file = Arc::new(RandomAccessFile::open(&guarded_path.path).unwrap());
+        let archive = file.read_zip().await.unwrap();
+        for streamed in &entries {
+            let entry = archive
+                .by_name(&streamed.entry().name)
+                .unwrap_or_else(|| panic!("{}: no entry {}", case.name, streamed.entry().name));
+            let expected = entry.bytes().await.unwrap();
+            assert_eq!(streamed.data(), &expected[..], "{}", streamed.entry().name);
+        }
 
         drop(guarded_path)
     }
 }
 
+#[tokio::test]
+async fn all_compression_methods() {
+    corpus::install_test_subscriber();
+
+    // every method with a decoder enabled has a `found-me-<method>.zip` case
+    // in the corpus, all with the same contents
+    let expected: Vec<(&str, Method)> = vec![
+        #[cfg(feature = "lzma")]
+        ("found-me-lzma.zip", Method::Lzma),
+        #[cfg(feature = "deflate64")]
+        ("found-me-deflate64.zip", Method::Deflate64),
+        #[cfg(feature = "bzip2")]
+        ("found-me-bzip2.zip", Method::Bzip2),
+        #[cfg(feature = "zstd")]
+        ("found-me-zstd.zip", Method::Zstd),
+        #[cfg(feature = "xz")]
+        ("found-me-xz.zip", Method::Xz),
+    ];
+
+    let cases: Vec<Case> = corpus::test_cases()
+        .into_iter()
+        .filter(|case| case.name.starts_with("found-me-"))
+        .collect();
+    assert_eq!(
+        cases.iter().map(|case| case.name).collect::<Vec<_>>(),
+        expected.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+    );
+
+    for (case, (_, method)) in cases.iter().zip(expected) {
+        tracing::info!("============ testing {}", case.name);
+
+        let bytes = case.bytes();
+        let archive = bytes.read_zip().await;
+        for entry in archive.as_ref().unwrap().entries() {
+            assert_eq!(entry.method, method, "{} uses the wrong method", case.name);
+        }
+        check_case(case, archive).await;
+    }
+}
+
+#[tokio::test]
+async fn read_from_subrange() {
+    corpus::install_test_subscriber();
+
+    let cases = corpus::test_cases();
+    let case = cases.iter().find(|x| x.name == "test.zip").unwrap();
+    let zip = case.bytes();
+
+    // embed the zip in a larger blob, with data on both sides
+    let offset = 1000;
+    let mut blob = vec![0xAAu8; offset];
+    blob.extend_from_slice(&zip);
+    blob.extend(std::iter::repeat(0xBB).take(500));
+
+    let subrange = Subrange::new(&blob, offset as u64, zip.len() as u64);
+    let archive = subrange.read_zip().await;
+    check_case(case, archive).await;
+}
+
+#[tokio::test]
+async fn verify() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    bytes.read_zip().await.unwrap().verify().await.unwrap();
+
+    // corrupt the CRC32 of `test.txt` in the central directory
+    let mut bytes = bytes;
+    let header_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[header_offset + 16] ^= 0xff;
+
+    let archive = bytes.read_zip().await.unwrap();
+    let err = archive.verify().await.unwrap_err();
+    assert_eq!(err.entry_name, "test.txt");
+    assert!(
+        matches!(err.error, Error::Format(FormatError::WrongChecksum { .. })),
+        "unexpected error: {err}"
+    );
+}
+
 // This helps find bugs in state machines!
 
 struct OneByteReadWrapper<R>(R);
@@ -128,3 +277,303 @@ where
         OneByteReadWrapper(self.0.cursor_at(offset))
     }
 }
+
+#[tokio::test]
+async fn nested_zip() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("nested.zip")).unwrap();
+    let archive = bytes.read_zip().await.unwrap();
+    let nested = archive
+        .by_name("test.zip")
+        .unwrap()
+        .open_nested_zip()
+        .await
+        .unwrap();
+    assert_eq!(nested.depth(), 1);
+
+    let nested_archive = nested.read_zip().await.unwrap();
+    let names: Vec<_> = nested_archive.entries().map(|e| e.name.clone()).collect();
+    assert_eq!(names, vec!["test.txt", "gophercolor16x16.png"]);
+    let test_txt = nested_archive
+        .by_name("test.txt")
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(test_txt, b"This is a test text file.\n");
+
+    // not every entry is a zip
+    let err = archive
+        .by_name("readme.txt")
+        .unwrap()
+        .open_nested_zip()
+        .await
+        .unwrap()
+        .read_zip()
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        Error::Format(FormatError::DirectoryEndSignatureNotFound)
+    ));
+}
+
+#[tokio::test]
+async fn nested_zip_too_deep() {
+    corpus::install_test_subscriber();
+
+    /// Opens `inner.zip` entries recursively, returning the deepest level
+    /// reached.
+    fn open_innermost(
+        entry: EntryHandle<'_, Vec<u8>>,
+    ) -> Pin<Box<dyn Future<Output = Result<usize, Error>> + '_>> {
+        Box::pin(async move {
+            let nested = entry.open_nested_zip().await?;
+            let archive = nested.read_zip().await?;
+            match archive.by_name("inner.zip") {
+                Some(entry) => open_innermost(entry).await,
+                None => Ok(nested.depth()),
+            }
+        })
+    }
+
+    // ten levels of `inner.zip` within `inner.zip`
+    let bytes = std::fs::read(zips_dir().join("nested-too-deep.zip")).unwrap();
+    let archive = bytes.read_zip().await.unwrap();
+    let entry = archive.by_name("inner.zip").unwrap();
+    match open_innermost(entry).await {
+        Err(Error::NestingTooDeep { max_depth }) => assert_eq!(max_depth, MAX_NESTING_DEPTH),
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(depth) => panic!("should have failed, reached depth {depth}"),
+    }
+}
+
+#[tokio::test]
+async fn truncated_archive() {
+    corpus::install_test_subscriber();
+
+    // a partial download: we know how large the file should be, but only
+    // have part of it
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let size = bytes.len() as u64;
+    bytes.truncate(bytes.len() - 300);
+
+    match bytes.read_zip_with_size(size).await {
+        Err(Error::Format(FormatError::TruncatedArchive {
+            expected_at,
+            file_size,
+        })) => {
+            assert_eq!(expected_at, bytes.len() as u64);
+            assert_eq!(file_size, size);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[tokio::test]
+async fn buf_reader() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let archive = bytes.read_zip().await.unwrap();
+    let entry = archive.by_name("test.txt").unwrap();
+    let mut lines = entry.buf_reader().lines();
+    let mut read = vec![];
+    while let Some(line) = lines.next_line().await.unwrap() {
+        read.push(line);
+    }
+    assert_eq!(read, vec!["This is a test text file."]);
+}
+
+#[tokio::test]
+async fn tee_reader() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let good_crc = bytes
+        .read_zip()
+        .await
+        .unwrap()
+        .by_name("test.txt")
+        .unwrap()
+        .crc32;
+
+    // corrupt the CRC32 of `test.txt` in the central directory
+    let mut corrupted = bytes.clone();
+    let header_offset = corrupted
+        .windows(4)
+        .position(|w| w == b"PK\x01\x02")
+        .unwrap();
+    corrupted[header_offset + 16] ^= 0xff;
+
+    for bytes in [bytes, corrupted] {
+        let archive = bytes.read_zip().await.unwrap();
+        let entry = archive.by_name("test.txt").unwrap();
+
+        let mut reported = None;
+        let mut contents = vec![];
+        entry
+            .tee_reader(|crc32, size| reported = Some((crc32, size)))
+            .read_to_end(&mut contents)
+            .await
+            .unwrap();
+        assert_eq!(contents, b"This is a test text file.\n");
+        assert_eq!(reported, Some((good_crc, contents.len() as u64)));
+    }
+}
+
+#[tokio::test]
+async fn read_budget() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("meta.zip")).unwrap();
+    let size = bytes.len() as u64;
+
+    // looking for the end of central directory record reads the whole file,
+    // reading the central directory then goes over budget
+    let limits = ArchiveLimits {
+        max_bytes_read: Some(size),
+        ..Default::default()
+    };
+    match bytes.read_zip_with_limits(size, limits).await {
+        Err(Error::Budget { bytes_read }) => assert!(bytes_read > size),
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have gone over budget"),
+    }
+
+    // a deadline that has already passed stops reading right away
+    let limits = ArchiveLimits {
+        deadline: Some(std::time::Instant::now()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        bytes.read_zip_with_limits(size, limits).await,
+        Err(Error::Budget { .. })
+    ));
+
+    let limits = ArchiveLimits {
+        max_bytes_read: Some(4 * size),
+        ..Default::default()
+    };
+    let archive = bytes.read_zip_with_limits(size, limits).await.unwrap();
+    assert_eq!(archive.len(), 33);
+}
+
+#[tokio::test]
+async fn buffer_pool() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let pool = BufferPool::default();
+    let archive = bytes.read_zip_with_pool(&pool).await.unwrap();
+
+    for entry in archive.entries() {
+        let contents = entry.bytes().await.unwrap();
+        assert_eq!(contents.len() as u64, entry.uncompressed_size);
+        // the buffer is back in the pool for the next entry
+        assert_eq!(pool.len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn decompress_limits() {
+    corpus::install_test_subscriber();
+
+    // 1MiB of zeroes, deflated to about 1KiB
+    let bytes = std::fs::read(zips_dir().join("zeroes.zip")).unwrap();
+    let options = ReadZipOptions {
+        decompress_limits: DecompressLimits {
+            max_ratio: Some(100),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let archive = bytes
+        .read_zip_with_options(bytes.len() as u64, options)
+        .await
+        .unwrap();
+    let entry = archive.entries().next().unwrap();
+    let err = entry.bytes().await.unwrap_err();
+    assert!(
+        err.get_ref().is_some_and(|e| matches!(
+            e.downcast_ref::<Error>(),
+            Some(Error::LimitExceeded { .. })
+        )),
+        "expected ratio limit to be hit, got {err:?}"
+    );
+}
+
+#[tokio::test]
+async fn streaming_as_stream_limits() {
+    use futures_util::StreamExt;
+
+    corpus::install_test_subscriber();
+
+    // entries aren't read into memory past the limits
+    let bytes = std::fs::read(zips_dir().join("zeroes.zip")).unwrap();
+    let limits = DecompressLimits {
+        max_output_bytes: Some(64 * 1024),
+        ..Default::default()
+    };
+    let entries: Vec<_> = (&bytes[..])
+        .stream_zip_entries_as_stream(limits)
+        .collect()
+        .await;
+    assert_eq!(entries.len(), 1);
+    assert!(
+        matches!(entries[0], Err(Error::LimitExceeded { uncompressed_bytes, .. }) if uncompressed_bytes < 1024 * 1024),
+        "expected output limit to be hit"
+    );
+}
+
+/// test.zip, with the central directory header of its first entry repeated
+/// `count` times.
+fn many_entries_zip(count: u16) -> Vec<u8> {
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+
+    let eocd_offset = bytes.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    let header_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    let header_len = 46
+        + u16_at(header_offset + 28) as usize
+        + u16_at(header_offset + 30) as usize
+        + u16_at(header_offset + 32) as usize;
+
+    let mut zip = bytes[..header_offset].to_vec();
+    for _ in 0..count {
+        zip.extend_from_slice(&bytes[header_offset..header_offset + header_len]);
+    }
+    let mut eocd = bytes[eocd_offset..].to_vec();
+    eocd[8..10].copy_from_slice(&count.to_le_bytes());
+    eocd[10..12].copy_from_slice(&count.to_le_bytes());
+    eocd[12..16].copy_from_slice(&((header_len * count as usize) as u32).to_le_bytes());
+    zip.extend_from_slice(&eocd);
+    zip
+}
+
+#[tokio::test]
+async fn read_zip_yields() {
+    let bytes = many_entries_zip(60_000);
+
+    // on the single-threaded runtime, this only gets to run when read_zip
+    // yields
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let competitor = tokio::spawn({
+        let ticks = ticks.clone();
+        async move {
+            loop {
+                ticks.fetch_add(1, Ordering::Relaxed);
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+
+    let archive = bytes.read_zip().await.unwrap();
+    assert_eq!(archive.entries().count(), 60_000);
+    // the central directory takes several buffers' worth of headers
+    assert!(ticks.load(Ordering::Relaxed) > 4);
+    competitor.abort();
+}
@@ -11,6 +11,7 @@ use tracing::span;
 use crate::{
     encoding::Encoding,
     error::Error,
+    fsm::{ArchiveFsm, EntryFsm, FsmResult},
     parse::{Archive, Entry, EntryKind},
 };
 
@@ -20,6 +21,7 @@ pub struct Case {
     pub comment: Option<&'static str>,
     pub files: Files,
     pub error: Option<Error>,
+    pub prefix_len: u64,
 }
 
 pub enum Files {
@@ -28,12 +30,16 @@ pub enum Files {
 }
 
 impl Files {
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         match self {
             Self::ExhaustiveList(list) => list.len(),
             Self::NumFiles(n) => *n,
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl Default for Case {
@@ -44,6 +50,7 @@ impl Default for Case {
             comment: None,
             files: Files::NumFiles(0),
             error: None,
+            prefix_len: 0,
         }
     }
 }
@@ -86,6 +93,8 @@ pub struct CaseFile {
     pub name: &'static str,
     pub mode: Option<u32>,
     pub modified: Option<DateTime<Utc>>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
     pub content: FileContent,
 }
 
@@ -101,6 +110,8 @@ impl Default for CaseFile {
             name: "default",
             mode: None,
             modified: None,
+            uid: None,
+            gid: None,
             content: FileContent::Unchecked,
         }
     }
@@ -143,6 +154,32 @@ pub fn test_cases() -> Vec<Case> {
                 ),
                 modified: Some(date((2012, 8, 10), (14, 33, 32), 0, time_zone(0)).unwrap()),
                 mode: Some(0o644),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            // zip64.zip, minus the end of central directory record
+            name: "zip64-without-eocd.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "README",
+                content: FileContent::Bytes(
+                    "This small file is in ZIP64 format.\n".as_bytes().into(),
+                ),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            // zip64.zip, minus the end of central directory record and the
+            // zip64 end of central directory locator
+            name: "zip64-without-eocd-or-locator.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "README",
+                content: FileContent::Bytes(
+                    "This small file is in ZIP64 format.\n".as_bytes().into(),
+                ),
+                ..Default::default()
             }]),
             ..Default::default()
         },
@@ -156,16 +193,43 @@ pub fn test_cases() -> Vec<Case> {
                     content: FileContent::Bytes("This is a test text file.\n".as_bytes().into()),
                     modified: Some(date((2010, 9, 5), (12, 12, 1), 0, time_zone(10)).unwrap()),
                     mode: Some(0o644),
+                    ..Default::default()
                 },
                 CaseFile {
                     name: "gophercolor16x16.png",
                     content: FileContent::File("gophercolor16x16.png"),
                     modified: Some(date((2010, 9, 5), (15, 52, 58), 0, time_zone(10)).unwrap()),
                     mode: Some(0o644),
+                    ..Default::default()
                 },
             ]),
             ..Default::default()
         },
+        Case {
+            // test.zip, after an ELF header standing in for an installer
+            // binary, like mojosetup does
+            name: "sfx-elf.zip",
+            comment: Some("This is a zipfile comment."),
+            expected_encoding: Some(Encoding::Utf8),
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "test.txt",
+                    content: FileContent::Bytes("This is a test text file.\n".as_bytes().into()),
+                    modified: Some(date((2010, 9, 5), (12, 12, 1), 0, time_zone(10)).unwrap()),
+                    mode: Some(0o644),
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "gophercolor16x16.png",
+                    content: FileContent::File("gophercolor16x16.png"),
+                    modified: Some(date((2010, 9, 5), (15, 52, 58), 0, time_zone(10)).unwrap()),
+                    mode: Some(0o644),
+                    ..Default::default()
+                },
+            ]),
+            prefix_len: 1024,
+            ..Default::default()
+        },
         Case {
             name: "cp-437.zip",
             expected_encoding: Some(Encoding::Cp437),
@@ -190,6 +254,135 @@ pub fn test_cases() -> Vec<Case> {
             ]),
             ..Default::default()
         },
+        Case {
+            // the last name isn't valid Shift-JIS, which shouldn't prevent
+            // reading the archive
+            name: "shift-jis-bad-name.zip",
+            expected_encoding: Some(Encoding::ShiftJis),
+            files: Files::NumFiles(81),
+            ..Default::default()
+        },
+        Case {
+            name: "gbk.zip",
+            expected_encoding: Some(Encoding::Gbk),
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "中文文件夹/",
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "中文文件夹/测试文档.txt",
+                    content: FileContent::Bytes(
+                        b"\xc4\xe3\xba\xc3\xa3\xac\xca\xc0\xbd\xe7\n".to_vec(),
+                    ),
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "中文文件夹/新建文本文档.txt",
+                    content: FileContent::Bytes(b"hello\n".to_vec()),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        Case {
+            name: "euc-kr.zip",
+            expected_encoding: Some(Encoding::EucKr),
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "한국어 폴더/",
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "한국어 폴더/새 텍스트 문서.txt",
+                    content: FileContent::Bytes(b"hello\n".to_vec()),
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "한국어 폴더/안녕하세요.txt",
+                    content: FileContent::Bytes(
+                        b"\xbe\xc8\xb3\xe7\xc7\xcf\xbc\xbc\xbf\xe4\n".to_vec(),
+                    ),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        Case {
+            name: "unicode-path.zip",
+            expected_encoding: Some(Encoding::Utf8),
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "日本語.txt",
+                    content: FileContent::Bytes(b"hello\n".to_vec()),
+                    ..Default::default()
+                },
+                // the unicode path extra field was made for another name
+                CaseFile {
+                    name: "stale.txt",
+                    content: FileContent::Bytes(b"stale\n".to_vec()),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        Case {
+            name: "unix-owner.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "owned.txt",
+                uid: Some(1001),
+                gid: Some(1002),
+                content: FileContent::Bytes(b"hi\n".to_vec()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        Case {
+            name: "unix-legacy-extra.zip",
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "infozip.txt",
+                    uid: Some(1003),
+                    gid: Some(1004),
+                    ..Default::default()
+                },
+                // the central directory version of the field has no ids
+                CaseFile {
+                    name: "infozip-central.txt",
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "pkware.txt",
+                    uid: Some(1005),
+                    gid: Some(1006),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        // the macOS bun releases (bun-darwin-x64.zip, bun-darwin-aarch64.zip)
+        // that used to fail with an extra field error are tens of megabytes,
+        // too large for the corpus: this synthetic archive has one entry per
+        // kind of extra field they tripped over
+        Case {
+            name: "odd-extra-fields.zip",
+            files: Files::ExhaustiveList(
+                [
+                    "trailing-bytes.txt",
+                    "truncated-field.txt",
+                    "unknown-field.txt",
+                    "overlong-field.txt",
+                ]
+                .into_iter()
+                .map(|name| CaseFile {
+                    name,
+                    modified: Some(date((2017, 11, 1), (4, 11, 57), 0, time_zone(0)).unwrap()),
+                    ..Default::default()
+                })
+                .collect(),
+            ),
+            ..Default::default()
+        },
         Case {
             name: "utf8-winrar.zip",
             expected_encoding: Some(Encoding::Utf8),
@@ -257,15 +450,243 @@ pub fn test_cases() -> Vec<Case> {
             }]),
             ..Default::default()
         },
+        // same with xz
+        #[cfg(feature = "xz")]
+        Case {
+            name: "found-me-xz.zip",
+            expected_encoding: Some(Encoding::Utf8),
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "found-me.txt",
+                content: FileContent::Bytes("Oh no, you found me\n".repeat(5000).into()),
+                modified: Some(date((2024, 1, 26), (16, 14, 34), 0, time_zone(0)).unwrap()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        // same with PPMd, plus an entry that doesn't compress as well, so
+        // that it gets decoded over several reads
+        #[cfg(feature = "ppmd")]
+        Case {
+            name: "ppmd.zip",
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "found-me.txt",
+                    content: FileContent::Bytes("Oh no, you found me\n".repeat(5000).into()),
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "numbers.txt",
+                    content: FileContent::Bytes(
+                        (0..20000)
+                            .flat_map(|i| format!("{i}\n").into_bytes())
+                            .collect(),
+                    ),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        // data descriptors written after each entry, even though the
+        // general purpose flag doesn't announce them
+        Case {
+            name: "data-descriptor-without-flag.zip",
+            files: Files::ExhaustiveList(vec![
+                CaseFile {
+                    name: "a.txt",
+                    content: FileContent::Bytes("descriptor without a flag\n".into()),
+                    ..Default::default()
+                },
+                CaseFile {
+                    name: "b.txt",
+                    content: FileContent::Bytes("the next entry is still found\n".into()),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        },
+        // a zstd entry made of two concatenated frames
+        #[cfg(feature = "zstd")]
+        Case {
+            name: "zstd-multi-frame.zip",
+            files: Files::ExhaustiveList(vec![CaseFile {
+                name: "multi-frame.txt",
+                content: FileContent::Bytes(
+                    [
+                        format!("first frame: {}\n", "hello zstd ".repeat(50)),
+                        format!("second frame: {}\n", "goodbye zstd ".repeat(50)),
+                    ]
+                    .concat()
+                    .into(),
+                ),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
     ]
 }
 
 pub fn streaming_test_cases() -> Vec<Case> {
-    vec![Case {
-        name: "meta.zip",
-        files: Files::NumFiles(0),
-        ..Default::default()
-    }]
+    vec![
+        Case {
+            name: "meta.zip",
+            files: Files::NumFiles(33),
+            ..Default::default()
+        },
+        Case {
+            name: "cp-437.zip",
+            files: Files::NumFiles(1),
+            ..Default::default()
+        },
+        Case {
+            name: "data-descriptor-without-flag.zip",
+            files: Files::NumFiles(2),
+            ..Default::default()
+        },
+    ]
+}
+
+/// An input that once made the fuzzer crash, kept around as a regression
+/// test. Artifacts are stored base64-encoded, as pasted in bug reports.
+pub struct FuzzArtifact {
+    /// Short description of what the input exercises, shown when a replay
+    /// fails
+    pub name: &'static str,
+
+    /// The input itself, base64-encoded (standard alphabet, with padding)
+    pub base64: &'static str,
+}
+
+impl FuzzArtifact {
+    /// Decodes the input, panicking if [Self::base64] isn't valid base64
+    pub fn bytes(&self) -> Vec<u8> {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD
+            .decode(self.base64)
+            .unwrap()
+    }
+}
+
+/// Inputs to replay through [replay_fuzz_artifact].
+///
+/// The blob from the original fuzzing report wasn't attached to it, so these
+/// are hand-crafted inputs for the error paths of both state machines.
+/// Reported artifacts go here as they come in, pasted as-is.
+pub fn fuzz_artifacts() -> Vec<FuzzArtifact> {
+    vec![
+        FuzzArtifact {
+            // end of central directory record claiming 65535 entries in a
+            // 4GiB central directory, and nothing else
+            name: "eocd-only-huge-directory",
+            base64: "UEsFBgAAAAD/////8P///wAAAAAAAA==",
+        },
+        FuzzArtifact {
+            // local header for a 100-byte stored file, followed by 5 bytes
+            // of data and nothing else
+            name: "truncated-stored-data",
+            base64: "UEsDBBQAAAAAAAAAAAAAAAAAZAAAAGQAAAAFAAAAYS50eHRoZWxsbw==",
+        },
+        FuzzArtifact {
+            // deflate entry with garbage data, whose central directory
+            // header has a name running past the end of the file
+            name: "garbage-deflate-long-name",
+            base64: "UEsDBBQAAAAIAAAAAAB4VjQSBQAAAAUAAAAFAAAAYS50eHT//////1BLAQIUABQAAAAIAAAAAAB4VjQSBQAAAAUAAAAA/wAAAAAAAAAAAAAAAAAAAABhLnR4dFBLBQYAAAAAAQABADMAAAAoAAAAAAA=",
+        },
+        FuzzArtifact {
+            // zip64 end of central directory locator pointing way past the
+            // end of the file
+            name: "eocd64-locator-past-end",
+            base64: "UEsGBwAAAAAA/////////wEAAABQSwUGAAAAAP///////////////wAA",
+        },
+        FuzzArtifact {
+            // zip64 end of central directory record claiming to be on the
+            // last possible disk
+            name: "eocd64-last-disk",
+            base64: "UEsGBiwAAAAAAAAALQAtAP//////////AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABQSwYHAAAAAAAAAAAAAAAAAQAAAFBLBQYAAAAA////////////////AAA=",
+        },
+        FuzzArtifact {
+            // central directory header whose local header is way past the
+            // end of the file
+            name: "header-offset-past-end",
+            base64: "UEsBAhQAFAAAAAAAAAAAAAAAAAAAAAAAAAAAAAUAAAAAAAAAAAAAAAAA8P//f2EudHh0UEsFBgAAAAABAAEAMwAAAAAAAAAAAA==",
+        },
+        FuzzArtifact {
+            // end of central directory record claiming a central directory
+            // larger than the whole file
+            name: "directory-larger-than-file",
+            base64: "UEsFBgAAAAABAAEAAAEAAAAAAAAAAA==",
+        },
+        FuzzArtifact {
+            // zip64 end of central directory record with a directory offset
+            // that doesn't fit in an i64
+            name: "eocd64-huge-directory-offset",
+            base64: "UEsGBiwAAAAAAAAALQAtAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAIBQSwYHAAAAAAAAAAAAAAAAAQAAAFBLBQYAAAAA////////////////AAA=",
+        },
+    ]
+}
+
+/// What happened when feeding a fuzz artifact through the state machines
+pub struct Replay {
+    /// Reading the archive with [ArchiveFsm], then every entry with [EntryFsm]
+    pub archive: Result<(), Error>,
+    /// Reading the first entry with [EntryFsm], without a central directory
+    pub streaming: Result<(), Error>,
+}
+
+/// Feeds `data` through [ArchiveFsm] and [EntryFsm], the same way the sync
+/// and tokio crates would (minus the I/O). This should never panic.
+pub fn replay_fuzz_artifact(data: &[u8]) -> Replay {
+    let archive = replay_archive(data).and_then(|archive| {
+        archive.entries().try_for_each(|entry| {
+            let offset = (entry.header_offset as usize).min(data.len());
+            replay_entry(Some(entry.clone()), &data[offset..])
+        })
+    });
+    let streaming = replay_entry(None, data);
+    Replay { archive, streaming }
+}
+
+fn replay_archive(data: &[u8]) -> Result<Archive, Error> {
+    let mut fsm = ArchiveFsm::new(data.len() as u64);
+    loop {
+        if let Some(offset) = fsm.wants_read() {
+            let rest = data.get(offset as usize..).unwrap_or_default();
+            let n = rest.len().min(fsm.space().len());
+            if n == 0 {
+                return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            fsm.space()[..n].copy_from_slice(&rest[..n]);
+            fsm.fill(n);
+        }
+
+        fsm = match fsm.process()? {
+            FsmResult::Continue(fsm) => fsm,
+            FsmResult::Done(archive) => return Ok(archive),
+        }
+    }
+}
+
+fn replay_entry(entry: Option<Entry>, mut data: &[u8]) -> Result<(), Error> {
+    let mut fsm = EntryFsm::new(entry, None);
+    let mut out = vec![0u8; 64 * 1024];
+    loop {
+        let mut filled = 0;
+        if fsm.wants_read() {
+            filled = data.len().min(fsm.space().len());
+            fsm.space()[..filled].copy_from_slice(&data[..filled]);
+            fsm.fill(filled);
+            data = &data[filled..];
+        }
+
+        fsm = match fsm.process(&mut out)? {
+            FsmResult::Continue((fsm, outcome)) => {
+                if filled == 0 && outcome.bytes_read == 0 && outcome.bytes_written == 0 {
+                    return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+                fsm
+            }
+            FsmResult::Done(_) => return Ok(()),
+        }
+    }
 }
 
 pub fn check_case(case: &Case, archive: Result<&Archive, &Error>) {
@@ -289,6 +710,7 @@ pub fn check_case(case: &Case, archive: Result<&Archive, &Error>) {
     });
 
     assert_eq!(case_bytes.len() as u64, archive.size());
+    assert_eq!(case.prefix_len, archive.prefix_len(), "{}", case.name);
 
     if let Some(expected) = case.comment {
         assert_eq!(expected, archive.comment())
@@ -300,11 +722,13 @@ pub fn check_case(case: &Case, archive: Result<&Archive, &Error>) {
 
     assert_eq!(
         case.files.len(),
-        archive.entries().count(),
+        archive.len(),
         "{} should have {} entries files",
         case.name,
         case.files.len()
     );
+    assert_eq!(archive.len(), archive.entries().count());
+    assert_eq!(archive.is_empty(), case.files.is_empty());
 
     // then each implementation should check individual files
 }
@@ -322,6 +746,15 @@ pub fn check_file_against(file: &CaseFile, entry: &Entry, actual_bytes: &[u8]) {
         assert_eq!(entry.mode.0 & 0o777, mode);
     }
 
+    if file.uid.is_some() || file.gid.is_some() {
+        assert_eq!(
+            (entry.uid, entry.gid),
+            (file.uid, file.gid),
+            "{}",
+            entry.name
+        );
+    }
+
     // I have honestly yet to see a zip file _entry_ with a comment.
     assert!(entry.comment.is_empty());
 
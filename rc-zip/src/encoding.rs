@@ -26,6 +26,18 @@ pub enum Encoding {
     ///
     /// Still in use by some Japanese users as of 2019.
     ShiftJis,
+
+    /// [GBK](https://en.wikipedia.org/wiki/GBK_(character_encoding)), the
+    /// encoding used for Simplified Chinese on Windows.
+    ///
+    /// Windows Explorer uses it for zip files made on Chinese systems.
+    Gbk,
+
+    /// [EUC-KR](https://en.wikipedia.org/wiki/Extended_Unix_Code#EUC-KR), the
+    /// encoding used for Korean on Windows (as code page 949).
+    ///
+    /// Windows Explorer uses it for zip files made on Korean systems.
+    EucKr,
 }
 
 impl fmt::Display for Encoding {
@@ -35,6 +47,8 @@ impl fmt::Display for Encoding {
             T::Utf8 => write!(f, "utf-8"),
             T::Cp437 => write!(f, "cp-437"),
             T::ShiftJis => write!(f, "shift-jis"),
+            T::Gbk => write!(f, "gbk"),
+            T::EucKr => write!(f, "euc-kr"),
         }
     }
 }
@@ -65,7 +79,11 @@ impl From<std::str::Utf8Error> for DecodingError {
 }
 
 impl Encoding {
-    pub(crate) fn decode(&self, i: &[u8]) -> Result<String, DecodingError> {
+    /// Decodes `i` as text in this encoding, failing if it contains byte
+    /// sequences that aren't valid in this encoding.
+    ///
+    /// See also [Self::decode_lossy].
+    pub fn decode(&self, i: &[u8]) -> Result<String, DecodingError> {
         match self {
             Encoding::Utf8 => {
                 let s = std::str::from_utf8(i)?;
@@ -76,6 +94,34 @@ impl Encoding {
                 &oem_cp::code_table::DECODING_TABLE_CP437,
             )),
             Encoding::ShiftJis => self.decode_as(i, encoding_rs::SHIFT_JIS),
+            Encoding::Gbk => self.decode_as(i, encoding_rs::GBK),
+            Encoding::EucKr => self.decode_as(i, encoding_rs::EUC_KR),
+        }
+    }
+
+    /// Decodes `i` as text in this encoding, replacing invalid byte sequences
+    /// with U+FFFD REPLACEMENT CHARACTER.
+    ///
+    /// This is what is used for entry names and comments, so that a single
+    /// odd name doesn't make a whole archive unreadable.
+    pub fn decode_lossy(&self, i: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(i).into_owned(),
+            Encoding::Cp437 => {
+                oem_cp::decode_string_complete_table(i, &oem_cp::code_table::DECODING_TABLE_CP437)
+            }
+            Encoding::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling(i)
+                .0
+                .into_owned(),
+            Encoding::Gbk => encoding_rs::GBK
+                .decode_without_bom_handling(i)
+                .0
+                .into_owned(),
+            Encoding::EucKr => encoding_rs::EUC_KR
+                .decode_without_bom_handling(i)
+                .0
+                .into_owned(),
         }
     }
 
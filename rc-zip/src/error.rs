@@ -35,6 +35,35 @@ pub enum Error {
     /// Could not read as a zip because size could not be determined
     #[error("size must be known to open zip file")]
     UnknownSize,
+
+    /// Reading the archive's metadata went over the budget (bytes read or
+    /// deadline) set in [ArchiveLimits](crate::fsm::ArchiveLimits)
+    #[error("budget exceeded after reading {bytes_read} bytes")]
+    Budget {
+        /// number of bytes read when the budget ran out
+        bytes_read: u64,
+    },
+
+    /// Decompressing an entry went over the limits set in
+    /// [DecompressLimits](crate::fsm::DecompressLimits)
+    #[error("decompression limits exceeded: {uncompressed_bytes} bytes out of {compressed_bytes} compressed bytes")]
+    LimitExceeded {
+        /// number of compressed bytes read when the limit was hit
+        compressed_bytes: u64,
+        /// number of bytes decompressed when the limit was hit
+        uncompressed_bytes: u64,
+    },
+
+    /// Zip files nested within each other go deeper than allowed
+    #[error("zip files are nested more than {max_depth} levels deep")]
+    NestingTooDeep {
+        /// maximum nesting depth that was allowed
+        max_depth: usize,
+    },
+
+    /// Something went wrong while writing an archive
+    #[error("write: {0}")]
+    Write(#[from] WriteError),
 }
 
 impl Error {
@@ -49,17 +78,63 @@ impl Error {
     }
 }
 
+/// Both the name and the code of a method, so that a method this crate
+/// doesn't implement can be told apart from a garbage method field.
+fn describe_method(method: &Method) -> String {
+    match method.name() {
+        Some(name) => format!("{name} (method {})", method.as_u16()),
+        None => format!("unrecognized method {}", method.as_u16()),
+    }
+}
+
 /// Some part of the zip format is not supported by this crate.
 #[derive(Debug, thiserror::Error)]
 pub enum UnsupportedError {
-    /// The compression method is not supported.
-    #[error("compression method not supported: {0:?}")]
+    /// The compression method is not supported. This may be a known method
+    /// this crate doesn't implement, or an unrecognized code, in which case
+    /// [Method::Unrecognized] holds it.
+    #[error("compression method not supported: {}", describe_method(.0))]
     MethodNotSupported(Method),
 
     /// The compression method is supported, but not enabled in this build.
-    #[error("compression method supported, but not enabled in this build: {0:?}")]
+    #[error(
+        "compression method supported, but not enabled in this build: {}",
+        describe_method(.0)
+    )]
     MethodNotEnabled(Method),
 
+    /// Random access was requested into an entry whose data is compressed:
+    /// only stored entries support it.
+    #[error("random access is only supported in stored entries, not {0:?} ones")]
+    RandomAccessUnsupported(Method),
+
+    /// The entry's data is encrypted (see
+    /// [Entry::is_encrypted](crate::parse::Entry::is_encrypted)), and was
+    /// asked for as is, straight from the archive.
+    #[error("entry is encrypted, its data can't be read straight from the archive")]
+    EncryptedEntry,
+
+    /// A stored entry with a data descriptor can only be read if its size is
+    /// known from the central directory: when streaming, nothing marks the
+    /// end of its data.
+    #[error("the size of a stored entry with a data descriptor is unknown when streaming")]
+    UnknownStoredSize,
+
+    /// Like [UnsupportedError::UnknownStoredSize], for compression methods
+    /// whose data doesn't tell where it ends precisely enough to find the
+    /// data descriptor after it (PPMd).
+    #[error("the size of a {0:?} entry with a data descriptor is unknown when streaming")]
+    UnknownCompressedSize(Method),
+
+    /// The archive is split across several disks (or files), which is only
+    /// supported when their offsets are known, see
+    /// [ArchiveFsm::with_disk_offsets](crate::fsm::ArchiveFsm::with_disk_offsets).
+    #[error("archive spans {total_disks} disks, spanned archives are not supported")]
+    SpannedArchiveUnsupported {
+        /// number of disks the archive claims to span
+        total_disks: u32,
+    },
+
     /// The zip file uses a version of LZMA that is not supported.
     #[error("only LZMA2.0 is supported, found LZMA{minor}.{major}")]
     LzmaVersionUnsupported {
@@ -96,11 +171,28 @@ pub enum FormatError {
     #[error("zip64 end of central directory record not found")]
     Directory64EndRecordInvalid,
 
+    /// The end of central directory record says the size or offset of the
+    /// central directory are stored in a zip64 end of central directory
+    /// record (by setting them to 0xFFFFFFFF), but there is none.
+    #[error("end of central directory record defers to a zip64 record that is missing")]
+    MissingZip64Eocd,
+
     /// Corrupted/partial zip file: the offset we found for the central directory
     /// points outside of the current file.
     #[error("directory offset points outside of file")]
     DirectoryOffsetPointsOutsideFile,
 
+    /// The central directory is supposed to end right where the (zip64) end
+    /// of central directory record starts, but it's larger than everything
+    /// that precedes that record.
+    #[error("central directory size ({directory_size} bytes) does not fit before offset {directory_end}")]
+    InconsistentDirectorySize {
+        /// size of the central directory, as recorded
+        directory_size: u64,
+        /// offset at which the central directory should end
+        directory_end: u64,
+    },
+
     /// The central record is corrupted somewhat.
     ///
     /// This can happen when the end of central directory record advertises
@@ -114,9 +206,32 @@ pub enum FormatError {
         actual: u16,
     },
 
+    /// A variable-length field of a central directory header is larger than
+    /// allowed by the configured limits.
+    #[error("{field} is too long: {len} bytes, maximum is {max}")]
+    FieldTooLong {
+        /// which field: name, extra field or comment
+        field: &'static str,
+        /// length claimed by the header, in bytes
+        len: usize,
+        /// maximum length allowed, in bytes
+        max: usize,
+    },
+
+    /// An archive comment is longer than the end of central directory record
+    /// can hold.
+    #[error("comment is too long: {len} bytes, maximum is {max}")]
+    CommentTooLong {
+        /// length of the comment, in bytes
+        len: usize,
+        /// maximum length allowed, in bytes
+        max: usize,
+    },
+
     /// An extra field (that we support) was not decoded correctly.
     ///
     /// This can indicate an invalid zip archive, or an implementation error in this crate.
+    #[deprecated = "never returned, parsing of extra fields stops at the first one that can't be decoded"]
     #[error("could not decode extra field")]
     InvalidExtraField,
 
@@ -126,10 +241,22 @@ pub enum FormatError {
     #[error("invalid header offset")]
     InvalidHeaderOffset,
 
+    /// A split archive refers to a disk (segment) it doesn't have.
+    #[error("disk {disk} does not exist, the archive has {total_disks} disks")]
+    InvalidDiskNumber {
+        /// the disk number, as recorded
+        disk: u32,
+        /// number of disks the archive was read from
+        total_disks: u32,
+    },
+
     /// End of central directory record claims an impossible number of files.
     ///
     /// Each entry takes a minimum amount of size, so if the overall archive size is smaller than
     /// claimed_records_count * minimum_entry_size, we know it's not a valid zip file.
+    ///
+    /// This is also returned when an archive has more entries than allowed by
+    /// [ArchiveLimits::max_entries](crate::fsm::ArchiveLimits::max_entries).
     #[error("impossible number of files: claims to have {claimed_records_count}, but zip size is {zip_size}")]
     ImpossibleNumberOfFiles {
         /// number of files claimed in the end of central directory record
@@ -138,10 +265,48 @@ pub enum FormatError {
         zip_size: u64,
     },
 
+    /// The file ended before all the metadata could be read: it's shorter
+    /// than the size it was opened with, for example a partial download.
+    #[error("truncated archive: expected data at offset {expected_at}, but the file ended early (expected size: {file_size})")]
+    TruncatedArchive {
+        /// offset at which more data was needed
+        expected_at: u64,
+        /// size the archive was opened with
+        file_size: u64,
+    },
+
+    /// A deflate stream ended without its final block: the entry's
+    /// compressed data was all read, but the decompressor needed more.
+    #[error(
+        "deflate stream is truncated: the entry's compressed data ends before its final block"
+    )]
+    TruncatedDeflateStream,
+
+    /// An entry's data doesn't fit in the archive: it ends past the end of
+    /// the file.
+    #[error("entry data ends at offset {data_end}, past the end of the file (size: {file_size})")]
+    DataOutOfBounds {
+        /// offset right after the entry's data
+        data_end: u64,
+        /// size of the archive
+        file_size: u64,
+    },
+
     /// The local file header (before the file data) could not be parsed correctly.
     #[error("invalid local file header")]
     InvalidLocalHeader,
 
+    /// A local file header doesn't fit in the entry reader's buffer, because
+    /// of its long name or extra field. See
+    /// [EntryFsm::with_capacity](crate::fsm::EntryFsm::with_capacity).
+    #[error("local file header is {len} bytes, too large for a {capacity} bytes buffer")]
+    LocalHeaderTooLarge {
+        /// size of the header, name and extra field included
+        len: usize,
+        /// capacity of the buffer
+        capacity: usize,
+    },
+
     /// The data descriptor (after the file data) could not be parsed correctly.
     #[error("invalid data descriptor")]
     InvalidDataDescriptor,
@@ -163,6 +328,20 @@ pub enum FormatError {
         /// actual checksum (from decompressing the entry)
         actual: u32,
     },
+
+    /// An entry's data descriptor disagrees with its central directory
+    /// header, see
+    /// [EntryFsm::with_strict_descriptors](crate::fsm::EntryFsm::with_strict_descriptors)
+    #[error("data descriptor has {field} {descriptor:#x}, central directory has {central:#x}")]
+    DescriptorCentralMismatch {
+        /// which field disagrees: "crc32", "compressed size" or
+        /// "uncompressed size"
+        field: &'static str,
+        /// value from the central directory
+        central: u64,
+        /// value from the data descriptor
+        descriptor: u64,
+    },
 }
 
 impl From<Error> for std::io::Error {
@@ -173,3 +352,47 @@ impl From<Error> for std::io::Error {
         }
     }
 }
+
+/// Errors that can happen while writing an archive with
+/// [WriteFsm](crate::fsm::WriteFsm), mostly from calling its methods out of
+/// order.
+#[derive(Debug, thiserror::Error)]
+pub enum WriteError {
+    /// Data was written, or an entry finished, before starting an entry
+    #[error("no entry in progress")]
+    NoEntryInProgress,
+
+    /// An entry was started, or the archive finished, before finishing the
+    /// previous entry
+    #[error("an entry is still in progress")]
+    EntryInProgress,
+
+    /// Something was written after finishing the archive
+    #[error("the archive is already finished")]
+    AlreadyFinished,
+
+    /// The compressor reported an error
+    #[error("{method:?} compression error: {msg}")]
+    Compression {
+        /// The compression method that failed
+        method: Method,
+        /// Additional information
+        msg: String,
+    },
+
+    /// The data of an entry copied with
+    /// [WriteFsm::start_raw_entry](crate::fsm::WriteFsm::start_raw_entry)
+    /// doesn't match its compressed size
+    #[error("raw entry data should be {expected} bytes, got {actual}")]
+    WrongRawSize {
+        /// the entry's compressed size
+        expected: u64,
+        /// the number of bytes written
+        actual: u64,
+    },
+
+    /// A streamed entry went over 4 GiB: its local header was written
+    /// before its size was known, without the zip64 extra field it needs
+    #[error("streamed entries must be smaller than 4 GiB")]
+    StreamedEntryTooLarge,
+}
@@ -1,14 +1,16 @@
 use super::FsmResult;
 use crate::{
     encoding::Encoding,
-    error::{Error, FormatError},
+    error::{Error, FormatError, UnsupportedError},
     parse::{
-        Archive, CentralDirectoryFileHeader, EndOfCentralDirectory, EndOfCentralDirectory64Locator,
-        EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, Entry, Located,
+        mark_implicit_directories, Archive, CentralDirectoryFileHeader, DigitalSignature,
+        EndOfCentralDirectory, EndOfCentralDirectory64Locator, EndOfCentralDirectory64Record,
+        EndOfCentralDirectoryRecord, Entry, Located,
     },
 };
 
 use ownable::traits::IntoOwned;
+use std::{collections::VecDeque, time::Instant};
 use tracing::trace;
 use winnow::{
     error::ErrMode,
@@ -41,6 +43,140 @@ pub struct ArchiveFsm {
 
     /// Buffer for reading data from the file
     buffer: Buffer,
+
+    /// Caps on the size of variable-length fields, and on the work done
+    limits: ArchiveLimits,
+
+    /// Total number of bytes passed to [Self::fill]
+    bytes_read: u64,
+
+    /// Encoding to decode text fields with, instead of detecting it
+    encoding: Option<Encoding>,
+
+    /// Whether to read the central directory as a whole, and parse its
+    /// headers in parallel
+    #[cfg(feature = "rayon")]
+    parallel: bool,
+
+    /// When streaming, entries parsed but not taken with [Self::next_entry]
+    /// yet
+    streamed_entries: Option<VecDeque<Entry>>,
+
+    /// When streaming, the number of entries parsed so far
+    streamed_count: usize,
+
+    /// For split archives, where each disk starts in the concatenation of
+    /// all of them
+    disk_offsets: Option<Vec<u64>>,
+
+    /// What to do when the central directory is damaged
+    recovery: RecoveryMode,
+
+    /// Data of the digital signature record, if one was found after the
+    /// central directory headers
+    digital_signature: Option<Vec<u8>>,
+}
+
+/// What [ArchiveFsm] does when the central directory turns out to be
+/// damaged partway through, see [ArchiveFsm::with_recovery].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecoveryMode {
+    /// Fail to read the archive, which is the default
+    #[default]
+    Strict,
+
+    /// Return the entries whose headers could be parsed, with
+    /// [Archive::truncated] set
+    BestEffort,
+}
+
+/// Caps on the size of the variable-length fields of central directory
+/// headers, and on how much work reading the metadata may take, enforced by
+/// [ArchiveFsm].
+///
+/// These guard against crafted archives that make the parser buffer (and
+/// allocate) large amounts of memory per entry.
+///
+/// Names, comments and extra fields have 16-bit lengths, so the defaults for
+/// [Self::max_name_len], [Self::max_comment_len] and [Self::max_extra_len]
+/// (the largest lengths they can have) never trigger: they're only there for
+/// callers to lower.
+///
+/// The budget ([Self::max_bytes_read] and [Self::deadline]) is unbounded by
+/// default: services that need to bound the time it takes to open untrusted
+/// archives can set either.
+///
+/// The number of entries ([Self::max_entries]) is unbounded by default too.
+/// The central directory headers of all entries are kept in memory, so
+/// services reading untrusted archives may want to cap it.
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveLimits {
+    /// Maximum length of an entry name, in bytes
+    pub max_name_len: usize,
+
+    /// Maximum length of an entry comment, in bytes
+    pub max_comment_len: usize,
+
+    /// Maximum length of an entry's extra field, in bytes
+    pub max_extra_len: usize,
+
+    /// Maximum number of bytes to read from the file, in total
+    pub max_bytes_read: Option<u64>,
+
+    /// Point in time after which to give up reading
+    pub deadline: Option<Instant>,
+
+    /// Maximum number of entries in the archive, whether claimed by the end
+    /// of central directory record or actually found in the central
+    /// directory
+    pub max_entries: Option<u64>,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_name_len: u16::MAX as usize,
+            max_comment_len: u16::MAX as usize,
+            max_extra_len: u16::MAX as usize,
+            max_bytes_read: None,
+            deadline: None,
+            max_entries: None,
+        }
+    }
+}
+
+impl ArchiveLimits {
+    fn check(&self, field: &'static str, len: u16, max: usize) -> Result<(), FormatError> {
+        if len as usize > max {
+            return Err(FormatError::FieldTooLong {
+                field,
+                len: len as usize,
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_entries(&self, count: u64, size: u64) -> Result<(), FormatError> {
+        if self.max_entries.is_some_and(|max| count > max) {
+            return Err(FormatError::ImpossibleNumberOfFiles {
+                claimed_records_count: count,
+                zip_size: size,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_budget(&self, bytes_read: u64) -> Result<(), Error> {
+        let over_budget = self.max_bytes_read.is_some_and(|max| bytes_read > max)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+        if over_budget {
+            return Err(Error::Budget { bytes_read });
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -53,15 +189,22 @@ enum State {
         haystack_size: u64,
     },
 
-    /// Reading the zip64 end of central directory record.
+    /// Reading the zip64 end of central directory locator.
     ReadEocd64Locator {
         eocdr: Located<EndOfCentralDirectoryRecord<'static>>,
+        /// whether `eocdr` is in the archive, rather than a placeholder
+        dir_found: bool,
     },
 
     /// Reading the zip64 end of central directory record.
     ReadEocd64 {
         eocdr64_offset: u64,
         eocdr: Located<EndOfCentralDirectoryRecord<'static>>,
+        /// whether `eocdr` is in the archive, rather than a placeholder
+        dir_found: bool,
+        /// whether `eocdr64_offset` comes from a locator, rather than
+        /// scanning for the record
+        locator_found: bool,
     },
 
     /// Reading all headers from the central directory
@@ -70,28 +213,250 @@ enum State {
         directory_headers: Vec<CentralDirectoryFileHeader<'static>>,
     },
 
+    /// Reading the whole central directory in memory, to parse its headers
+    /// in parallel once it's all there
+    #[cfg(feature = "rayon")]
+    ReadWholeCentralDirectory {
+        eocd: EndOfCentralDirectory<'static>,
+        directory: Vec<u8>,
+    },
+
     #[default]
     Transitioning,
 }
 
+impl State {
+    /// Central directory headers are at least this long (with empty name,
+    /// extra field and comment).
+    const MIN_DIRECTORY_HEADER_LEN: u64 = 46;
+
+    /// Never reserve room for more headers than this up front, no matter
+    /// what the end of central directory record says.
+    const MAX_RESERVED_HEADERS: u64 = 64 * 1024;
+
+    /// Start reading the central directory, pre-sizing the list of headers.
+    ///
+    /// The record count comes from the archive and can't be trusted, so it
+    /// is clamped by what fits in the announced directory size, and by
+    /// [Self::MAX_RESERVED_HEADERS]: past that, the vec grows as usual.
+    fn read_central_directory(eocd: EndOfCentralDirectory<'static>) -> Self {
+        let capacity = eocd
+            .directory_records()
+            .min(eocd.directory_size() / Self::MIN_DIRECTORY_HEADER_LEN)
+            .min(Self::MAX_RESERVED_HEADERS);
+        trace!(%capacity, "reserving room for central directory headers");
+
+        Self::ReadCentralDirectory {
+            eocd,
+            directory_headers: Vec::with_capacity(capacity as usize),
+        }
+    }
+}
+
 impl ArchiveFsm {
-    /// This should be > 65KiB, because the section at the end of the
-    /// file that we check for end of central directory record is 65KiB.
-    const DEFAULT_BUFFER_SIZE: usize = 256 * 1024;
+    /// Size of the section at the end of the file that we check for the end
+    /// of central directory record.
+    const HAYSTACK_SIZE: u64 = 65 * 1024;
+
+    /// Capacity of the internal buffer used by [Self::new].
+    pub const DEFAULT_BUFFER_CAPACITY: usize = 256 * 1024;
+
+    /// The smallest internal buffer [Self::with_capacity] accepts: the whole
+    /// section at the end of the file that we check for the end of central
+    /// directory record (65KiB) has to fit in it.
+    pub const MIN_BUFFER_CAPACITY: usize = Self::HAYSTACK_SIZE as usize;
 
     /// Create a new archive reader with a specified file size.
     pub fn new(size: u64) -> Self {
-        let haystack_size: u64 = 65 * 1024;
-        let haystack_size = if size < haystack_size {
-            size
-        } else {
-            haystack_size
-        };
+        Self::with_capacity(size, Self::DEFAULT_BUFFER_CAPACITY)
+    }
+
+    /// Like [Self::new], but with an internal buffer of `capacity` bytes,
+    /// rounded up to [Self::MIN_BUFFER_CAPACITY].
+    ///
+    /// Central directory headers must fit in the buffer as a whole, so
+    /// archives with headers larger than `capacity` fail to read with
+    /// [FormatError::FieldTooLong].
+    pub fn with_capacity(size: u64, capacity: usize) -> Self {
+        let haystack_size = size.min(Self::HAYSTACK_SIZE);
 
         Self {
             size,
-            buffer: Buffer::with_capacity(Self::DEFAULT_BUFFER_SIZE),
+            buffer: Buffer::with_capacity(capacity.max(Self::MIN_BUFFER_CAPACITY)),
             state: State::ReadEocd { haystack_size },
+            limits: Default::default(),
+            bytes_read: 0,
+            encoding: None,
+            #[cfg(feature = "rayon")]
+            parallel: false,
+            streamed_entries: None,
+            streamed_count: 0,
+            disk_offsets: None,
+            recovery: Default::default(),
+            digital_signature: None,
+        }
+    }
+
+    /// Sets the limits enforced while reading the central directory.
+    pub fn with_limits(mut self, limits: ArchiveLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Decodes all entry names and comments (and the archive comment) with
+    /// `encoding`, instead of detecting it: entries flagged as UTF-8 are
+    /// decoded with `encoding` as well.
+    ///
+    /// Use this when the encoding of an archive is known, as detection can
+    /// only make an educated guess.
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Reads the whole central directory in memory, then parses its headers
+    /// in parallel, which is much faster for archives with lots of entries.
+    ///
+    /// The central directory has to be exactly as large as the end of
+    /// central directory record says it is: archives that get it wrong are
+    /// only readable without this.
+    #[cfg(feature = "rayon")]
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Hands out entries one at a time, as their central directory headers
+    /// are parsed, instead of collecting them all in the [Archive]: take
+    /// them with [Self::next_entry] after each call to [Self::process].
+    ///
+    /// This keeps memory usage down when only some entries of a very large
+    /// archive are of interest. Since entries are handed out before the
+    /// rest of the central directory is read:
+    ///
+    ///   * without [Self::with_encoding], the encoding of each entry is
+    ///     guessed from its own name and comment, which is less reliable
+    ///   * entries that only look like directories because other entries
+    ///     are nested under them are not treated as such
+    ///
+    /// The [Archive] returned once done holds the entries that haven't been
+    /// taken yet. Streaming takes precedence over parsing in parallel.
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streamed_entries = streaming.then(VecDeque::new);
+        self
+    }
+
+    /// Reads a split (or spanned) archive, whose disks — `.z01`, `.z02`, …,
+    /// then `.zip` — are read one after the other as if they were a single
+    /// file of `size` bytes: `disk_offsets` holds where each disk starts in
+    /// it, in order, starting with 0.
+    ///
+    /// Offsets recorded in the archive are relative to the start of their
+    /// disk, this translates them. Without this, archives that span several
+    /// disks fail to read with [UnsupportedError::SpannedArchiveUnsupported].
+    pub fn with_disk_offsets(mut self, disk_offsets: Vec<u64>) -> Self {
+        self.disk_offsets = Some(disk_offsets);
+        self
+    }
+
+    /// Sets what to do when a central directory header fails to parse
+    /// before all the records announced by the end of central directory
+    /// record were found, as happens with damaged archives.
+    ///
+    /// With [RecoveryMode::BestEffort], the entries parsed up to that point
+    /// are returned, and [Archive::truncated] tells the central directory
+    /// was cut short.
+    pub fn with_recovery(mut self, recovery: RecoveryMode) -> Self {
+        self.recovery = recovery;
+        self
+    }
+
+    /// When streaming (see [Self::with_streaming]), takes the next entry
+    /// parsed so far, if any.
+    pub fn next_entry(&mut self) -> Option<Entry> {
+        self.streamed_entries.as_mut()?.pop_front()
+    }
+
+    /// Start reading the central directory, header by header, or as a whole
+    /// if its headers are to be parsed in parallel.
+    fn read_central_directory(&self, eocd: EndOfCentralDirectory<'static>) -> State {
+        if self.streamed_entries.is_some() {
+            // headers are only kept until they're turned into entries
+            return State::ReadCentralDirectory {
+                eocd,
+                directory_headers: Vec::new(),
+            };
+        }
+
+        #[cfg(feature = "rayon")]
+        if self.parallel {
+            // the announced size fits in the file, but the file could be
+            // larger than what's worth reserving up front
+            let capacity = eocd
+                .directory_size()
+                .min(State::MAX_RESERVED_HEADERS * State::MIN_DIRECTORY_HEADER_LEN);
+            return State::ReadWholeCentralDirectory {
+                eocd,
+                directory: Vec::with_capacity(capacity as usize),
+            };
+        }
+        State::read_central_directory(eocd)
+    }
+
+    /// Some writers leave out the end of central directory record of zip64
+    /// archives, which goes against the spec but happens in the wild. In
+    /// that case, look for the zip64 end of central directory locator, or
+    /// failing that, the zip64 end of central directory record itself, in
+    /// the haystack, and carry on from there.
+    fn process_missing_eocd(
+        mut self,
+        haystack_size: u64,
+    ) -> Result<FsmResult<Self, Archive>, Error> {
+        use State as S;
+
+        let haystack_offset = self.size - haystack_size;
+        let (locator, record) = {
+            let haystack = &self.buffer.data()[..haystack_size as usize];
+            match EndOfCentralDirectory64Locator::find_in_block(haystack) {
+                Some(locator) => (Some(locator.offset), None),
+                None => (
+                    None,
+                    EndOfCentralDirectory64Record::find_in_block(haystack)
+                        .map(|record| record.offset),
+                ),
+            }
+        };
+
+        if let Some(offset) = locator {
+            trace!(offset, "ReadEocd | no eocd, but found a zip64 eocd locator");
+            let eocdr = Located {
+                // where the end of central directory record should have been
+                offset: haystack_offset + offset + EndOfCentralDirectory64Locator::LENGTH as u64,
+                inner: EndOfCentralDirectoryRecord::zip64_placeholder(),
+            };
+            self.buffer.reset();
+            transition!(self.state => (S::ReadEocd { .. }) {
+                S::ReadEocd64Locator { eocdr, dir_found: false }
+            });
+            Ok(FsmResult::Continue(self))
+        } else if let Some(offset) = record {
+            trace!(offset, "ReadEocd | no eocd, but found a zip64 eocd record");
+            let eocdr = Located {
+                offset: self.size,
+                inner: EndOfCentralDirectoryRecord::zip64_placeholder(),
+            };
+            self.buffer.reset();
+            transition!(self.state => (S::ReadEocd { .. }) {
+                S::ReadEocd64 {
+                    eocdr64_offset: haystack_offset + offset,
+                    eocdr,
+                    dir_found: false,
+                    locator_found: false,
+                }
+            });
+            Ok(FsmResult::Continue(self))
+        } else {
+            Err(FormatError::DirectoryEndSignatureNotFound.into())
         }
     }
 
@@ -104,7 +469,7 @@ impl ArchiveFsm {
             S::ReadEocd { haystack_size } => {
                 Some(self.buffer.read_offset(self.size - haystack_size))
             }
-            S::ReadEocd64Locator { ref eocdr } => {
+            S::ReadEocd64Locator { ref eocdr, .. } => {
                 let length = EndOfCentralDirectory64Locator::LENGTH as u64;
                 Some(self.buffer.read_offset(eocdr.offset - length))
             }
@@ -112,6 +477,10 @@ impl ArchiveFsm {
             S::ReadCentralDirectory { ref eocd, .. } => {
                 Some(self.buffer.read_offset(eocd.directory_offset()))
             }
+            #[cfg(feature = "rayon")]
+            S::ReadWholeCentralDirectory { ref eocd, .. } => {
+                Some(self.buffer.read_offset(eocd.directory_offset()))
+            }
             S::Transitioning => unreachable!(),
         }
     }
@@ -129,6 +498,8 @@ impl ArchiveFsm {
     /// a fully-parsed [Archive].
     pub fn process(mut self) -> Result<FsmResult<Self, Archive>, Error> {
         use State as S;
+        self.limits.check_budget(self.bytes_read)?;
+
         match self.state {
             S::ReadEocd { haystack_size } => {
                 if self.buffer.read_bytes() < haystack_size {
@@ -141,7 +512,7 @@ impl ArchiveFsm {
                     EndOfCentralDirectoryRecord::find_in_block(haystack)
                 };
                 match res {
-                    None => Err(FormatError::DirectoryEndSignatureNotFound.into()),
+                    None => self.process_missing_eocd(haystack_size),
                     Some(eocdr) => {
                         trace!(
                             ?eocdr,
@@ -160,24 +531,29 @@ impl ArchiveFsm {
                                 "no room for an EOCD64 locator, definitely not a zip64 file"
                             );
                             transition!(self.state => (S::ReadEocd { .. }) {
-                                S::ReadCentralDirectory {
-                                    eocd: EndOfCentralDirectory::new(self.size, eocdr, None)?,
-                                    directory_headers: vec![],
-                                }
+                                self.read_central_directory(
+                                    EndOfCentralDirectory::new(self.size, eocdr, None)?,
+                                )
                             });
                             Ok(FsmResult::Continue(self))
                         } else {
                             trace!("ReadEocd | transition to ReadEocd64Locator");
                             self.buffer.reset();
                             transition!(self.state => (S::ReadEocd { .. }) {
-                                S::ReadEocd64Locator { eocdr }
+                                S::ReadEocd64Locator { eocdr, dir_found: true }
                             });
                             Ok(FsmResult::Continue(self))
                         }
                     }
                 }
             }
-            S::ReadEocd64Locator { .. } => {
+            S::ReadEocd64Locator { ref eocdr, .. } => {
+                // the zip64 record must fit before the locator, which sits
+                // right before the end of central directory record
+                let max_eocdr64_offset = eocdr
+                    .offset
+                    .saturating_sub(EndOfCentralDirectory64Locator::LENGTH as u64)
+                    .saturating_sub(EndOfCentralDirectory64Record::MIN_LENGTH as u64);
                 let input = Partial::new(self.buffer.data());
                 match EndOfCentralDirectory64Locator::parser.parse_peek(input) {
                     Err(ErrMode::Incomplete(_)) => {
@@ -192,11 +568,10 @@ impl ArchiveFsm {
                             self.buffer.data()
                         );
                         self.buffer.reset();
-                        transition!(self.state => (S::ReadEocd64Locator { eocdr }) {
-                            S::ReadCentralDirectory {
-                                eocd: EndOfCentralDirectory::new(self.size, eocdr, None)?,
-                                directory_headers: vec![],
-                            }
+                        transition!(self.state => (S::ReadEocd64Locator { eocdr, .. }) {
+                            self.read_central_directory(
+                                EndOfCentralDirectory::new(self.size, eocdr, None)?,
+                            )
                         });
                         Ok(FsmResult::Continue(self))
                     }
@@ -205,11 +580,32 @@ impl ArchiveFsm {
                             ?locator,
                             "ReadEocd64Locator | found zip64 end of central directory locator"
                         );
+                        let eocdr64_offset = match self.disk_offsets.as_deref() {
+                            Some(disk_offsets) => {
+                                // the locator points within the disk holding
+                                // the zip64 end of central directory record
+                                disk_offset(disk_offsets, locator.dir_disk_number)?
+                                    .checked_add(locator.directory_offset)
+                                    .ok_or(FormatError::Directory64EndRecordInvalid)?
+                            }
+                            None if locator.total_disks > 1 => {
+                                return Err(UnsupportedError::SpannedArchiveUnsupported {
+                                    total_disks: locator.total_disks,
+                                }
+                                .into());
+                            }
+                            None => locator.directory_offset,
+                        };
+                        if eocdr64_offset > max_eocdr64_offset {
+                            return Err(FormatError::Directory64EndRecordInvalid.into());
+                        }
                         self.buffer.reset();
-                        transition!(self.state => (S::ReadEocd64Locator { eocdr }) {
+                        transition!(self.state => (S::ReadEocd64Locator { eocdr, dir_found }) {
                             S::ReadEocd64 {
-                                eocdr64_offset: locator.directory_offset,
+                                eocdr64_offset,
                                 eocdr,
+                                dir_found,
+                                locator_found: true,
                             }
                         });
                         Ok(FsmResult::Continue(self))
@@ -230,15 +626,29 @@ impl ArchiveFsm {
                         Err(FormatError::Directory64EndRecordInvalid.into())
                     }
                     Ok((_, eocdr64)) => {
-                        self.buffer.reset();
-                        transition!(self.state => (S::ReadEocd64 { eocdr, eocdr64_offset }) {
-                            S::ReadCentralDirectory {
-                                eocd: EndOfCentralDirectory::new(self.size, eocdr, Some(Located {
-                                    offset: eocdr64_offset,
-                                    inner: eocdr64
-                                }))?,
-                                directory_headers: vec![],
+                        let last_disk = eocdr64.disk_nbr.max(eocdr64.dir_disk_nbr);
+                        match self.disk_offsets.as_deref() {
+                            Some(disk_offsets) => {
+                                disk_offset(disk_offsets, last_disk)?;
+                            }
+                            None if last_disk > 0 => {
+                                // disks are numbered from zero
+                                return Err(UnsupportedError::SpannedArchiveUnsupported {
+                                    total_disks: last_disk.saturating_add(1),
+                                }
+                                .into());
                             }
+                            None => {}
+                        }
+                        self.buffer.reset();
+                        transition!(self.state => (S::ReadEocd64 { eocdr, eocdr64_offset, dir_found, locator_found }) {
+                            let mut eocd = EndOfCentralDirectory::new(self.size, eocdr, Some(Located {
+                                offset: eocdr64_offset,
+                                inner: eocdr64
+                            }))?;
+                            eocd.dir_found = dir_found;
+                            eocd.locator_found = locator_found;
+                            self.read_central_directory(eocd)
                         });
                         Ok(FsmResult::Continue(self))
                     }
@@ -252,6 +662,8 @@ impl ArchiveFsm {
                     "ReadCentralDirectory | process(), available: {}",
                     self.buffer.available_data()
                 );
+                self.limits
+                    .check_entries(eocd.directory_records(), self.size)?;
                 let mut valid_consumed = 0;
                 let mut input = Partial::new(self.buffer.data());
                 trace!(
@@ -260,6 +672,57 @@ impl ArchiveFsm {
                     "initial offset & len"
                 );
                 'read_headers: while !input.is_empty() {
+                    if input
+                        .as_bytes()
+                        .starts_with(DigitalSignature::SIGNATURE.as_bytes())
+                    {
+                        match DigitalSignature::parser.parse_next(&mut input) {
+                            Ok(signature) => {
+                                trace!(
+                                    len = signature.data.len(),
+                                    "ReadCentralDirectory | parsed digital signature"
+                                );
+                                valid_consumed = input.as_bytes().offset_from(&self.buffer.data());
+                                self.digital_signature = Some(signature.data.into_owned());
+                                // the headers are over, the next iteration
+                                // wraps up
+                                continue 'read_headers;
+                            }
+                            Err(ErrMode::Incomplete(_needed)) => {
+                                trace!("ReadCentralDirectory | incomplete digital signature!");
+                                break 'read_headers;
+                            }
+                            // not a signature record after all: the header
+                            // parser below gives up on it too
+                            Err(_) => {}
+                        }
+                    }
+
+                    if let Some((name_len, extra_len, comment_len)) =
+                        CentralDirectoryFileHeader::peek_lengths(input.as_bytes())
+                    {
+                        // reject oversized headers before waiting for them
+                        // to be buffered in their entirety
+                        let limits = &self.limits;
+                        limits.check("name", name_len, limits.max_name_len)?;
+                        limits.check("extra field", extra_len, limits.max_extra_len)?;
+                        limits.check("comment", comment_len, limits.max_comment_len)?;
+
+                        let header_len = CentralDirectoryFileHeader::MIN_LENGTH
+                            + name_len as usize
+                            + extra_len as usize
+                            + comment_len as usize;
+                        let capacity = self.buffer.buffer.capacity();
+                        if header_len > capacity {
+                            return Err(FormatError::FieldTooLong {
+                                field: "central directory header",
+                                len: header_len,
+                                max: capacity,
+                            }
+                            .into());
+                        }
+                    }
+
                     match CentralDirectoryFileHeader::parser.parse_next(&mut input) {
                         Ok(dh) => {
                             trace!(
@@ -269,7 +732,29 @@ impl ArchiveFsm {
                                 "ReadCentralDirectory | parsed directory header"
                             );
                             valid_consumed = input.as_bytes().offset_from(&self.buffer.data());
-                            directory_headers.push(dh.into_owned());
+                            let found = match self.streamed_entries.as_mut() {
+                                Some(streamed_entries) => {
+                                    let encoding = match self.encoding {
+                                        Some(encoding) => encoding,
+                                        None => detect_encoding(std::slice::from_ref(&dh)),
+                                    };
+                                    streamed_entries.push_back(to_entry(
+                                        &dh,
+                                        encoding,
+                                        eocd,
+                                        self.disk_offsets.as_deref(),
+                                    )?);
+                                    self.streamed_count += 1;
+                                    self.streamed_count
+                                }
+                                None => {
+                                    directory_headers.push(dh.into_owned());
+                                    directory_headers.len()
+                                }
+                            };
+                            // the record count can wrap around (or lie), so
+                            // count what we actually found too
+                            self.limits.check_entries(found as u64, self.size)?;
                         }
                         Err(ErrMode::Incomplete(_needed)) => {
                             // need more data to read the full header
@@ -280,96 +765,42 @@ impl ArchiveFsm {
                             // this is the normal end condition when reading
                             // the central directory (due to 65536-entries non-zip64 files)
                             // let's just check a few numbers first.
+                            trace!(
+                                "ReadCentralDirectory | end of headers, the last failed with: {err:?} (display: {err}). at that point, input had length {}",
+                                input.len()
+                            );
+                            let truncated = check_record_count(
+                                directory_headers.len() + self.streamed_count,
+                                eocd,
+                                self.recovery,
+                            )?;
 
-                            // only compare 16 bits here
-                            let expected_records = directory_headers.len() as u16;
-                            let actual_records = eocd.directory_records() as u16;
-
-                            if expected_records != actual_records {
-                                tracing::trace!(
-                                    "error while reading central records: we read {} records, but EOCD announced {}. the last failed with: {err:?} (display: {err}). at that point, input had length {}",
-                                    expected_records,
-                                    actual_records,
-                                    input.len()
-                                );
-
-                                // if we read the wrong number of directory entries,
-                                // error out.
-                                return Err(FormatError::InvalidCentralRecord {
-                                    expected: expected_records,
-                                    actual: actual_records,
-                                }
-                                .into());
-                            }
-
-                            let mut detectorng = chardetng::EncodingDetector::new();
-                            let mut all_utf8 = true;
-                            let mut had_suspicious_chars_for_cp437 = false;
-
-                            {
-                                let max_feed: usize = 4096;
-                                let mut total_fed: usize = 0;
-                                let mut feed = |slice: &[u8]| {
-                                    detectorng.feed(slice, false);
-                                    for b in slice {
-                                        if (0xB0..=0xDF).contains(b) {
-                                            // those are, like, box drawing characters
-                                            had_suspicious_chars_for_cp437 = true;
-                                        }
-                                    }
-
-                                    total_fed += slice.len();
-                                    total_fed < max_feed
-                                };
-
-                                'recognize_encoding: for fh in
-                                    directory_headers.iter().filter(|fh| fh.is_non_utf8())
-                                {
-                                    all_utf8 = false;
-                                    if !feed(&fh.name[..]) || !feed(&fh.comment[..]) {
-                                        break 'recognize_encoding;
-                                    }
-                                }
-                            }
-
-                            let encoding = {
-                                if all_utf8 {
-                                    Encoding::Utf8
-                                } else {
-                                    let encoding = detectorng.guess(None, true);
-                                    if encoding == encoding_rs::SHIFT_JIS {
-                                        // well hold on, sometimes Codepage 437 is detected as
-                                        // Shift-JIS by chardetng. If we have any characters
-                                        // that aren't valid DOS file names, then okay it's probably
-                                        // Shift-JIS. Otherwise, assume it's CP437.
-                                        if had_suspicious_chars_for_cp437 {
-                                            Encoding::ShiftJis
-                                        } else {
-                                            Encoding::Cp437
-                                        }
-                                    } else if encoding == encoding_rs::UTF_8 {
-                                        Encoding::Utf8
-                                    } else {
-                                        Encoding::Cp437
-                                    }
-                                }
+                            let encoding = match self.encoding {
+                                Some(encoding) => encoding,
+                                None => detect_encoding(directory_headers),
                             };
 
-                            let global_offset = eocd.global_offset as u64;
+                            let disk_offsets = self.disk_offsets.as_deref();
                             let entries: Result<Vec<Entry>, Error> = directory_headers
                                 .iter()
-                                .map(|x| x.as_entry(encoding, global_offset))
+                                .map(|x| to_entry(x, encoding, eocd, disk_offsets))
                                 .collect();
-                            let entries = entries?;
+                            let mut entries = entries?;
+                            match self.streamed_entries.take() {
+                                // whatever wasn't taken with next_entry yet
+                                Some(streamed_entries) => entries = streamed_entries.into(),
+                                None => mark_implicit_directories(&mut entries),
+                            }
 
-                            let comment = encoding.decode(eocd.comment())?;
+                            let S::ReadCentralDirectory { eocd, .. } =
+                                std::mem::take(&mut self.state)
+                            else {
+                                unreachable!()
+                            };
 
-                            return Ok(FsmResult::Done(Archive {
-                                size: self.size,
-                                comment,
-                                entries,
-                                encoding,
-                            }));
+                            return Ok(FsmResult::Done(
+                                self.into_archive(eocd, entries, encoding, truncated),
+                            ));
                         }
                     }
                 }
@@ -380,10 +811,130 @@ impl ArchiveFsm {
                 // need more data
                 Ok(FsmResult::Continue(self))
             }
+            #[cfg(feature = "rayon")]
+            S::ReadWholeCentralDirectory {
+                ref eocd,
+                ref mut directory,
+            } => {
+                let remaining = eocd.directory_size() - directory.len() as u64;
+                let data = self.buffer.data();
+                let n = (data.len() as u64).min(remaining) as usize;
+                directory.extend_from_slice(&data[..n]);
+                self.buffer.consume(n);
+                if (n as u64) < remaining {
+                    // need more data
+                    return Ok(FsmResult::Continue(self));
+                }
+
+                let S::ReadWholeCentralDirectory { eocd, directory } =
+                    std::mem::take(&mut self.state)
+                else {
+                    unreachable!()
+                };
+                let archive = self.parse_directory_parallel(eocd, &directory)?;
+                Ok(FsmResult::Done(archive))
+            }
             S::Transitioning => unreachable!(),
         }
     }
 
+    /// Parses the headers of a central directory read as a whole, in
+    /// parallel.
+    #[cfg(feature = "rayon")]
+    fn parse_directory_parallel(
+        self,
+        eocd: EndOfCentralDirectory<'static>,
+        directory: &[u8],
+    ) -> Result<Archive, Error> {
+        use rayon::prelude::*;
+
+        // headers have variable lengths, so finding where each of them
+        // starts can't be done in parallel - but it's cheap, parsing them
+        // is what takes time.
+        let limits = &self.limits;
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        while let Some((name_len, extra_len, comment_len)) =
+            CentralDirectoryFileHeader::peek_lengths(&directory[offset..])
+        {
+            limits.check("name", name_len, limits.max_name_len)?;
+            limits.check("extra field", extra_len, limits.max_extra_len)?;
+            limits.check("comment", comment_len, limits.max_comment_len)?;
+
+            let header_len = CentralDirectoryFileHeader::MIN_LENGTH
+                + name_len as usize
+                + extra_len as usize
+                + comment_len as usize;
+            if offset + header_len > directory.len() {
+                break;
+            }
+            ranges.push(offset..offset + header_len);
+            limits.check_entries(ranges.len() as u64, self.size)?;
+            offset += header_len;
+        }
+        let digital_signature = DigitalSignature::parser
+            .parse_peek(Partial::new(&directory[offset..]))
+            .ok()
+            .map(|(_, signature)| signature.data.into_owned());
+        trace!(
+            headers = ranges.len(),
+            "ReadWholeCentralDirectory | found headers"
+        );
+
+        // like when reading headers one by one, the first one that doesn't
+        // parse marks the end of the central directory
+        let directory_headers: Vec<_> = ranges
+            .into_par_iter()
+            .map(|range| {
+                CentralDirectoryFileHeader::parser
+                    .parse_peek(Partial::new(&directory[range]))
+                    .ok()
+                    .map(|(_, dh)| dh)
+            })
+            .collect();
+        let directory_headers: Vec<_> = directory_headers.into_iter().map_while(|dh| dh).collect();
+        let truncated = check_record_count(directory_headers.len(), &eocd, self.recovery)?;
+
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => detect_encoding(&directory_headers),
+        };
+
+        let disk_offsets = self.disk_offsets.as_deref();
+        let entries: Result<Vec<Entry>, Error> = directory_headers
+            .par_iter()
+            .map(|x| to_entry(x, encoding, &eocd, disk_offsets))
+            .collect();
+        let mut entries = entries?;
+        mark_implicit_directories(&mut entries);
+        let mut archive = self.into_archive(eocd, entries, encoding, truncated);
+        archive.digital_signature = digital_signature;
+        Ok(archive)
+    }
+
+    /// Wraps up reading the archive, once all its entries are known.
+    fn into_archive(
+        self,
+        eocd: EndOfCentralDirectory<'static>,
+        entries: Vec<Entry>,
+        encoding: Encoding,
+        truncated: bool,
+    ) -> Archive {
+        let comment = encoding.decode_lossy(eocd.comment());
+
+        Archive {
+            size: self.size,
+            comment,
+            comment_encoding: encoding,
+            names: Archive::index_names(&entries),
+            entries,
+            encoding,
+            eocd,
+            truncated,
+            digital_signature: self.digital_signature,
+        }
+    }
+
     /// Returns a mutable slice with all the available space to write to.
     ///
     /// After writing to this, call [Self::fill] with the number of bytes written.
@@ -399,7 +950,142 @@ impl ArchiveFsm {
     /// many bytes were written.
     #[inline]
     pub fn fill(&mut self, count: usize) -> usize {
-        self.buffer.fill(count)
+        let n = self.buffer.fill(count);
+        self.bytes_read += n as u64;
+        n
+    }
+}
+
+/// Converts a central directory header into an entry, whose header offset
+/// is translated to an offset in the file: past any data preceding the
+/// archive, and to the start of its disk for split archives.
+fn to_entry(
+    dh: &CentralDirectoryFileHeader<'_>,
+    encoding: Encoding,
+    eocd: &EndOfCentralDirectory<'_>,
+    disk_offsets: Option<&[u64]>,
+) -> Result<Entry, Error> {
+    let global_offset = eocd.global_offset as u64;
+    let Some(disk_offsets) = disk_offsets else {
+        return dh.as_entry(encoding, global_offset);
+    };
+
+    // the disk number may come from the zip64 extra field, so it's only
+    // known once the entry is parsed. the global offset was worked out from
+    // where the central directory actually is, so it includes the start of
+    // the disk it's on
+    let mut entry = dh.as_entry(encoding, 0)?;
+    let dir_disk_offset = disk_offset(disk_offsets, eocd.dir_disk_nbr())?;
+    let entry_disk_offset = disk_offset(disk_offsets, entry.disk_nbr_start)?;
+    entry.header_offset = global_offset
+        .checked_sub(dir_disk_offset)
+        .and_then(|offset| offset.checked_add(entry_disk_offset))
+        .and_then(|offset| offset.checked_add(entry.header_offset))
+        .ok_or(FormatError::InvalidHeaderOffset)?;
+    Ok(entry)
+}
+
+/// Where `disk` starts, according to [ArchiveFsm::with_disk_offsets].
+fn disk_offset(disk_offsets: &[u64], disk: u32) -> Result<u64, FormatError> {
+    disk_offsets
+        .get(disk as usize)
+        .copied()
+        .ok_or(FormatError::InvalidDiskNumber {
+            disk,
+            total_disks: disk_offsets.len() as u32,
+        })
+}
+
+/// Checks the number of central directory headers found against the number
+/// of records announced by the end of central directory record.
+///
+/// With [RecoveryMode::BestEffort], a mismatch isn't an error: this returns
+/// whether the central directory was cut short instead.
+fn check_record_count(
+    found: usize,
+    eocd: &EndOfCentralDirectory<'_>,
+    recovery: RecoveryMode,
+) -> Result<bool, FormatError> {
+    // only compare 16 bits here
+    let expected_records = found as u16;
+    let actual_records = eocd.directory_records() as u16;
+
+    if expected_records != actual_records {
+        trace!(
+            "error while reading central records: we read {} records, but EOCD announced {}",
+            expected_records,
+            actual_records,
+        );
+
+        if recovery == RecoveryMode::BestEffort {
+            trace!("keeping the {found} entries found");
+            return Ok(true);
+        }
+
+        // if we read the wrong number of directory entries,
+        // error out.
+        return Err(FormatError::InvalidCentralRecord {
+            expected: expected_records,
+            actual: actual_records,
+        });
+    }
+    Ok(false)
+}
+
+/// Guesses the encoding of the names and comments of central directory
+/// headers that aren't flagged (or obviously encoded) as UTF-8.
+fn detect_encoding(directory_headers: &[CentralDirectoryFileHeader<'_>]) -> Encoding {
+    let mut detectorng = chardetng::EncodingDetector::new();
+    let mut all_utf8 = true;
+    let mut had_suspicious_chars_for_cp437 = false;
+
+    {
+        let max_feed: usize = 4096;
+        let mut total_fed: usize = 0;
+        let mut feed = |slice: &[u8]| {
+            detectorng.feed(slice, false);
+            for b in slice {
+                if (0xB0..=0xDF).contains(b) {
+                    // those are, like, box drawing characters
+                    had_suspicious_chars_for_cp437 = true;
+                }
+            }
+
+            total_fed += slice.len();
+            total_fed < max_feed
+        };
+
+        'recognize_encoding: for fh in directory_headers.iter().filter(|fh| fh.is_non_utf8()) {
+            all_utf8 = false;
+            if !feed(&fh.name[..]) || !feed(&fh.comment[..]) {
+                break 'recognize_encoding;
+            }
+        }
+    }
+
+    if all_utf8 {
+        Encoding::Utf8
+    } else {
+        let encoding = detectorng.guess(None, true);
+        if encoding == encoding_rs::SHIFT_JIS {
+            // well hold on, sometimes Codepage 437 is detected as
+            // Shift-JIS by chardetng. If we have any characters
+            // that aren't valid DOS file names, then okay it's probably
+            // Shift-JIS. Otherwise, assume it's CP437.
+            if had_suspicious_chars_for_cp437 {
+                Encoding::ShiftJis
+            } else {
+                Encoding::Cp437
+            }
+        } else if encoding == encoding_rs::UTF_8 {
+            Encoding::Utf8
+        } else if encoding == encoding_rs::GBK {
+            Encoding::Gbk
+        } else if encoding == encoding_rs::EUC_KR {
+            Encoding::EucKr
+        } else {
+            Encoding::Cp437
+        }
     }
 }
 
@@ -493,6 +1179,6 @@ impl Buffer {
     /// [ArchiveFsm], when we read records at fixed offsets within the file,
     /// that possibly take several reads to fully parse.
     pub(crate) fn read_offset(&self, offset: u64) -> u64 {
-        self.read_bytes + offset
+        self.read_bytes.saturating_add(offset)
     }
 }
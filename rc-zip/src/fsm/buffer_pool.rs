@@ -0,0 +1,95 @@
+use std::sync::{Arc, Mutex};
+
+use oval::Buffer;
+
+use super::EntryFsm;
+
+/// A pool of buffers for [EntryFsm], so that readers of different entries
+/// can reuse each other's allocations instead of making their own.
+///
+/// Cloning a pool is cheap, and clones share the same buffers.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    buffers: Mutex<Vec<Buffer>>,
+    buffer_capacity: usize,
+    max_retained: usize,
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(
+            EntryFsm::DEFAULT_BUFFER_CAPACITY,
+            Self::DEFAULT_MAX_RETAINED,
+        )
+    }
+}
+
+impl BufferPool {
+    /// Number of buffers retained by [BufferPool::default]
+    pub const DEFAULT_MAX_RETAINED: usize = 16;
+
+    /// Creates an empty pool of buffers of `buffer_capacity` bytes (rounded
+    /// up to [EntryFsm::MIN_BUFFER_CAPACITY]), which keeps at most
+    /// `max_retained` of them around once they're given back.
+    pub fn new(buffer_capacity: usize, max_retained: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                buffers: Mutex::new(Vec::new()),
+                buffer_capacity: buffer_capacity.max(EntryFsm::MIN_BUFFER_CAPACITY),
+                max_retained,
+            }),
+        }
+    }
+
+    /// Capacity of the buffers handed out by this pool
+    pub fn buffer_capacity(&self) -> usize {
+        self.inner.buffer_capacity
+    }
+
+    /// Takes an empty buffer from the pool, or allocates a new one if the
+    /// pool is empty.
+    pub fn get(&self) -> Buffer {
+        match self.lock().pop() {
+            Some(buffer) => buffer,
+            None => Buffer::with_capacity(self.inner.buffer_capacity),
+        }
+    }
+
+    /// Gives a buffer back to the pool, for later use by [Self::get]. The
+    /// buffer is dropped if the pool is full, or if it's smaller than the
+    /// pool's buffers.
+    pub fn put(&self, mut buffer: Buffer) {
+        if buffer.capacity() < self.inner.buffer_capacity {
+            return;
+        }
+
+        let mut buffers = self.lock();
+        if buffers.len() < self.inner.max_retained {
+            buffer.reset();
+            buffers.push(buffer);
+        }
+    }
+
+    /// Number of buffers currently in the pool
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if there are no buffers in the pool
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Buffer>> {
+        // buffers are reset when given back, so whatever a panicking
+        // thread left behind is still usable
+        self.inner
+            .buffers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -10,7 +10,11 @@ use miniz_oxide::inflate::{
 };
 use tracing::trace;
 
-use crate::{error::Error, fsm::entry::HasMoreInput, parse::Method};
+use crate::{
+    error::{Error, FormatError},
+    fsm::entry::HasMoreInput,
+    parse::Method,
+};
 
 use super::{DecompressOutcome, Decompressor};
 
@@ -93,7 +97,9 @@ impl Decompressor for DeflateDec {
 
         match status {
             TINFLStatus::FailedCannotMakeProgress => {
-                return Err(Error::Decompression { method: Method::Deflate, msg: "Failed to make progress: more input data was expected, but the caller indicated there was no more data, so the input stream is likely truncated".to_string() })
+                // all of the entry's compressed data was fed, but the stream
+                // never got to its final block
+                return Err(FormatError::TruncatedDeflateStream.into())
             }
             TINFLStatus::BadParam => {
 				return Err(Error::Decompression { method: Method::Deflate, msg: "The output buffer is an invalid size; consider the flags parameter".to_string() })
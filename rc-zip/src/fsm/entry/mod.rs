@@ -25,6 +25,12 @@ mod lzma_dec;
 #[cfg(feature = "zstd")]
 mod zstd_dec;
 
+#[cfg(feature = "xz")]
+mod xz_dec;
+
+#[cfg(feature = "ppmd")]
+mod ppmd_dec;
+
 use crate::{
     error::{Error, FormatError, UnsupportedError},
     parse::{DataDescriptorRecord, Entry, LocalFileHeader, Method},
@@ -51,9 +57,17 @@ enum State {
         /// Amount of bytes we've fed to the decompressor
         compressed_bytes: u64,
 
+        /// Compressed size of the entry, if known before reading its data
+        /// (it isn't when streaming entries that have a data descriptor)
+        compressed_size: Option<u64>,
+
         /// Amount of bytes the decompressor has produced
         uncompressed_bytes: u64,
 
+        /// Uncompressed size of the entry, if known before reading its data
+        /// (it isn't when streaming entries that have a data descriptor)
+        expected_size: Option<u64>,
+
         /// CRC32 hash of the decompressed data
         hasher: crc32fast::Hasher,
 
@@ -65,6 +79,18 @@ enum State {
         /// Whether the entry is zip64 (because its compressed size or uncompressed size is u32::MAX)
         is_zip64: bool,
 
+        /// Whether the entry's sizes and CRC-32 are only known from the data
+        /// descriptor
+        sizes_from_descriptor: bool,
+
+        /// Size we've decompressed + crc32 hash we've computed
+        metrics: EntryReadMetrics,
+    },
+
+    ProbeDataDescriptor {
+        /// Amount of bytes we've fed to the decompressor
+        compressed_bytes: u64,
+
         /// Size we've decompressed + crc32 hash we've computed
         metrics: EntryReadMetrics,
     },
@@ -81,31 +107,152 @@ enum State {
     Transition,
 }
 
+/// When [EntryFsm] checks the size and CRC-32 checksum of an entry against
+/// the expected values, see [EntryFsm::with_validation].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Never check: reading an entry whose data is corrupted (or doesn't
+    /// match its headers) succeeds
+    Skip,
+
+    /// Check once the whole entry has been read, which is the default.
+    /// Callers that only read part of an entry never get an error.
+    #[default]
+    OnFullRead,
+
+    /// Like [Validation::OnFullRead], but also fail as soon as the entry
+    /// decompresses to more than its expected size, without waiting for
+    /// the end of its data
+    Always,
+}
+
+/// Caps on how much data [EntryFsm] may decompress, to guard against zip
+/// bombs: entries that decompress to far more data than they take up in the
+/// archive, or than their declared uncompressed size.
+///
+/// All limits are off by default. Going over the output ones fails with
+/// [Error::LimitExceeded].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecompressLimits {
+    /// Maximum number of bytes to decompress from a single entry
+    pub max_output_bytes: Option<u64>,
+
+    /// Maximum ratio of decompressed bytes to compressed bytes read so far.
+    ///
+    /// Small, highly compressible entries (a few kilobytes of zeroes, say)
+    /// legitimately reach ratios in the hundreds, so this should be generous.
+    pub max_ratio: Option<u64>,
+
+    /// Maximum size of the model PPMd entries may ask for. Archives set it,
+    /// up to 256MiB, and it's allocated before decompressing anything.
+    pub max_ppmd_memory: Option<u64>,
+}
+
+impl DecompressLimits {
+    fn check(&self, compressed_bytes: u64, uncompressed_bytes: u64) -> Result<(), Error> {
+        let over_output = self
+            .max_output_bytes
+            .is_some_and(|max| uncompressed_bytes > max);
+        let over_ratio = self.max_ratio.is_some_and(|ratio| {
+            uncompressed_bytes > compressed_bytes.max(1).saturating_mul(ratio)
+        });
+        if over_output || over_ratio {
+            return Err(Error::LimitExceeded {
+                compressed_bytes,
+                uncompressed_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
 /// A state machine that can parse a zip entry
 pub struct EntryFsm {
     state: State,
     entry: Option<Entry>,
     buffer: Buffer,
+    validation: Validation,
+    limits: DecompressLimits,
+    strict_descriptors: bool,
+    /// whether [Self::fill] was called with 0 bytes, see [Self::fill]
+    eof: bool,
 }
 
 impl EntryFsm {
+    /// Capacity of the internal buffer used by [Self::new], when it's not
+    /// given one.
+    pub const DEFAULT_BUFFER_CAPACITY: usize = 256 * 1024;
+
+    /// The smallest internal buffer [Self::with_capacity] accepts. The local
+    /// file header has to fit in the buffer as a whole, so entries with long
+    /// names or extra fields may need a larger one: they fail with
+    /// [FormatError::LocalHeaderTooLarge] otherwise.
+    pub const MIN_BUFFER_CAPACITY: usize = 4 * 1024;
+
     /// Create a new state machine for decompressing a zip entry
     pub fn new(entry: Option<Entry>, buffer: Option<Buffer>) -> Self {
-        const BUF_CAPACITY: usize = 256 * 1024;
+        Self::with_capacity(entry, buffer, Self::DEFAULT_BUFFER_CAPACITY)
+    }
 
+    /// Like [Self::new], but allocates an internal buffer of `capacity`
+    /// bytes (rounded up to [Self::MIN_BUFFER_CAPACITY]) when not given one.
+    ///
+    /// Panics if `buffer` is smaller than [Self::MIN_BUFFER_CAPACITY].
+    pub fn with_capacity(entry: Option<Entry>, buffer: Option<Buffer>, capacity: usize) -> Self {
         Self {
             state: State::ReadLocalHeader,
             entry,
             buffer: match buffer {
                 Some(buffer) => {
-                    assert!(buffer.capacity() >= BUF_CAPACITY, "buffer too small");
+                    assert!(
+                        buffer.capacity() >= Self::MIN_BUFFER_CAPACITY,
+                        "buffer too small"
+                    );
                     buffer
                 }
-                None => Buffer::with_capacity(BUF_CAPACITY),
+                None => Buffer::with_capacity(capacity.max(Self::MIN_BUFFER_CAPACITY)),
             },
+            validation: Validation::default(),
+            limits: Default::default(),
+            strict_descriptors: false,
+            eof: false,
         }
     }
 
+    /// Sets when the uncompressed size and CRC-32 checksum are checked
+    /// against the expected values, see [Validation]. They are once the
+    /// entry has been read by default, and a mismatch is an error.
+    ///
+    /// Skipping validation gives up on integrity checks altogether: corrupted
+    /// data is returned as-is. It's for callers who compute (and check) these
+    /// themselves, and would rather report a mismatch than fail. Callers
+    /// that only want a prefix of an entry don't need to, since validation
+    /// only happens once the end of the entry is reached.
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Sets whether a data descriptor that contradicts the entry's CRC-32
+    /// checksum or sizes (from the central directory) is an error.
+    ///
+    /// The central directory's values are the ones checked against, so such
+    /// a mismatch is only logged by default. Since well-behaved writers never
+    /// produce one, it's a sign of a broken or tampered archive, which
+    /// security tooling may want to reject: in strict mode it fails with
+    /// [FormatError::DescriptorCentralMismatch].
+    pub fn with_strict_descriptors(mut self, strict: bool) -> Self {
+        self.strict_descriptors = strict;
+        self
+    }
+
+    /// Sets the limits enforced while decompressing the entry, see
+    /// [DecompressLimits].
+    pub fn with_limits(mut self, limits: DecompressLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// If this returns true, the caller should read data from into
     /// [Self::space] — without forgetting to call [Self::fill] with the number
     /// of bytes written.
@@ -117,6 +264,9 @@ impl EntryFsm {
                 self.buffer.available_space() > 0
             }
             State::ReadDataDescriptor { .. } => true,
+            State::ProbeDataDescriptor { .. } => {
+                !self.eof && self.buffer.data().len() < MAX_DATA_DESCRIPTOR_LEN
+            }
             State::Validate { .. } => false,
             State::Transition => unreachable!(),
         }
@@ -139,6 +289,22 @@ impl EntryFsm {
         Ok(self.entry.as_ref())
     }
 
+    /// Returns true if, instead of a local file header, the buffered data
+    /// starts with a record that comes after all entries: a central directory
+    /// header, a zip64 end of central directory record, or an end of central
+    /// directory record.
+    ///
+    /// When streaming through an archive, this is how we know there are no
+    /// entries left. Only meaningful before the local header is parsed.
+    pub fn reached_end_of_entries(&self) -> bool {
+        const SIGNATURES: [&[u8]; 3] = [b"PK\x01\x02", b"PK\x06\x06", b"PK\x05\x06"];
+
+        matches!(self.state, State::ReadLocalHeader)
+            && SIGNATURES
+                .iter()
+                .any(|sig| self.buffer.data().starts_with(sig))
+    }
+
     fn internal_process_local_header(&mut self) -> Result<bool, Error> {
         assert!(
             matches!(self.state, State::ReadLocalHeader),
@@ -153,25 +319,66 @@ impl EntryFsm {
                 let decompressor = AnyDecompressor::new(
                     header.method,
                     self.entry.as_ref().map(|entry| entry.uncompressed_size),
+                    &self.limits,
                 )?;
 
-                if self.entry.is_none() {
-                    self.entry = Some(header.as_entry()?);
+                // sizes in the central directory are always set, those in
+                // the local header are zero when there's a data descriptor
+                let sizes_known = self.entry.is_some() || !header.has_data_descriptor();
+
+                if !sizes_known && header.method == Method::Store {
+                    // nothing marks the end of stored data but its size
+                    return Err(UnsupportedError::UnknownStoredSize.into());
+                }
+                if !sizes_known && header.method == Method::Ppmd {
+                    // PPMd data doesn't tell where it ends precisely
+                    // enough to find the data descriptor after it
+                    let err = UnsupportedError::UnknownCompressedSize(header.method);
+                    return Err(err.into());
+                }
+
+                match self.entry.as_mut() {
+                    Some(entry) => {
+                        entry.local_extra = Some((header.extra.to_vec(), header.extra_settings()));
+                    }
+                    None => self.entry = Some(header.as_entry()?),
                 }
+                let (compressed_size, expected_size) = match self.entry.as_ref() {
+                    Some(entry) if sizes_known => {
+                        (Some(entry.compressed_size), Some(entry.uncompressed_size))
+                    }
+                    _ => (None, None),
+                };
 
                 self.state = State::ReadData {
                     is_zip64: header.compressed_size == u32::MAX
                         || header.uncompressed_size == u32::MAX,
                     has_data_descriptor: header.has_data_descriptor(),
                     compressed_bytes: 0,
+                    compressed_size,
                     uncompressed_bytes: 0,
+                    expected_size,
                     hasher: crc32fast::Hasher::new(),
                     decompressor,
                 };
                 self.buffer.consume(consumed);
                 Ok(true)
             }
-            Err(ErrMode::Incomplete(_)) => Ok(false),
+            Err(ErrMode::Incomplete(_)) => {
+                let data = self.buffer.data();
+                if data.len() < self.buffer.capacity() {
+                    return Ok(false);
+                }
+
+                // the buffer is full, reading more won't help. The name and
+                // extra field lengths end the 30 bytes fixed-size part.
+                let field_len = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+                Err(FormatError::LocalHeaderTooLarge {
+                    len: 30 + field_len(26) + field_len(28),
+                    capacity: self.buffer.capacity(),
+                }
+                .into())
+            }
             Err(_e) => Err(Error::Format(FormatError::InvalidLocalHeader)),
         }
     }
@@ -195,6 +402,7 @@ impl EntryFsm {
                 State::ReadLocalHeader => "ReadLocalHeader",
                 State::ReadData { .. } => "ReadData",
                 State::ReadDataDescriptor { .. } => "ReadDataDescriptor",
+                State::ProbeDataDescriptor { .. } => "ProbeDataDescriptor",
                 State::Validate { .. } => "Validate",
                 State::Transition => "Transition",
             },
@@ -219,30 +427,32 @@ impl EntryFsm {
                 }
                 S::ReadData {
                     compressed_bytes,
+                    compressed_size,
                     uncompressed_bytes,
+                    expected_size,
                     hasher,
                     decompressor,
                     ..
                 } => {
                     let in_buf = self.buffer.data();
-                    let entry = self.entry.as_ref().unwrap();
+                    let remaining = compressed_size.map(|size| size - *compressed_bytes);
 
                     // do we have more input to feed to the decompressor?
                     // if so, don't give it an empty read
-                    if in_buf.is_empty() && *compressed_bytes < entry.compressed_size {
+                    if in_buf.is_empty() && remaining != Some(0) {
                         return Ok(FsmResult::Continue((self, Default::default())));
                     }
 
                     // don't feed the decompressor bytes beyond the entry's compressed size
-                    let in_buf_max_len = cmp::min(
-                        in_buf.len(),
-                        entry.compressed_size as usize - *compressed_bytes as usize,
-                    );
+                    let in_buf_max_len = match remaining {
+                        Some(remaining) => cmp::min(in_buf.len() as u64, remaining) as usize,
+                        None => in_buf.len(),
+                    };
                     let in_buf = &in_buf[..in_buf_max_len];
                     let bytes_fed_this_turn = in_buf.len();
 
                     let fed_bytes_after_this = *compressed_bytes + in_buf.len() as u64;
-                    let has_more_input = if fed_bytes_after_this == entry.compressed_size as _ {
+                    let has_more_input = if Some(fed_bytes_after_this) == *compressed_size {
                         HasMoreInput::No
                     } else {
                         HasMoreInput::Yes
@@ -256,6 +466,7 @@ impl EntryFsm {
                         ?has_more_input,
                         "decompressing"
                     );
+                    let out_len = out.len();
 
                     let outcome = decompressor.decompress(in_buf, out, has_more_input)?;
                     self.buffer.consume(outcome.bytes_read);
@@ -263,16 +474,29 @@ impl EntryFsm {
                     trace!(
                         compressed_bytes = *compressed_bytes,
                         uncompressed_bytes = *uncompressed_bytes,
-                        entry_compressed_size = %entry.compressed_size,
+                        ?compressed_size,
                         ?outcome,
                         "decompressed"
                     );
 
-                    if outcome.bytes_written == 0 && *compressed_bytes == entry.compressed_size {
+                    let done = match *compressed_size {
+                        Some(size) => outcome.bytes_written == 0 && *compressed_bytes == size,
+                        // without a compressed size, the data ends when the
+                        // decompressor stops making progress although it
+                        // has input and space for output (past the end of
+                        // its stream)
+                        None => {
+                            outcome.bytes_written == 0
+                                && outcome.bytes_read == 0
+                                && bytes_fed_this_turn > 0
+                                && out_len > 0
+                        }
+                    };
+                    if done {
                         trace!("eof and no bytes written, we're done");
 
                         // we're done, let's read the data descriptor (if there's one)
-                        transition!(self.state => (S::ReadData {  has_data_descriptor, is_zip64, uncompressed_bytes, hasher, .. }) {
+                        transition!(self.state => (S::ReadData {  has_data_descriptor, is_zip64, compressed_bytes, compressed_size, uncompressed_bytes, hasher, .. }) {
                             let metrics = EntryReadMetrics {
                                 uncompressed_size: uncompressed_bytes,
                                 crc32: hasher.finalize(),
@@ -280,10 +504,10 @@ impl EntryFsm {
 
                             if has_data_descriptor {
                                 trace!("transitioning to ReadDataDescriptor");
-                                S::ReadDataDescriptor { metrics, is_zip64 }
+                                S::ReadDataDescriptor { metrics, is_zip64, sizes_from_descriptor: compressed_size.is_none() }
                             } else {
-                                trace!("transitioning to Validate");
-                                S::Validate { metrics, descriptor: None }
+                                trace!("transitioning to ProbeDataDescriptor");
+                                S::ProbeDataDescriptor { compressed_bytes, metrics }
                             }
                         });
                         return self.process(out);
@@ -302,6 +526,17 @@ impl EntryFsm {
                     hasher.update(&out[..outcome.bytes_written]);
                     // update the number of bytes we've decompressed
                     *uncompressed_bytes += outcome.bytes_written as u64;
+                    self.limits.check(*compressed_bytes, *uncompressed_bytes)?;
+
+                    if let (Validation::Always, Some(expected)) = (self.validation, *expected_size)
+                    {
+                        if *uncompressed_bytes > expected {
+                            return Err(Error::Format(FormatError::WrongSize {
+                                expected,
+                                actual: *uncompressed_bytes,
+                            }));
+                        }
+                    }
 
                     trace!(
                         compressed_bytes = *compressed_bytes,
@@ -319,7 +554,15 @@ impl EntryFsm {
                             self.buffer
                                 .consume(input.as_bytes().offset_from(&self.buffer.data()));
                             trace!("data descriptor = {:#?}", descriptor);
-                            transition!(self.state => (S::ReadDataDescriptor { metrics, .. }) {
+                            transition!(self.state => (S::ReadDataDescriptor { metrics, sizes_from_descriptor, .. }) {
+                                if sizes_from_descriptor {
+                                    // the local header had placeholders, the
+                                    // descriptor has the actual values
+                                    let entry = self.entry.as_mut().unwrap();
+                                    entry.crc32 = descriptor.crc32;
+                                    entry.compressed_size = descriptor.compressed_size;
+                                    entry.uncompressed_size = descriptor.uncompressed_size;
+                                }
                                 S::Validate { metrics, descriptor: Some(descriptor) }
                             });
                             self.process(out)
@@ -330,10 +573,28 @@ impl EntryFsm {
                         Err(_e) => Err(Error::Format(FormatError::InvalidDataDescriptor)),
                     }
                 }
+                S::ProbeDataDescriptor { .. } => {
+                    if !self.eof && self.buffer.data().len() < MAX_DATA_DESCRIPTOR_LEN {
+                        // a data descriptor may follow, wait until it's
+                        // buffered as a whole (or the input ends)
+                        return Ok(FsmResult::Continue((self, Default::default())));
+                    }
+
+                    transition!(self.state => (S::ProbeDataDescriptor { compressed_bytes, metrics }) {
+                        let descriptor = probe_data_descriptor(&mut self.buffer, compressed_bytes, &metrics);
+                        S::Validate { metrics, descriptor }
+                    });
+                    self.process(out)
+                }
                 S::Validate {
                     metrics,
                     descriptor,
                 } => {
+                    if self.validation == Validation::Skip {
+                        trace!("validation disabled, done");
+                        return Ok(FsmResult::Done(self.buffer));
+                    }
+
                     let entry = self.entry.as_ref().unwrap();
 
                     let expected_crc32 = if entry.crc32 != 0 {
@@ -358,6 +619,10 @@ impl EntryFsm {
                         }));
                     }
 
+                    if let Some(descriptor) = descriptor.as_ref() {
+                        check_descriptor(entry, descriptor, self.strict_descriptors)?;
+                    }
+
                     Ok(FsmResult::Done(self.buffer))
                 }
                 S::Transition => {
@@ -380,12 +645,98 @@ impl EntryFsm {
 
     /// After having written data to [Self::space], call this to indicate how
     /// many bytes were written.
+    ///
+    /// Calling it with 0 means the input has ended: the state machine then
+    /// stops waiting for bytes that may follow the entry's data.
     #[inline]
     pub fn fill(&mut self, count: usize) -> usize {
+        if count == 0 {
+            self.eof = true;
+        }
         self.buffer.fill(count)
     }
 }
 
+/// Size of the largest data descriptor: a zip64 one, with its signature
+const MAX_DATA_DESCRIPTOR_LEN: usize = 4 + 4 + 8 + 8;
+
+/// Some writers emit a data descriptor without setting bit 3 of the general
+/// purpose flag. If the bytes following an entry's data look like a data
+/// descriptor for exactly what we just decompressed, consume them, so that
+/// whatever comes next (usually another local header) is parsed correctly.
+///
+/// The caller should have buffered [MAX_DATA_DESCRIPTOR_LEN] bytes, unless
+/// the input ended before that.
+fn probe_data_descriptor(
+    buffer: &mut Buffer,
+    compressed_size: u64,
+    metrics: &EntryReadMetrics,
+) -> Option<DataDescriptorRecord> {
+    for is_zip64 in [false, true] {
+        let mut input = Partial::new(buffer.data());
+        let Ok(descriptor) = DataDescriptorRecord::mk_parser(is_zip64).parse_next(&mut input)
+        else {
+            continue;
+        };
+
+        if descriptor.crc32 == metrics.crc32
+            && descriptor.compressed_size == compressed_size
+            && descriptor.uncompressed_size == metrics.uncompressed_size
+        {
+            trace!(
+                ?descriptor,
+                "found a data descriptor the flags didn't announce"
+            );
+            let consumed = input.as_bytes().offset_from(&buffer.data());
+            buffer.consume(consumed);
+            return Some(descriptor);
+        }
+    }
+    None
+}
+
+/// Compares a data descriptor with the values from the central directory,
+/// see [EntryFsm::with_strict_descriptors].
+fn check_descriptor(
+    entry: &Entry,
+    descriptor: &DataDescriptorRecord,
+    strict: bool,
+) -> Result<(), FormatError> {
+    let fields = [
+        ("crc32", entry.crc32 as u64, descriptor.crc32 as u64),
+        (
+            "compressed size",
+            entry.compressed_size,
+            descriptor.compressed_size,
+        ),
+        (
+            "uncompressed size",
+            entry.uncompressed_size,
+            descriptor.uncompressed_size,
+        ),
+    ];
+    for (field, central, in_descriptor) in fields {
+        if central == in_descriptor {
+            continue;
+        }
+        tracing::warn!(
+            name = %entry.name,
+            field,
+            central,
+            descriptor = in_descriptor,
+            "data descriptor contradicts the central directory"
+        );
+        if strict {
+            return Err(FormatError::DescriptorCentralMismatch {
+                field,
+                central,
+                descriptor: in_descriptor,
+            });
+        }
+    }
+    Ok(())
+}
+
 enum AnyDecompressor {
     Store(store_dec::StoreDec),
     #[cfg(feature = "deflate")]
@@ -398,6 +749,10 @@ enum AnyDecompressor {
     Lzma(Box<lzma_dec::LzmaDec>),
     #[cfg(feature = "zstd")]
     Zstd(zstd_dec::ZstdDec),
+    #[cfg(feature = "xz")]
+    Xz(xz_dec::XzDec),
+    #[cfg(feature = "ppmd")]
+    Ppmd(Box<ppmd_dec::PpmdDec>),
 }
 
 #[derive(Default, Debug)]
@@ -426,7 +781,11 @@ trait Decompressor {
 }
 
 impl AnyDecompressor {
-    fn new(method: Method, #[allow(unused)] uncompressed_size: Option<u64>) -> Result<Self, Error> {
+    fn new(
+        method: Method,
+        #[allow(unused)] uncompressed_size: Option<u64>,
+        #[allow(unused)] limits: &DecompressLimits,
+    ) -> Result<Self, Error> {
         let dec = match method {
             Method::Store => Self::Store(Default::default()),
 
@@ -470,6 +829,25 @@ impl AnyDecompressor {
                 return Err(err);
             }
 
+            #[cfg(feature = "xz")]
+            Method::Xz => Self::Xz(xz_dec::XzDec::new()?),
+            #[cfg(not(feature = "xz"))]
+            Method::Xz => {
+                let err = Error::Unsupported(UnsupportedError::MethodNotEnabled(method));
+                return Err(err);
+            }
+
+            #[cfg(feature = "ppmd")]
+            Method::Ppmd => Self::Ppmd(Box::new(ppmd_dec::PpmdDec::new(
+                uncompressed_size,
+                limits.max_ppmd_memory,
+            ))),
+            #[cfg(not(feature = "ppmd"))]
+            Method::Ppmd => {
+                let err = Error::Unsupported(UnsupportedError::MethodNotEnabled(method));
+                return Err(err);
+            }
+
             _ => {
                 let err = Error::Unsupported(UnsupportedError::MethodNotSupported(method));
                 return Err(err);
@@ -500,6 +878,10 @@ impl Decompressor for AnyDecompressor {
             Self::Lzma(dec) => dec.decompress(in_buf, out, has_more_input),
             #[cfg(feature = "zstd")]
             Self::Zstd(dec) => dec.decompress(in_buf, out, has_more_input),
+            #[cfg(feature = "xz")]
+            Self::Xz(dec) => dec.decompress(in_buf, out, has_more_input),
+            #[cfg(feature = "ppmd")]
+            Self::Ppmd(dec) => dec.decompress(in_buf, out, has_more_input),
         }
     }
 }
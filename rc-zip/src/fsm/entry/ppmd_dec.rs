@@ -0,0 +1,166 @@
+use std::{cmp, collections::VecDeque, io::Read};
+
+use ppmd_rust::{Ppmd8Decoder, RestoreMethod, PPMD8_MAX_ORDER};
+use tracing::trace;
+
+use crate::{error::Error, parse::Method};
+
+use super::{DecompressOutcome, Decompressor, HasMoreInput};
+
+/// How much compressed data is buffered ahead of the decoder
+const WINDOW_CAPACITY: usize = 64 * 1024;
+
+/// Input the decoder may need for a single symbol: the range decoder reads
+/// a few bytes for each context it escapes from, and there are at most
+/// [PPMD8_MAX_ORDER] of those.
+const MAX_SYMBOL_INPUT: usize = 16 * PPMD8_MAX_ORDER as usize;
+
+/// Parameters (2 bytes) plus the initial range decoder code (4 bytes)
+const HEADER_LEN: usize = 2 + 4;
+
+/// Compressed data fed so far, which the decoder pulls its input from.
+///
+/// The decoder can't be suspended in the middle of a symbol, and takes a
+/// short read for the end of the data, so it's only asked for as many
+/// symbols as the window is sure to hold input for.
+#[derive(Default)]
+struct Window(VecDeque<u8>);
+
+impl Read for Window {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+enum State {
+    /// Waiting for the parameters and the start of the compressed data
+    Header(Window),
+
+    /// Decoding, as input comes in
+    Decoding(Box<Ppmd8Decoder<Window>>),
+
+    /// The decoder is done, any remaining input is ignored
+    Done,
+}
+
+/// PPMd variant I, revision 1, as written by WinZip and 7-Zip.
+///
+/// The end of the compressed data can't be told from the data itself with
+/// enough precision to find what follows, so its size must be known.
+pub(crate) struct PpmdDec {
+    state: State,
+    uncompressed_size: Option<u64>,
+    max_memory: Option<u64>,
+    decoded: u64,
+}
+
+impl PpmdDec {
+    pub fn new(uncompressed_size: Option<u64>, max_memory: Option<u64>) -> Self {
+        Self {
+            state: State::Header(Default::default()),
+            uncompressed_size,
+            max_memory,
+            decoded: 0,
+        }
+    }
+
+    /// Parses the 2-byte parameters header at the start of the window, and
+    /// sets up a decoder for the rest of it
+    fn start_decoder(&self, mut window: Window) -> Result<Ppmd8Decoder<Window>, Error> {
+        let (Some(lo), Some(hi)) = (window.0.pop_front(), window.0.pop_front()) else {
+            return Err(dec_err("missing PPMd parameters"));
+        };
+        let params = u16::from_le_bytes([lo, hi]);
+        let order = (params & 0xf) as u32 + 1;
+        let mem_size = (((params >> 4) & 0xff) as u32 + 1) * 1024 * 1024;
+        let restore_method = match params >> 12 {
+            0 => RestoreMethod::Restart,
+            1 => RestoreMethod::CutOff,
+            other => return Err(dec_err(format!("unknown PPMd restore method {other}"))),
+        };
+        trace!(order, mem_size, "PPMd parameters");
+
+        if let Some(max) = self.max_memory.filter(|&max| mem_size as u64 > max) {
+            return Err(dec_err(format!(
+                "PPMd model needs {mem_size} bytes, more than the {max} allowed"
+            )));
+        }
+
+        Ppmd8Decoder::new(window, order, mem_size, restore_method).map_err(dec_err)
+    }
+}
+
+impl Decompressor for PpmdDec {
+    fn decompress(
+        &mut self,
+        in_buf: &[u8],
+        out: &mut [u8],
+        has_more_input: HasMoreInput,
+    ) -> Result<DecompressOutcome, Error> {
+        let mut outcome: DecompressOutcome = Default::default();
+
+        let window = match &mut self.state {
+            State::Header(window) => window,
+            State::Decoding(decoder) => decoder.get_mut(),
+            State::Done => {
+                outcome.bytes_read = in_buf.len();
+                return Ok(outcome);
+            }
+        };
+        let n = cmp::min(in_buf.len(), WINDOW_CAPACITY.saturating_sub(window.0.len()));
+        window.0.extend(&in_buf[..n]);
+        outcome.bytes_read = n;
+        let input_done = matches!(has_more_input, HasMoreInput::No) && n == in_buf.len();
+
+        if let State::Header(window) = &mut self.state {
+            if window.0.len() < HEADER_LEN && !input_done {
+                return Ok(outcome);
+            }
+            let window = std::mem::take(window);
+            self.state = State::Decoding(Box::new(self.start_decoder(window)?));
+        }
+
+        if let State::Decoding(decoder) = &mut self.state {
+            while outcome.bytes_written < out.len() {
+                let mut max = out.len() - outcome.bytes_written;
+                if let Some(size) = self.uncompressed_size {
+                    if self.decoded == size {
+                        trace!(decoded = self.decoded, "decoded the whole PPMd entry");
+                        self.state = State::Done;
+                        break;
+                    }
+                    max = cmp::min(max as u64, size - self.decoded) as usize;
+                }
+                if !input_done {
+                    max = cmp::min(max, decoder.get_ref().0.len() / MAX_SYMBOL_INPUT);
+                    if max == 0 {
+                        // wait for more input
+                        break;
+                    }
+                }
+
+                let written = decoder
+                    .read(&mut out[outcome.bytes_written..][..max])
+                    .map_err(dec_err)?;
+                outcome.bytes_written += written;
+                self.decoded += written as u64;
+
+                // the end marker, or the end of the input
+                if written == 0 {
+                    trace!(decoded = self.decoded, "done decoding PPMd stream");
+                    self.state = State::Done;
+                    break;
+                }
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
+fn dec_err(e: impl std::fmt::Display) -> Error {
+    Error::Decompression {
+        method: Method::Ppmd,
+        msg: e.to_string(),
+    }
+}
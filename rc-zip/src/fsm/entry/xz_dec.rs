@@ -0,0 +1,75 @@
+use xz2::stream::{Action, Status, Stream};
+
+use crate::{error::Error, parse::Method};
+
+use super::{DecompressOutcome, Decompressor, HasMoreInput};
+
+pub(crate) struct XzDec {
+    inner: Stream,
+    eof: bool,
+}
+
+impl XzDec {
+    pub fn new() -> Result<Self, Error> {
+        // same memory limit as the LZMA decoder
+        let memlimit = 128 * 1024 * 1024;
+        // the end of the (single) xz stream is the end of the entry, which
+        // matters when streaming entries whose size isn't known up front
+        let flags = 0;
+        let inner = Stream::new_stream_decoder(memlimit, flags).map_err(dec_err)?;
+        Ok(Self { inner, eof: false })
+    }
+}
+
+impl Decompressor for XzDec {
+    fn decompress(
+        &mut self,
+        in_buf: &[u8],
+        out: &mut [u8],
+        has_more_input: HasMoreInput,
+    ) -> Result<DecompressOutcome, Error> {
+        tracing::trace!(
+            in_buf_len = in_buf.len(),
+            out_len = out.len(),
+            total_in = self.inner.total_in(),
+            total_out = self.inner.total_out(),
+            "XzDec::decompress",
+        );
+
+        if self.eof {
+            return Ok(DecompressOutcome {
+                bytes_written: 0,
+                bytes_read: 0,
+            });
+        }
+
+        // once the decoder has all the input, it should error out on a
+        // truncated stream rather than wait for more
+        let action = match has_more_input {
+            HasMoreInput::Yes => Action::Run,
+            HasMoreInput::No => Action::Finish,
+        };
+
+        let before_in = self.inner.total_in();
+        let before_out = self.inner.total_out();
+
+        let status = self.inner.process(in_buf, out, action).map_err(dec_err)?;
+        tracing::trace!("status: {:?}", status);
+        if status == Status::StreamEnd {
+            self.eof = true;
+        }
+
+        let outcome = DecompressOutcome {
+            bytes_written: (self.inner.total_out() - before_out) as usize,
+            bytes_read: (self.inner.total_in() - before_in) as usize,
+        };
+        Ok(outcome)
+    }
+}
+
+fn dec_err(e: impl std::fmt::Display) -> Error {
+    Error::Decompression {
+        method: Method::Xz,
+        msg: e.to_string(),
+    }
+}
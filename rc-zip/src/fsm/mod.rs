@@ -8,6 +8,9 @@
 //! data (while calculating the CRC32), then the data descriptor, and then
 //! checking whether the uncompressed size and CRC32 match the values in the
 //! central directory.
+//!
+//! Writing an archive is the reverse: [WriteFsm] produces local headers, data
+//! and finally the central directory, for the caller to write out.
 
 macro_rules! transition {
     ($state: expr => ($pattern: pat) $body: expr) => {
@@ -20,10 +23,16 @@ macro_rules! transition {
 }
 
 mod archive;
-pub use archive::ArchiveFsm;
+pub use archive::{ArchiveFsm, ArchiveLimits, RecoveryMode};
 
 mod entry;
-pub use entry::EntryFsm;
+pub use entry::{DecompressLimits, EntryFsm, Validation};
+
+mod buffer_pool;
+pub use buffer_pool::BufferPool;
+
+mod writer;
+pub use writer::{EntryOptions, WriteFsm};
 
 /// Indicates whether or not the state machine has completed its work
 pub enum FsmResult<M, R> {
@@ -0,0 +1,55 @@
+use miniz_oxide::deflate::core::{
+    compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+
+use crate::{
+    error::{Error, WriteError},
+    parse::Method,
+};
+
+use super::Encoder;
+
+pub(crate) struct DeflateEnc {
+    /// The miniz_oxide compressor state
+    state: CompressorOxide,
+}
+
+impl DeflateEnc {
+    pub(crate) fn new(level: u8) -> Self {
+        // negative window bits: raw deflate stream, without zlib header
+        let flags = create_comp_flags_from_zip_params(level as i32, -15, 0);
+        Self {
+            state: CompressorOxide::new(flags),
+        }
+    }
+
+    fn compress(
+        &mut self,
+        input: &[u8],
+        out: &mut Vec<u8>,
+        flush: TDEFLFlush,
+    ) -> Result<(), Error> {
+        let (status, _) = compress_to_output(&mut self.state, input, flush, |chunk| {
+            out.extend_from_slice(chunk);
+            true
+        });
+        match status {
+            TDEFLStatus::Okay | TDEFLStatus::Done => Ok(()),
+            status => Err(WriteError::Compression {
+                method: Method::Deflate,
+                msg: format!("{status:?}"),
+            }
+            .into()),
+        }
+    }
+}
+
+impl Encoder for DeflateEnc {
+    fn encode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        self.compress(input, out, TDEFLFlush::None)
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        self.compress(&[], out, TDEFLFlush::Finish)
+    }
+}
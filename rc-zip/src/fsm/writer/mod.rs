@@ -0,0 +1,606 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use tracing::trace;
+
+use crate::{
+    error::{Error, FormatError, UnsupportedError, WriteError},
+    parse::{
+        CentralDirectoryFileHeader, DataDescriptorRecord, EndOfCentralDirectory64Locator,
+        EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, Entry, EntryBuilder,
+        HostSystem, LocalFileHeader, Method, MethodSpecific, Mode, MsdosMode, MsdosTimestamp,
+        UnixMode, Version,
+    },
+};
+
+mod store_enc;
+
+#[cfg(feature = "deflate")]
+mod deflate_enc;
+
+/// Options for an entry written with [WriteFsm::start_entry].
+#[derive(Debug, Clone)]
+pub struct EntryOptions {
+    method: Method,
+    level: u8,
+    modified: Option<DateTime<Utc>>,
+    mode: Option<Mode>,
+    comment: String,
+}
+
+impl Default for EntryOptions {
+    fn default() -> Self {
+        Self {
+            method: Method::Store,
+            level: Self::DEFAULT_LEVEL,
+            modified: None,
+            mode: None,
+            comment: String::new(),
+        }
+    }
+}
+
+impl EntryOptions {
+    /// Compression level used unless [Self::level] is called
+    pub const DEFAULT_LEVEL: u8 = 6;
+
+    /// Set the compression method: [Method::Store] (the default), or
+    /// [Method::Deflate] if the `deflate` feature is enabled.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the compression level, from 0 (fastest) to 9 (smallest). Higher
+    /// values are capped, and the level is ignored for [Method::Store].
+    pub fn level(mut self, level: u8) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set the "last modified" timestamp, which defaults to the time the
+    /// entry is started.
+    pub fn modified(mut self, modified: DateTime<Utc>) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Set the file mode, including the file type bits. Defaults to a
+    /// directory for names that end with a slash, and to a regular file
+    /// otherwise.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the entry's comment
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+}
+
+/// [WriteFsm] writes a zip archive, without doing any I/O itself.
+///
+/// The loop is as follows:
+///
+///   * Call [Self::start_entry], then [Self::write_data] as many times as
+///     needed, then [Self::finish_entry], for each entry.
+///   * Call [Self::finish] to write the central directory.
+///   * After each of those calls, write [Self::output] to the destination,
+///     then call [Self::consume] with the number of bytes written.
+///
+/// The sizes and CRC-32 checksum of an entry go in its local header, before
+/// its data, so the (compressed) data of the entry in progress is held in
+/// memory until [Self::finish_entry] is called, unless streaming is enabled
+/// with [Self::with_streaming].
+///
+/// Look at [rc-zip-sync](https://crates.io/crates/rc-zip-sync) for a
+/// concrete example.
+#[derive(Default)]
+pub struct WriteFsm {
+    /// Bytes ready to be written out
+    output: Vec<u8>,
+
+    /// Number of bytes produced so far, including those still in `output`
+    offset: u64,
+
+    /// Entries written so far, for the central directory
+    entries: Vec<Entry>,
+
+    /// The entry in progress, if any
+    current: Option<CurrentEntry>,
+
+    /// Whether [Self::finish] was called
+    finished: bool,
+
+    /// Whether entries are followed by data descriptors, see
+    /// [Self::with_streaming]
+    streaming: bool,
+}
+
+struct CurrentEntry {
+    entry: Entry,
+    hasher: crc32fast::Hasher,
+    encoder: AnyEncoder,
+    data: Vec<u8>,
+
+    /// For entries copied with [WriteFsm::start_raw_entry], the number of
+    /// compressed bytes still expected
+    raw_remaining: Option<u64>,
+}
+
+impl WriteFsm {
+    /// Create a new archive writer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable streaming: when enabled, data is available in
+    /// [Self::output] as soon as it's compressed, instead of being held in
+    /// memory until the end of the entry.
+    ///
+    /// Since the sizes and CRC-32 checksum aren't known yet when the local
+    /// header is written, the header only has placeholders (and bit 3 of
+    /// its flags set), and the actual values go in a data descriptor after
+    /// the data. Use this when the destination isn't seekable, like a socket
+    /// or stdout.
+    ///
+    /// Note that stored entries written this way can only be read with the
+    /// central directory: streaming readers have no way to tell where their
+    /// data ends.
+    ///
+    /// This must be set before starting the first entry.
+    pub fn with_streaming(mut self, streaming: bool) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Starts writing an entry named `name`, which should be a relative path
+    /// separated by `/` (with a trailing `/` for directories).
+    pub fn start_entry(&mut self, name: &str, options: &EntryOptions) -> Result<(), Error> {
+        self.check_not_finished()?;
+        if self.current.is_some() {
+            return Err(WriteError::EntryInProgress.into());
+        }
+        let encoder = AnyEncoder::new(options.method, options.level)?;
+        check_len("name", name.len())?;
+        check_len("comment", options.comment.len())?;
+
+        let mut builder = EntryBuilder::new(name)
+            .method(options.method)
+            .modified(options.modified.unwrap_or_else(Utc::now))
+            .comment(options.comment.clone());
+        if let Some(mode) = options.mode {
+            builder = builder.mode(mode);
+        }
+        let mut entry = builder.build();
+        entry.header_offset = self.offset;
+        trace!(
+            name,
+            offset = entry.header_offset,
+            streaming = self.streaming,
+            "WriteFsm | starting entry"
+        );
+
+        if self.streaming {
+            // sizes and CRC-32 are still zero, they go in a data descriptor
+            entry.flags |= 0b1000;
+            let len_before = self.output.len();
+            local_header(&entry).write(&mut self.output);
+            self.offset += (self.output.len() - len_before) as u64;
+        }
+
+        self.current = Some(CurrentEntry {
+            entry,
+            hasher: crc32fast::Hasher::new(),
+            encoder,
+            data: Vec::new(),
+            raw_remaining: None,
+        });
+        Ok(())
+    }
+
+    /// Starts copying `entry`, read from another archive, without
+    /// decompressing it: [Self::write_data] then takes the entry's data
+    /// exactly as stored, which must be `entry.compressed_size` bytes long.
+    ///
+    /// The entry keeps its name, method, CRC-32 checksum, sizes, flags, mode
+    /// and comment. Extra fields aren't copied, so only its MS-DOS timestamp
+    /// is kept (see [Entry::modified_local]). Since its sizes are known
+    /// upfront, its data is available in [Self::output] right away, with no
+    /// data descriptor, even when streaming.
+    pub fn start_raw_entry(&mut self, entry: &Entry) -> Result<(), Error> {
+        self.check_not_finished()?;
+        if self.current.is_some() {
+            return Err(WriteError::EntryInProgress.into());
+        }
+        check_len("name", entry.name_bytes.len())?;
+        check_len("comment", entry.comment_bytes.len())?;
+
+        let mut entry = entry.clone();
+        entry.header_offset = self.offset;
+        entry.disk_nbr_start = 0;
+        // the sizes go in the local header
+        entry.flags &= !0b1000;
+        if needs_zip64(entry.compressed_size) || needs_zip64(entry.uncompressed_size) {
+            entry.reader_version.version = entry.reader_version.version.max(ZIP64_VERSION);
+        }
+        trace!(
+            name = %entry.name,
+            offset = entry.header_offset,
+            method = ?entry.method,
+            "WriteFsm | starting raw entry"
+        );
+
+        let len_before = self.output.len();
+        local_header(&entry).write(&mut self.output);
+        self.offset += (self.output.len() - len_before) as u64;
+
+        self.current = Some(CurrentEntry {
+            raw_remaining: Some(entry.compressed_size),
+            entry,
+            hasher: crc32fast::Hasher::new(),
+            encoder: AnyEncoder::Store(Default::default()),
+            data: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Adds `data` to the entry in progress.
+    pub fn write_data(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.check_not_finished()?;
+        let current = self.current.as_mut().ok_or(WriteError::NoEntryInProgress)?;
+
+        if let Some(remaining) = current.raw_remaining.as_mut() {
+            if data.len() as u64 > *remaining {
+                return Err(WriteError::WrongRawSize {
+                    expected: current.entry.compressed_size,
+                    actual: current.entry.compressed_size - *remaining + data.len() as u64,
+                }
+                .into());
+            }
+            *remaining -= data.len() as u64;
+            self.offset += data.len() as u64;
+            self.output.extend_from_slice(data);
+            return Ok(());
+        }
+
+        current.hasher.update(data);
+        current.entry.uncompressed_size += data.len() as u64;
+        current.encoder.encode(data, &mut current.data)?;
+
+        if self.streaming {
+            current.entry.compressed_size += current.data.len() as u64;
+            self.offset += current.data.len() as u64;
+            self.output.append(&mut current.data);
+        }
+        Ok(())
+    }
+
+    /// Finishes the entry in progress: its local header and data are then
+    /// available in [Self::output].
+    pub fn finish_entry(&mut self) -> Result<(), Error> {
+        self.check_not_finished()?;
+        let CurrentEntry {
+            mut entry,
+            hasher,
+            mut encoder,
+            mut data,
+            raw_remaining,
+        } = self.current.take().ok_or(WriteError::NoEntryInProgress)?;
+
+        if let Some(remaining) = raw_remaining {
+            if remaining > 0 {
+                return Err(WriteError::WrongRawSize {
+                    expected: entry.compressed_size,
+                    actual: entry.compressed_size - remaining,
+                }
+                .into());
+            }
+            if needs_zip64(entry.header_offset) {
+                entry.reader_version.version = entry.reader_version.version.max(ZIP64_VERSION);
+            }
+            trace!(name = %entry.name, "WriteFsm | finishing raw entry");
+            self.entries.push(entry);
+            return Ok(());
+        }
+
+        encoder.finish(&mut data)?;
+
+        entry.crc32 = hasher.finalize();
+        entry.compressed_size += data.len() as u64;
+        let large = needs_zip64(entry.compressed_size) || needs_zip64(entry.uncompressed_size);
+        if large && self.streaming {
+            return Err(WriteError::StreamedEntryTooLarge.into());
+        }
+        if large || needs_zip64(entry.header_offset) {
+            entry.reader_version.version = ZIP64_VERSION;
+        }
+        trace!(
+            name = %entry.name,
+            crc32 = entry.crc32,
+            size = entry.uncompressed_size,
+            "WriteFsm | finishing entry"
+        );
+
+        let len_before = self.output.len();
+        if self.streaming {
+            self.output.append(&mut data);
+            DataDescriptorRecord {
+                crc32: entry.crc32,
+                compressed_size: entry.compressed_size,
+                uncompressed_size: entry.uncompressed_size,
+            }
+            .write(&mut self.output);
+        } else {
+            local_header(&entry).write(&mut self.output);
+            self.output.extend_from_slice(&data);
+        }
+        self.offset += (self.output.len() - len_before) as u64;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Finishes the archive: the central directory and end of central
+    /// directory record are then available in [Self::output]. Nothing can
+    /// be written after this.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        self.check_not_finished()?;
+        if self.current.is_some() {
+            return Err(WriteError::EntryInProgress.into());
+        }
+        self.finished = true;
+
+        let directory_offset = self.offset;
+        let len_before = self.output.len();
+        for entry in &self.entries {
+            central_header(entry).write(&mut self.output);
+        }
+        let directory_size = (self.output.len() - len_before) as u64;
+        self.offset += directory_size;
+
+        let num_entries = self.entries.len() as u64;
+        let len_before = self.output.len();
+        // 0xFFFF entries is the placeholder for "see the zip64 record"
+        if num_entries >= u16::MAX as u64
+            || needs_zip64(directory_offset)
+            || needs_zip64(directory_size)
+        {
+            trace!(
+                num_entries,
+                directory_offset,
+                "WriteFsm | writing zip64 eocd"
+            );
+            let eocd64_offset = self.offset;
+            EndOfCentralDirectory64Record {
+                // not counting the signature and this field
+                record_size: EndOfCentralDirectory64Record::MIN_LENGTH as u64 - 12,
+                creator_version: ZIP64_VERSION as u16,
+                reader_version: ZIP64_VERSION as u16,
+                disk_nbr: 0,
+                dir_disk_nbr: 0,
+                dir_records_this_disk: num_entries,
+                directory_records: num_entries,
+                directory_size,
+                directory_offset,
+            }
+            .write(&mut self.output);
+            EndOfCentralDirectory64Locator {
+                dir_disk_number: 0,
+                directory_offset: eocd64_offset,
+                total_disks: 1,
+            }
+            .write(&mut self.output);
+        }
+
+        let num_entries = num_entries.min(u16::MAX as u64) as u16;
+        EndOfCentralDirectoryRecord {
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: num_entries,
+            directory_records: num_entries,
+            directory_size: directory_size.min(u32::MAX as u64) as u32,
+            directory_offset: directory_offset.min(u32::MAX as u64) as u32,
+            comment: Cow::Borrowed(&[]),
+        }
+        .write(&mut self.output);
+        self.offset += (self.output.len() - len_before) as u64;
+        trace!(
+            entries = self.entries.len(),
+            size = self.offset,
+            "WriteFsm | finished archive"
+        );
+        Ok(())
+    }
+
+    /// Returns the bytes ready to be written out.
+    ///
+    /// After writing some (or all) of them, call [Self::consume] with the
+    /// number of bytes written.
+    #[inline]
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Marks the first `count` bytes of [Self::output] as written.
+    #[inline]
+    pub fn consume(&mut self, count: usize) {
+        self.output.drain(..count);
+    }
+
+    /// Entries written so far, with their sizes, checksums and offsets
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    fn check_not_finished(&self) -> Result<(), WriteError> {
+        if self.finished {
+            return Err(WriteError::AlreadyFinished);
+        }
+        Ok(())
+    }
+}
+
+enum AnyEncoder {
+    Store(store_enc::StoreEnc),
+    #[cfg(feature = "deflate")]
+    Deflate(Box<deflate_enc::DeflateEnc>),
+}
+
+trait Encoder {
+    /// Compresses `input`, appending whatever output is ready to `out`
+    fn encode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Appends the rest of the output to `out`, once all input was encoded
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+impl AnyEncoder {
+    fn new(method: Method, #[allow(unused)] level: u8) -> Result<Self, Error> {
+        let enc = match method {
+            Method::Store => Self::Store(Default::default()),
+
+            #[cfg(feature = "deflate")]
+            Method::Deflate => Self::Deflate(Box::new(deflate_enc::DeflateEnc::new(level))),
+            #[cfg(not(feature = "deflate"))]
+            Method::Deflate => {
+                let err = Error::Unsupported(UnsupportedError::MethodNotEnabled(method));
+                return Err(err);
+            }
+
+            _ => {
+                let err = Error::Unsupported(UnsupportedError::MethodNotSupported(method));
+                return Err(err);
+            }
+        };
+        Ok(enc)
+    }
+}
+
+impl Encoder for AnyEncoder {
+    #[inline]
+    fn encode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Self::Store(enc) => enc.encode(input, out),
+            #[cfg(feature = "deflate")]
+            Self::Deflate(enc) => enc.encode(input, out),
+        }
+    }
+
+    #[inline]
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Self::Store(enc) => enc.finish(out),
+            #[cfg(feature = "deflate")]
+            Self::Deflate(enc) => enc.finish(out),
+        }
+    }
+}
+
+/// Version needed to extract entries that use zip64 extensions
+const ZIP64_VERSION: u8 = 45;
+
+/// Whether `value` can't be stored in a 32-bit field, where `u32::MAX` is the
+/// placeholder for "see the zip64 extra field"
+fn needs_zip64(value: u64) -> bool {
+    value >= u32::MAX as u64
+}
+
+/// Returns `value` if it fits in a 32-bit field, and the `u32::MAX`
+/// placeholder otherwise, in which case `value` is added to `zip64`.
+fn fit_u32(value: u64, zip64: &mut Vec<u64>) -> u32 {
+    if needs_zip64(value) {
+        zip64.push(value);
+        u32::MAX
+    } else {
+        value as u32
+    }
+}
+
+/// Serializes a zip64 extended information extra field (4.5.3) holding
+/// `values`, or nothing if there are none.
+fn zip64_extra(values: &[u64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    if !values.is_empty() {
+        out.extend_from_slice(&0x0001u16.to_le_bytes());
+        out.extend_from_slice(&(values.len() as u16 * 8).to_le_bytes());
+        for value in values {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    out
+}
+
+/// Variable-length fields have their length stored as a u16
+fn check_len(field: &'static str, len: usize) -> Result<(), FormatError> {
+    if len > u16::MAX as usize {
+        return Err(FormatError::FieldTooLong {
+            field,
+            len,
+            max: u16::MAX as usize,
+        });
+    }
+    Ok(())
+}
+
+fn local_header(entry: &Entry) -> LocalFileHeader<'_> {
+    // the local zip64 extra field has both sizes, or nothing (4.5.3)
+    let mut zip64 = Vec::new();
+    if needs_zip64(entry.uncompressed_size) || needs_zip64(entry.compressed_size) {
+        zip64 = vec![entry.uncompressed_size, entry.compressed_size];
+    }
+    let (uncompressed_size, compressed_size) = if zip64.is_empty() {
+        (entry.uncompressed_size as u32, entry.compressed_size as u32)
+    } else {
+        (u32::MAX, u32::MAX)
+    };
+
+    LocalFileHeader {
+        reader_version: entry.reader_version,
+        flags: entry.flags,
+        method: entry.method,
+        modified: MsdosTimestamp::from_naive_datetime(&entry.modified_local),
+        crc32: entry.crc32,
+        compressed_size,
+        uncompressed_size,
+        name: Cow::Borrowed(&entry.name_bytes),
+        extra: Cow::Owned(zip64_extra(&zip64)),
+        method_specific: MethodSpecific::None,
+    }
+}
+
+fn central_header(entry: &Entry) -> CentralDirectoryFileHeader<'_> {
+    let mut external_attrs = UnixMode::from(entry.mode).0 << 16;
+    if entry.mode.has(Mode::DIR) {
+        external_attrs |= MsdosMode::DIR.0;
+    }
+
+    // values that don't fit go in the zip64 extra field, in this order
+    let mut zip64 = Vec::new();
+    let uncompressed_size = fit_u32(entry.uncompressed_size, &mut zip64);
+    let compressed_size = fit_u32(entry.compressed_size, &mut zip64);
+    let header_offset = fit_u32(entry.header_offset, &mut zip64);
+
+    CentralDirectoryFileHeader {
+        // external attributes are always written as Unix mode bits
+        creator_version: Version {
+            host_system: HostSystem::Unix,
+            ..entry.creator_version
+        },
+        reader_version: entry.reader_version,
+        flags: entry.flags,
+        method: entry.method,
+        modified: MsdosTimestamp::from_naive_datetime(&entry.modified_local),
+        crc32: entry.crc32,
+        compressed_size,
+        uncompressed_size,
+        disk_nbr_start: 0,
+        internal_attrs: 0,
+        external_attrs,
+        header_offset,
+        name: Cow::Borrowed(&entry.name_bytes),
+        extra: Cow::Owned(zip64_extra(&zip64)),
+        comment: Cow::Borrowed(&entry.comment_bytes),
+    }
+}
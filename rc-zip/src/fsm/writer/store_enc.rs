@@ -0,0 +1,17 @@
+use crate::error::Error;
+
+use super::Encoder;
+
+#[derive(Default)]
+pub(crate) struct StoreEnc;
+
+impl Encoder for StoreEnc {
+    fn encode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
+        out.extend_from_slice(input);
+        Ok(())
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) -> Result<(), Error> {
+        Ok(())
+    }
+}
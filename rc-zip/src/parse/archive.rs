@@ -1,14 +1,26 @@
-use chrono::{offset::Utc, DateTime, TimeZone};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+};
+
+use chrono::{offset::Utc, DateTime, NaiveDateTime, TimeZone};
 use num_enum::{FromPrimitive, IntoPrimitive};
 use ownable::{IntoOwned, ToOwned};
-use winnow::{binary::le_u16, PResult, Partial};
+use tracing::trace;
+use winnow::{binary::le_u16, PResult, Parser, Partial};
 
 use crate::{
     encoding::Encoding,
-    parse::{Mode, Version},
+    error::{Error, FormatError},
+    parse::{HostSystem, Mode, RawRecord, Version},
 };
 
-use super::{zero_datetime, ExtraField, NtfsAttr};
+use super::{
+    zero_datetime, AesStrength, EndOfCentralDirectory, ExtraField, ExtraFieldSettings, NtfsAttr,
+    NtfsTimestamp,
+};
 
 /// An Archive contains general information about a zip files, along with a list
 /// of [entries][Entry].
@@ -22,7 +34,16 @@ pub struct Archive {
     pub(crate) size: u64,
     pub(crate) encoding: Encoding,
     pub(crate) entries: Vec<Entry>,
+    /// index of the entry for each name, see [Self::by_name]
+    pub(crate) names: HashMap<Box<str>, usize>,
     pub(crate) comment: String,
+    /// see [Self::comment_encoding]
+    pub(crate) comment_encoding: Encoding,
+    pub(crate) eocd: EndOfCentralDirectory<'static>,
+    /// see [Self::truncated]
+    pub(crate) truncated: bool,
+    /// see [Self::digital_signature]
+    pub(crate) digital_signature: Option<Vec<u8>>,
 }
 
 impl Archive {
@@ -32,15 +53,232 @@ impl Archive {
         self.size
     }
 
+    /// The number of bytes before the zip archive itself begins, like the
+    /// executable stub of a self-extracting archive.
+    ///
+    /// This is zero for regular zip files, and for archives whose offsets
+    /// were adjusted to account for the data in front of them (as done by
+    /// `zip -A`): stripping the first `prefix_len` bytes of the file leaves
+    /// a zip file of its own.
+    #[inline(always)]
+    pub fn prefix_len(&self) -> u64 {
+        self.eocd.global_offset.max(0) as u64
+    }
+
+    /// Whether the central directory was cut short: some of the records
+    /// announced by the end of central directory record couldn't be parsed,
+    /// and [Self::entries] only holds the ones before them.
+    ///
+    /// This can only happen when reading with
+    /// [RecoveryMode::BestEffort](crate::fsm::RecoveryMode::BestEffort), and
+    /// for [recovered][Self::recovered] archives.
+    #[inline(always)]
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// The data of the digital signature record found at the end of the
+    /// central directory, if any.
+    ///
+    /// This is returned as-is: its format is up to the writer, and it isn't
+    /// verified in any way.
+    #[inline(always)]
+    pub fn digital_signature(&self) -> Option<&[u8]> {
+        self.digital_signature.as_deref()
+    }
+
     /// Iterate over all files in this zip, read from the central directory.
     pub fn entries(&self) -> impl Iterator<Item = &Entry> {
         self.entries.iter()
     }
 
+    /// Iterate over all entries in this zip along with their
+    /// [normalized names][Entry::normalized_name], for matching entries by
+    /// path. The entries themselves keep their original names.
+    pub fn normalized_entries(&self) -> impl Iterator<Item = (Cow<'_, str>, &Entry)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.normalized_name(), entry))
+    }
+
+    /// Iterate over all entries under the directory `prefix`, at any depth,
+    /// in central directory order: with `foo/bar` (or `foo/bar/`), that's
+    /// `foo/bar/baz.txt` and `foo/bar/qux/`, but not `foo/bar/` itself.
+    ///
+    /// Both `prefix` and entry names are [normalized][Entry::normalized_name]
+    /// before being compared. An empty prefix matches all entries.
+    pub fn entries_under(&self, prefix: &str) -> impl Iterator<Item = &Entry> {
+        let prefix = directory_prefix(prefix);
+        self.entries.iter().filter(move |entry| {
+            let name = entry.normalized_name();
+            name.len() > prefix.len() && name.starts_with(prefix.as_str())
+        })
+    }
+
+    /// Lists what's directly in the directory `prefix`, to render one level
+    /// of a directory tree: the first path component of the names of
+    /// [Self::entries_under] `prefix`, with a trailing slash for directories.
+    ///
+    /// Each child is listed once, in the order it first appears in the
+    /// central directory, whether or not the archive has an entry for it:
+    /// with `foo/bar/baz.txt` as its only entry, the children of `foo` are
+    /// `["bar/"]`.
+    pub fn immediate_children(&self, prefix: &str) -> Vec<String> {
+        let prefix = directory_prefix(prefix);
+        let mut seen = HashSet::new();
+        let mut children = Vec::new();
+        for entry in self.entries_under(&prefix) {
+            let name = entry.normalized_name();
+            let rest = &name[prefix.len()..];
+            let child = match rest.find('/') {
+                Some(slash) => &rest[..=slash],
+                None => rest,
+            };
+            if seen.insert(child.to_owned()) {
+                children.push(child.to_owned());
+            }
+        }
+        children
+    }
+
+    /// Returns the number of entries in this zip (files, directories and
+    /// symlinks alike).
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if this zip has no entries at all.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over all regular files in this zip (see [Entry::kind]).
+    pub fn files(&self) -> impl Iterator<Item = &Entry> {
+        self.entries()
+            .filter(|entry| matches!(entry.kind(), EntryKind::File))
+    }
+
+    /// Iterate over all directories in this zip (see [Entry::kind]).
+    pub fn directories(&self) -> impl Iterator<Item = &Entry> {
+        self.entries()
+            .filter(|entry| matches!(entry.kind(), EntryKind::Directory))
+    }
+
+    /// Iterate over all symbolic links in this zip (see [Entry::kind]).
+    pub fn symlinks(&self) -> impl Iterator<Item = &Entry> {
+        self.entries()
+            .filter(|entry| matches!(entry.kind(), EntryKind::Symlink))
+    }
+
+    /// Returns the compression methods used by files in this archive that
+    /// this build can't decompress (see [Method::is_enabled]), in order of
+    /// first appearance.
+    pub fn disabled_methods(&self) -> Vec<Method> {
+        let mut methods = Vec::new();
+        for entry in self.files() {
+            if !entry.method.is_enabled() && !methods.contains(&entry.method) {
+                methods.push(entry.method);
+            }
+        }
+        methods
+    }
+
+    /// Returns all entries, ordered by `key`.
+    ///
+    /// The sort is stable: entries that compare equal stay in central
+    /// directory order, so the result is the same every time. Use
+    /// [Self::entries] to walk them in the order they're stored in.
+    pub fn entries_sorted_by(&self, key: SortKey) -> Vec<&Entry> {
+        let mut entries: Vec<&Entry> = self.entries.iter().collect();
+        match key {
+            SortKey::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+            SortKey::Size => entries.sort_by_key(|entry| entry.uncompressed_size),
+            SortKey::Modified => entries.sort_by_key(|entry| entry.modified),
+            SortKey::Offset => entries.sort_by_key(|entry| entry.header_offset),
+            SortKey::Method => entries.sort_by_key(|entry| u16::from(entry.method)),
+        }
+        entries
+    }
+
+    /// Consumes the archive, returning all files it contains, in central
+    /// directory order.
+    pub fn into_entries(self) -> Vec<Entry> {
+        self.entries
+    }
+
+    /// Returns a fingerprint of the archive's logical contents: the name,
+    /// uncompressed size, CRC-32 and compression method of every entry.
+    ///
+    /// It is computed from the central directory alone (nothing gets
+    /// decompressed), does not depend on entry order, and is stable across
+    /// runs and platforms, so it can be stored for change detection.
+    ///
+    /// This is a 64-bit FNV-1a hash, not a cryptographic one.
+    pub fn digest(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        fn sort_key(entry: &Entry) -> (&[u8], u64, u32, u16) {
+            let method = u16::from(entry.method);
+            (
+                &entry.name_bytes,
+                entry.uncompressed_size,
+                entry.crc32,
+                method,
+            )
+        }
+        let mut entries: Vec<&Entry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+
+        let mut hash = FNV_OFFSET_BASIS;
+        let mut write = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        };
+        for entry in entries {
+            // length-prefix names so that entry boundaries are unambiguous
+            write(&(entry.name_bytes.len() as u64).to_le_bytes());
+            write(&entry.name_bytes);
+            write(&entry.uncompressed_size.to_le_bytes());
+            write(&entry.crc32.to_le_bytes());
+            write(&u16::from(entry.method).to_le_bytes());
+        }
+        hash
+    }
+
     /// Attempts to look up an entry by name. This is usually a bad idea,
     /// as names aren't necessarily normalized in zip archives.
+    ///
+    /// Lookups are done in constant time, using an index built when the
+    /// archive is read. If several entries have the same name, the first one
+    /// (in central directory order) is returned.
     pub fn by_name<N: AsRef<str>>(&self, name: N) -> Option<&Entry> {
-        self.entries.iter().find(|&x| x.name == name.as_ref())
+        self.names
+            .get(name.as_ref())
+            .map(|&index| &self.entries[index])
+    }
+
+    /// Builds the index used by [Self::by_name]: later entries don't
+    /// replace earlier ones with the same name.
+    pub(crate) fn index_names(entries: &[Entry]) -> HashMap<Box<str>, usize> {
+        let mut names = HashMap::with_capacity(entries.len());
+        for (index, entry) in entries.iter().enumerate() {
+            names.entry(entry.name.as_str().into()).or_insert(index);
+        }
+        names
+    }
+
+    /// Returns the first entry (in central directory order) for which
+    /// `predicate` returns true, if any.
+    pub fn find_first<P>(&self, mut predicate: P) -> Option<&Entry>
+    where
+        P: FnMut(&Entry) -> bool,
+    {
+        self.entries.iter().find(|&entry| predicate(entry))
     }
 
     /// Returns the detected character encoding for text fields
@@ -56,6 +294,126 @@ impl Archive {
     pub fn comment(&self) -> &str {
         &self.comment
     }
+
+    /// Returns the comment for this archive as it is stored in the end of
+    /// central directory record, before decoding.
+    ///
+    /// Use this with [Self::comment_encoding] to decode the comment
+    /// differently, or to keep it byte-for-byte when writing the archive
+    /// back.
+    #[inline(always)]
+    pub fn comment_raw(&self) -> &[u8] {
+        self.eocd.comment()
+    }
+
+    /// Returns the encoding [Self::comment] was decoded with.
+    ///
+    /// That's the archive's [encoding][Self::encoding], except after
+    /// [Self::set_comment], which stores the new comment as UTF-8.
+    #[inline(always)]
+    pub fn comment_encoding(&self) -> Encoding {
+        self.comment_encoding
+    }
+
+    /// Replaces the comment for this archive, so it can be written back.
+    ///
+    /// The comment is stored as UTF-8, and its length is a u16 in the end of
+    /// central directory record: comments longer than 65535 bytes are
+    /// rejected with [FormatError::CommentTooLong], and the current comment
+    /// is left alone.
+    pub fn set_comment(&mut self, comment: impl Into<String>) -> Result<(), FormatError> {
+        let comment = comment.into();
+        if comment.len() > u16::MAX as usize {
+            return Err(FormatError::CommentTooLong {
+                len: comment.len(),
+                max: u16::MAX as usize,
+            });
+        }
+
+        self.eocd.set_comment(comment.as_bytes().to_vec());
+        self.comment = comment;
+        self.comment_encoding = Encoding::Utf8;
+        Ok(())
+    }
+
+    /// Decodes the names and comments of all entries (and the archive's
+    /// comment) again, using the given encoding instead of the detected one.
+    ///
+    /// This is useful when encoding detection guessed wrong: the original
+    /// bytes are kept around, so the central directory doesn't need to be
+    /// read again. Names and comments recovered from Unicode Path and
+    /// Unicode Comment extra fields are kept as-is.
+    pub fn redecode_names(&mut self, encoding: Encoding) {
+        for entry in &mut self.entries {
+            entry.name = entry
+                .unicode_path_name()
+                .unwrap_or_else(|| encoding.decode_lossy(&entry.name_bytes));
+            entry.comment = entry
+                .unicode_comment()
+                .unwrap_or_else(|| encoding.decode_lossy(&entry.comment_bytes));
+        }
+        self.names = Self::index_names(&self.entries);
+        self.comment = encoding.decode_lossy(self.eocd.comment());
+        self.comment_encoding = encoding;
+        self.encoding = encoding;
+    }
+
+    /// Returns the end of central directory information this archive was
+    /// read from: the (zip64) end of central directory records, where they
+    /// were found, and how much data precedes the zip itself.
+    #[inline(always)]
+    pub fn eocd(&self) -> &EndOfCentralDirectory<'static> {
+        &self.eocd
+    }
+
+    /// Puts together an archive of `size` bytes whose central directory
+    /// couldn't be read, from entries recovered some other way, like from
+    /// their local headers.
+    ///
+    /// The archive has no comment, and is [truncated][Self::truncated]: its
+    /// end of central directory information is a placeholder, as if an empty
+    /// central directory sat at the end of the file.
+    pub fn recovered(size: u64, entries: Vec<Entry>) -> Self {
+        let encoding = if entries
+            .iter()
+            .all(|entry| std::str::from_utf8(&entry.name_bytes).is_ok())
+        {
+            Encoding::Utf8
+        } else {
+            Encoding::Cp437
+        };
+
+        Self {
+            size,
+            encoding,
+            names: Self::index_names(&entries),
+            entries,
+            comment: Default::default(),
+            comment_encoding: encoding,
+            eocd: EndOfCentralDirectory::placeholder(size),
+            truncated: true,
+            digital_signature: None,
+        }
+    }
+}
+
+/// What to order entries by, see [Archive::entries_sorted_by]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Their name, compared byte by byte
+    Name,
+
+    /// Their uncompressed size, smallest first
+    Size,
+
+    /// Their modification time, oldest first
+    Modified,
+
+    /// Where their local header is in the file
+    Offset,
+
+    /// Their compression method, by its numeric code (stored first)
+    Method,
 }
 
 /// Describes a zip archive entry (a file, a directory, a symlink)
@@ -71,12 +429,18 @@ pub struct Entry {
     /// the name, working around zip slip vulnerabilities.
     pub name: String,
 
+    /// Name of the file, as stored in the archive, before decoding
+    pub(crate) name_bytes: Vec<u8>,
+
     /// Compression method: Store, Deflate, Bzip2, etc.
     pub method: Method,
 
     /// Comment is any arbitrary user-defined string shorter than 64KiB
     pub comment: String,
 
+    /// Comment, as stored in the archive, before decoding
+    pub(crate) comment_bytes: Vec<u8>,
+
     /// This entry's "last modified" timestamp - with caveats
     ///
     /// Due to the history of the ZIP file format, this may be inaccurate. It may be offset
@@ -87,6 +451,9 @@ pub struct Entry {
     /// If you're reading this after the year 2038, or after the year 2108, godspeed.
     pub modified: DateTime<Utc>,
 
+    /// Wall-clock time of the MS-DOS "last modified" timestamp
+    pub(crate) modified_local: NaiveDateTime,
+
     /// This entry's "created" timestamp, if available.
     ///
     /// See [Self::modified] for caveats.
@@ -112,6 +479,21 @@ pub struct Entry {
     /// ```
     pub header_offset: u64,
 
+    /// Number of the disk the local file header is on, for archives split
+    /// across several disks (or files): [Self::header_offset] is relative to
+    /// the start of that disk in the archive, but has been translated when
+    /// read with [ArchiveFsm::with_disk_offsets](crate::fsm::ArchiveFsm::with_disk_offsets).
+    ///
+    /// This is zero for regular archives.
+    pub disk_nbr_start: u32,
+
+    /// Version of zip this entry was created with, and the system it was
+    /// created on, see [Version::host_system].
+    ///
+    /// For entries read from a local header (when streaming), this is the
+    /// same as [Self::reader_version].
+    pub creator_version: Version,
+
     /// Version of zip needed to extract this archive.
     pub reader_version: Version,
 
@@ -150,43 +532,198 @@ pub struct Entry {
 
     /// File mode.
     pub mode: Mode,
+
+    /// Extra field, as stored in the header this entry was read from
+    pub(crate) extra: Vec<u8>,
+
+    /// Which fields the zip64 extra field holds, as determined by the header
+    /// this entry was read from
+    pub(crate) extra_settings: ExtraFieldSettings,
+
+    /// Whether [Self::extra] comes from a central directory header, rather
+    /// than from a local header or an [EntryBuilder]
+    pub(crate) extra_is_central: bool,
+
+    /// Extra field of the local header, along with the settings to parse it
+    /// with, once that header has been read
+    pub(crate) local_extra: Option<(Vec<u8>, ExtraFieldSettings)>,
 }
 
 impl Entry {
-    /// Returns a sanitized version of the entry's name, if it
-    /// seems safe. In particular, if this method feels like the
-    /// entry name is trying to do a zip slip (cf.
-    /// <https://snyk.io/research/zip-slip-vulnerability>), it'll return
-    /// None.
-    ///
-    /// Other than that, it will strip any leading slashes on non-Windows OSes.
-    pub fn sanitized_name(&self) -> Option<&str> {
-        let name = self.name.as_str();
-
-        // refuse entries with traversed/absolute path to mitigate zip slip
-        if name.contains("..") {
+    /// Returns the name of the entry as it was stored in the archive,
+    /// before being decoded into [Self::name].
+    ///
+    /// Names that aren't valid in the archive's encoding are decoded lossily,
+    /// so this is the only way to get at the original bytes.
+    pub fn name_bytes(&self) -> &[u8] {
+        &self.name_bytes
+    }
+
+    /// Returns the offset of the first byte of the entry's (compressed)
+    /// data, right after its local header, given `local_header`: the bytes
+    /// found at [Self::header_offset] in an archive of `size` bytes.
+    ///
+    /// This is for tools that read the data directly, like by mapping it in
+    /// memory. Returns `Ok(None)` if `local_header` is too short to hold the
+    /// whole header, and an error if the data doesn't fit in the archive.
+    pub fn data_offset(&self, local_header: &[u8], size: u64) -> Result<Option<u64>, Error> {
+        let Some(header) = RawRecord::local_file_header(self.header_offset, local_header)? else {
+            return Ok(None);
+        };
+        let data_offset = self.header_offset + header.length;
+        match data_offset.checked_add(self.compressed_size) {
+            Some(data_end) if data_end <= size => Ok(Some(data_offset)),
+            data_end => Err(FormatError::DataOutOfBounds {
+                data_end: data_end.unwrap_or(u64::MAX),
+                file_size: size,
+            }
+            .into()),
+        }
+    }
+
+    /// Returns the data of a stored (uncompressed, unencrypted) entry as a
+    /// slice of `archive_bytes`, the whole archive, without copying it.
+    ///
+    /// Returns `None` for compressed or encrypted entries, and if the local
+    /// header or the data don't fit in `archive_bytes`. The data isn't
+    /// checked against the entry's CRC-32 checksum.
+    #[cfg(feature = "bytes")]
+    pub fn bytes_slice(&self, archive_bytes: &bytes::Bytes) -> Option<bytes::Bytes> {
+        if self.method != Method::Store || self.is_encrypted() {
             return None;
         }
 
-        #[cfg(windows)]
-        {
-            if name.contains(":\\") || name.starts_with("\\") {
+        let local_header = archive_bytes.get(usize::try_from(self.header_offset).ok()?..)?;
+        let data_offset = self
+            .data_offset(local_header, archive_bytes.len() as u64)
+            .ok()??;
+        let start = usize::try_from(data_offset).ok()?;
+        let end = usize::try_from(data_offset + self.compressed_size).ok()?;
+        Some(archive_bytes.slice(start..end))
+    }
+
+    /// Returns the "last modified" timestamp as stored in the MS-DOS date and
+    /// time fields of the header: a wall-clock time, in whatever time zone
+    /// the archive was created in.
+    ///
+    /// Unlike [Self::modified], this ignores extra fields with more precise
+    /// timestamps, and doesn't pretend to know the time zone. It's what to
+    /// show to display the time "as the creator saw it".
+    pub fn modified_local(&self) -> NaiveDateTime {
+        self.modified_local
+    }
+
+    /// Returns the raw extra field of the entry, as stored in the header it
+    /// was read from (or as set by an [EntryBuilder]).
+    ///
+    /// Known extra fields (zip64, timestamps, unix ownership, etc.) have
+    /// already been applied to the entry's metadata.
+    pub fn extra(&self) -> &[u8] {
+        &self.extra
+    }
+
+    /// Returns an iterator over the extra fields of the entry, parsed
+    /// wherever they're understood.
+    ///
+    /// Fields that aren't (or that fail to parse) are yielded as
+    /// [ExtraField::Unknown], along with their payload.
+    pub fn extra_fields(&self) -> impl Iterator<Item = ExtraField<'_>> {
+        parse_extra_fields(&self.extra, self.extra_settings)
+    }
+
+    /// Returns an iterator over the extra fields of the entry's central
+    /// directory header, parsed like [Self::extra_fields].
+    ///
+    /// This is None for entries that weren't read from the central
+    /// directory, like those read while streaming through an archive.
+    pub fn central_extra_fields(&self) -> Option<impl Iterator<Item = ExtraField<'_>>> {
+        self.extra_is_central
+            .then(|| parse_extra_fields(&self.extra, self.extra_settings))
+    }
+
+    /// Returns an iterator over the extra fields of the entry's local
+    /// header, parsed like [Self::extra_fields].
+    ///
+    /// They can differ from those of the central directory header: writers
+    /// often store more precise timestamps in the local header, for example.
+    /// This is None until the local header has been read, which
+    /// [EntryFsm](crate::fsm::EntryFsm) does before the entry's data: see
+    /// [EntryFsm::process_till_header](crate::fsm::EntryFsm::process_till_header).
+    pub fn local_extra_fields(&self) -> Option<impl Iterator<Item = ExtraField<'_>>> {
+        self.local_extra
+            .as_ref()
+            .map(|(extra, settings)| parse_extra_fields(extra, *settings))
+    }
+
+    /// Returns the name stored in a valid Unicode Path extra field, if any.
+    fn unicode_path_name(&self) -> Option<String> {
+        self.extra_fields().find_map(|ef| match ef {
+            ExtraField::UnicodePath(up) => up.unicode_name(&self.name_bytes).map(str::to_owned),
+            _ => None,
+        })
+    }
+
+    /// Returns the comment stored in a valid Unicode Comment extra field, if
+    /// any.
+    fn unicode_comment(&self) -> Option<String> {
+        self.extra_fields().find_map(|ef| match ef {
+            ExtraField::UnicodeComment(uc) => {
+                uc.unicode_comment(&self.comment_bytes).map(str::to_owned)
+            }
+            _ => None,
+        })
+    }
+
+    /// Returns the entry's name with path noise removed: empty components
+    /// (as in `a//b`) and `.` components (as in `./a` or `a/./b`) are
+    /// dropped. Leading and trailing slashes are kept, and `..` components
+    /// are left alone (see [Self::sanitized_name] for that).
+    ///
+    /// Names that are already clean are borrowed as-is.
+    pub fn normalized_name(&self) -> Cow<'_, str> {
+        normalize_path(&self.name)
+    }
+
+    /// Returns a path to extract the entry to, relative to the destination
+    /// directory, if its name seems safe. Names that look like they're
+    /// trying to do a zip slip (cf.
+    /// <https://snyk.io/research/zip-slip-vulnerability>) give None.
+    ///
+    /// Both `/` and `\` are treated as separators. Leading separators are
+    /// stripped, and empty and `.` components are dropped. Names are
+    /// rejected when they have:
+    ///
+    ///   * a `..` component, or one Windows would turn into `..` (like
+    ///     `.. ` or `...`, as it trims trailing dots and spaces)
+    ///   * a component with a drive prefix, as in `C:\Windows` or `a/C:foo`
+    ///   * a component that is a reserved Windows device name, like `CON`,
+    ///     `nul` or `com1.txt`
+    ///   * nothing left once cleaned up, like `/` or `./`
+    ///
+    /// These rules are the same on every platform, so an archive extracts
+    /// to the same paths everywhere.
+    pub fn sanitized_name(&self) -> Option<PathBuf> {
+        let mut path = PathBuf::new();
+        let components = self
+            .name
+            .split(['/', '\\'])
+            .filter(|component| !component.is_empty() && *component != ".");
+        for component in components {
+            // on Windows, pushing a component with a drive prefix replaces
+            // the whole path, wherever it is
+            if is_dot_component(component)
+                || is_reserved_name(component)
+                || has_drive_prefix(component)
+            {
                 return None;
             }
-            Some(name)
+            path.push(component);
         }
 
-        #[cfg(not(windows))]
-        {
-            // strip absolute prefix on entries pointing to root path
-            let mut entry_chars = name.chars();
-            let mut name = name;
-            while name.starts_with('/') {
-                entry_chars.next();
-                name = entry_chars.as_str()
-            }
-            Some(name)
+        if path.as_os_str().is_empty() {
+            return None;
         }
+        Some(path)
     }
 
     /// Apply the extra field to the entry, updating its metadata.
@@ -196,6 +733,9 @@ impl Entry {
                 self.uncompressed_size = z64.uncompressed_size;
                 self.compressed_size = z64.compressed_size;
                 self.header_offset = z64.header_offset;
+                if let Some(disk_start) = z64.disk_start {
+                    self.disk_nbr_start = disk_start;
+                }
             }
             ExtraField::Timestamp(ts) => {
                 self.modified = Utc
@@ -207,9 +747,20 @@ impl Entry {
                 for attr in &nf.attrs {
                     // note: other attributes are unsupported
                     if let NtfsAttr::Attr1(attr) = attr {
-                        self.modified = attr.mtime.to_datetime().unwrap_or_else(zero_datetime);
-                        self.created = attr.ctime.to_datetime();
-                        self.accessed = attr.atime.to_datetime();
+                        // zero means the timestamp wasn't recorded, and the
+                        // MS-DOS one is better than nothing
+                        let to_datetime = |ts: &NtfsTimestamp| {
+                            if ts.timestamp == 0 {
+                                None
+                            } else {
+                                ts.to_datetime()
+                            }
+                        };
+                        if let Some(modified) = to_datetime(&attr.mtime) {
+                            self.modified = modified;
+                        }
+                        self.created = to_datetime(&attr.ctime);
+                        self.accessed = to_datetime(&attr.atime);
                     }
                 }
             }
@@ -227,15 +778,106 @@ impl Entry {
                     self.gid = Some(uf.gid as u32);
                 }
             }
+            ExtraField::InfoZipUnix(uf) => {
+                self.modified = Utc
+                    .timestamp_opt(uf.mtime as i64, 0)
+                    .single()
+                    .unwrap_or_else(zero_datetime);
+
+                if self.uid.is_none() {
+                    self.uid = uf.uid.map(u32::from);
+                }
+
+                if self.gid.is_none() {
+                    self.gid = uf.gid.map(u32::from);
+                }
+            }
             ExtraField::NewUnix(uf) => {
                 self.uid = Some(uf.uid as u32);
-                self.gid = Some(uf.uid as u32);
+                self.gid = Some(uf.gid as u32);
+            }
+            ExtraField::UnicodePath(up) => {
+                if let Some(name) = up.unicode_name(&self.name_bytes) {
+                    self.name = name.to_owned();
+                }
+            }
+            ExtraField::UnicodeComment(uc) => {
+                if let Some(comment) = uc.unicode_comment(&self.comment_bytes) {
+                    self.comment = comment.to_owned();
+                }
             }
             _ => {}
         };
     }
 }
 
+/// Normalizes `prefix` like an entry name, and makes sure it ends with a
+/// slash (unless it's empty), so that it only matches whole components.
+fn directory_prefix(prefix: &str) -> String {
+    let mut prefix = normalize_path(prefix).into_owned();
+    if !prefix.is_empty() && !prefix.ends_with('/') {
+        prefix.push('/');
+    }
+    prefix
+}
+
+/// See [Entry::normalized_name]
+fn normalize_path(name: &str) -> Cow<'_, str> {
+    let is_noisy = name.contains("//")
+        || name.contains("/./")
+        || name.starts_with("./")
+        || name.ends_with("/.")
+        || name == ".";
+    if !is_noisy {
+        return Cow::Borrowed(name);
+    }
+
+    let mut normalized = String::with_capacity(name.len());
+    if name.starts_with('/') {
+        normalized.push('/');
+    }
+    let components = name
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".");
+    for (i, component) in components.enumerate() {
+        if i > 0 {
+            normalized.push('/');
+        }
+        normalized.push_str(component);
+    }
+    if name.ends_with('/') && !normalized.is_empty() && !normalized.ends_with('/') {
+        normalized.push('/');
+    }
+    Cow::Owned(normalized)
+}
+
+/// Whether Windows would take this path component for `.` or `..`: it's
+/// only dots, once trailing dots and spaces are trimmed.
+fn is_dot_component(component: &str) -> bool {
+    component.chars().all(|c| c == '.' || c == ' ') && component.starts_with('.')
+}
+
+/// Whether this path component starts with a drive letter, as in `C:`
+fn has_drive_prefix(component: &str) -> bool {
+    let bytes = component.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Whether this path component refers to a Windows device, which it does
+/// regardless of case and extension, and of trailing dots and spaces.
+fn is_reserved_name(component: &str) -> bool {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+        "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+
+    let stem = component.split('.').next().unwrap_or_default();
+    let stem = stem.trim_end_matches(' ');
+    RESERVED
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
 /// The entry's file type: a directory, a file, or a symbolic link.
 #[derive(Debug)]
 pub enum EntryKind {
@@ -249,6 +891,22 @@ pub enum EntryKind {
     Symlink,
 }
 
+/// How an entry is encrypted, see [Entry::encryption].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    /// The entry is not encrypted
+    None,
+
+    /// Traditional PKWARE encryption, also known as ZipCrypto
+    ZipCrypto,
+
+    /// WinZip AES encryption
+    WinZipAes {
+        /// Key size
+        strength: AesStrength,
+    },
+}
+
 impl Entry {
     /// Determine the kind of this entry based on its mode.
     pub fn kind(&self) -> EntryKind {
@@ -260,6 +918,216 @@ impl Entry {
             EntryKind::File
         }
     }
+
+    /// Returns true if this entry could be a directory, even though neither
+    /// its name (no trailing slash) nor its mode say so: it's an empty,
+    /// regular file.
+    ///
+    /// On its own, that's a weak signal (empty files exist!), so entries are
+    /// only reclassified as directories when reading an archive if some other
+    /// entry is nested under them.
+    pub fn is_likely_directory_without_slash(&self) -> bool {
+        self.uncompressed_size == 0
+            && !self.name.ends_with('/')
+            && matches!(self.kind(), EntryKind::File)
+    }
+
+    /// Returns true if the entry's data is encrypted (bit 0 of [Self::flags]).
+    ///
+    /// This crate can't decrypt entries: reading them fails, or returns
+    /// garbage.
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 0x1 != 0
+    }
+
+    /// Returns how the entry's data is encrypted: with WinZip AES if it has
+    /// a WinZip AES extra field, with ZipCrypto otherwise.
+    pub fn encryption(&self) -> Encryption {
+        if !self.is_encrypted() {
+            return Encryption::None;
+        }
+        self.extra_fields()
+            .find_map(|ef| match ef {
+                ExtraField::WinZipAes(aes) => Some(Encryption::WinZipAes {
+                    strength: aes.strength,
+                }),
+                _ => None,
+            })
+            .unwrap_or(Encryption::ZipCrypto)
+    }
+}
+
+/// Builds an [Entry] from scratch, for example to describe a file that is
+/// about to be written to an archive.
+///
+/// Defaults to a [Deflate][Method::Deflate]-compressed regular file with
+/// `rw-r--r--` permissions, last modified now. Names ending in `/` default
+/// to directories with `rwxr-xr-x` permissions instead.
+///
+/// Sizes, CRC-32 and header offset are left at zero: they are only known
+/// once the entry's data has been written.
+#[derive(Debug, Clone)]
+pub struct EntryBuilder {
+    name: String,
+    method: Method,
+    modified: DateTime<Utc>,
+    mode: Mode,
+    comment: String,
+    extra: Vec<u8>,
+}
+
+impl EntryBuilder {
+    /// Start building an entry with the given name. Names should be relative
+    /// paths separated by `/`.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let mode = if name.ends_with('/') {
+            Mode::DIR | Mode(0o755)
+        } else {
+            Mode(0o644)
+        };
+
+        Self {
+            name,
+            method: Method::Deflate,
+            modified: Utc::now(),
+            mode,
+            comment: String::new(),
+            extra: Vec::new(),
+        }
+    }
+
+    /// Set the compression method
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Set the "last modified" timestamp
+    pub fn modified(mut self, modified: DateTime<Utc>) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    /// Set the file mode, including the file type bits: use [Mode::DIR] or
+    /// [Mode::SYMLINK] for directories and symlinks.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the entry's comment
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Set the raw extra field, which is stored as-is
+    pub fn extra(mut self, extra: impl Into<Vec<u8>>) -> Self {
+        self.extra = extra.into();
+        self
+    }
+
+    /// Build the entry
+    pub fn build(self) -> Entry {
+        // see APPNOTE 4.4.4: Bit 11 is the language encoding flag (EFS)
+        let flags = if self.name.is_ascii() && self.comment.is_ascii() {
+            0
+        } else {
+            0x800
+        };
+
+        Entry {
+            name_bytes: self.name.as_bytes().to_vec(),
+            name: self.name,
+            method: self.method,
+            comment_bytes: self.comment.as_bytes().to_vec(),
+            comment: self.comment,
+            modified: self.modified,
+            modified_local: self.modified.naive_utc(),
+            created: None,
+            accessed: None,
+            header_offset: 0,
+            disk_nbr_start: 0,
+            creator_version: Version {
+                host_system: HostSystem::Unix,
+                version: 20,
+            },
+            reader_version: Version {
+                host_system: HostSystem::Unix,
+                // 2.0: deflate, directories
+                version: 20,
+            },
+            flags,
+            uid: None,
+            gid: None,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            mode: self.mode,
+            extra: self.extra,
+            extra_settings: ExtraFieldSettings {
+                uncompressed_size_u32: 0,
+                compressed_size_u32: 0,
+                header_offset_u32: 0,
+            },
+            extra_is_central: false,
+            local_extra: None,
+        }
+    }
+}
+
+/// Parses extra fields until the end of `extra`, or until one fails to parse
+fn parse_extra_fields(
+    extra: &[u8],
+    settings: ExtraFieldSettings,
+) -> impl Iterator<Item = ExtraField<'_>> {
+    let mut slice = Partial::new(extra);
+    let mut parser = ExtraField::mk_parser(settings);
+    std::iter::from_fn(move || {
+        if slice.is_empty() {
+            return None;
+        }
+        match parser.parse_next(&mut slice) {
+            Ok(ef) => Some(ef),
+            Err(e) => {
+                trace!("extra field error: {:#?}", e);
+                None
+            }
+        }
+    })
+}
+
+/// Marks entries for which [Entry::is_likely_directory_without_slash] holds
+/// as directories, if other entries are nested under them.
+pub(crate) fn mark_implicit_directories(entries: &mut [Entry]) {
+    if !entries
+        .iter()
+        .any(|entry| entry.is_likely_directory_without_slash())
+    {
+        return;
+    }
+
+    let implicit_dirs: Vec<usize> = {
+        let parents: HashSet<&str> = entries
+            .iter()
+            .flat_map(|entry| entry.name.match_indices('/').map(|(i, _)| &entry.name[..i]))
+            .collect();
+
+        entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                entry.is_likely_directory_without_slash() && parents.contains(entry.name.as_str())
+            })
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    for i in implicit_dirs {
+        trace!(name = %entries[i].name, "treating entry as a directory");
+        entries[i].mode |= Mode::DIR;
+    }
 }
 
 /// Compression method used for a file entry.
@@ -290,6 +1158,9 @@ pub enum Method {
     Lzma = 14,
 
     /// [zstd](https://datatracker.ietf.org/doc/html/rfc8878)
+    ///
+    /// The compressed data may consist of several concatenated zstd frames,
+    /// they are decompressed one after the other until it runs out.
     Zstd = 93,
 
     /// [MP3](https://www.iso.org/obp/ui/#iso:std:iso-iec:11172:-3:ed-1:v1:en)
@@ -305,6 +1176,9 @@ pub enum Method {
     WavPack = 97,
 
     /// [PPMd](https://en.wikipedia.org/wiki/Prediction_by_partial_matching)
+    ///
+    /// Decompressing it takes the `ppmd` feature, which needs Rust 1.82 or
+    /// later, unlike the rest of the crate.
     Ppmd = 98,
 
     /// AE-x encryption marker (see Appendix E of appnote)
@@ -320,4 +1194,79 @@ impl Method {
     pub fn parser(i: &mut Partial<&[u8]>) -> PResult<Self> {
         le_u16(i).map(From::from)
     }
+
+    /// Maps a method code, as found in headers, to a method. Codes this
+    /// crate doesn't know about map to [Method::Unrecognized].
+    pub fn from_u16(code: u16) -> Self {
+        code.into()
+    }
+
+    /// The code of this method, as written in headers.
+    pub fn as_u16(&self) -> u16 {
+        (*self).into()
+    }
+
+    /// A human-readable name for this method, like "Deflate" or "LZMA", or
+    /// `None` for [Method::Unrecognized] ones.
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match self {
+            Self::Store => "Store",
+            Self::Deflate => "Deflate",
+            Self::Deflate64 => "Deflate64",
+            Self::Bzip2 => "BZIP2",
+            Self::Lzma => "LZMA",
+            Self::Zstd => "Zstd",
+            Self::Mp3 => "MP3",
+            Self::Xz => "XZ",
+            Self::Jpeg => "JPEG",
+            Self::WavPack => "WavPack",
+            Self::Ppmd => "PPMd",
+            Self::Aex => "AE-x",
+            Self::Unrecognized(_) => return None,
+        };
+        Some(name)
+    }
+
+    /// Returns true if this crate knows how to decompress this method, provided
+    /// the corresponding cargo feature is enabled.
+    pub fn is_supported(&self) -> bool {
+        matches!(
+            self,
+            Self::Store
+                | Self::Deflate
+                | Self::Deflate64
+                | Self::Bzip2
+                | Self::Lzma
+                | Self::Zstd
+                | Self::Xz
+                | Self::Ppmd
+        )
+    }
+
+    /// Returns true if entries using this method can be decompressed by
+    /// this build, i.e. it is supported and its cargo feature is enabled.
+    // with every feature on, this would be a `matches!`
+    #[allow(clippy::match_like_matches_macro)]
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Self::Store => true,
+            Self::Deflate => cfg!(feature = "deflate"),
+            Self::Deflate64 => cfg!(feature = "deflate64"),
+            Self::Bzip2 => cfg!(feature = "bzip2"),
+            Self::Lzma => cfg!(feature = "lzma"),
+            Self::Zstd => cfg!(feature = "zstd"),
+            Self::Xz => cfg!(feature = "xz"),
+            Self::Ppmd => cfg!(feature = "ppmd"),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "method {}", self.as_u16()),
+        }
+    }
 }
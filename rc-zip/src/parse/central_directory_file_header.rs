@@ -13,10 +13,7 @@ use crate::{
     encoding::detect_utf8,
     encoding::Encoding,
     error::{Error, FormatError},
-    parse::{
-        zero_datetime, Entry, ExtraField, ExtraFieldSettings, HostSystem, Mode, MsdosMode,
-        MsdosTimestamp, UnixMode, Version,
-    },
+    parse::{zero_datetime, Entry, ExtraField, ExtraFieldSettings, Mode, MsdosTimestamp, Version},
 };
 
 use super::Method;
@@ -73,6 +70,10 @@ pub struct CentralDirectoryFileHeader<'a> {
 impl<'a> CentralDirectoryFileHeader<'a> {
     const SIGNATURE: &'static str = "PK\x01\x02";
 
+    /// Length of the fixed-size part of the header, before the name, extra
+    /// field and comment.
+    pub(crate) const MIN_LENGTH: usize = 46;
+
     /// Parser for the central directory file header
     pub fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
         _ = literal(Self::SIGNATURE).parse_next(i)?;
@@ -117,6 +118,42 @@ impl<'a> CentralDirectoryFileHeader<'a> {
 }
 
 impl CentralDirectoryFileHeader<'_> {
+    /// Serializes the header, as read by [Self::parser]
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(Self::SIGNATURE.as_bytes());
+        self.creator_version.write(out);
+        self.reader_version.write(out);
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&u16::from(self.method).to_le_bytes());
+        self.modified.write(out);
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        out.extend_from_slice(&self.compressed_size.to_le_bytes());
+        out.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(self.extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.disk_nbr_start.to_le_bytes());
+        out.extend_from_slice(&self.internal_attrs.to_le_bytes());
+        out.extend_from_slice(&self.external_attrs.to_le_bytes());
+        out.extend_from_slice(&self.header_offset.to_le_bytes());
+        out.extend_from_slice(&self.name);
+        out.extend_from_slice(&self.extra);
+        out.extend_from_slice(&self.comment);
+    }
+
+    /// Returns the lengths of the name, extra field, and comment of the header
+    /// at the start of `i`, without parsing (or waiting for) the whole header.
+    ///
+    /// Returns `None` if `i` doesn't start with a full fixed-size header part.
+    pub(crate) fn peek_lengths(i: &[u8]) -> Option<(u16, u16, u16)> {
+        const FIXED_LEN: usize = 34;
+        if i.len() < FIXED_LEN || !i.starts_with(Self::SIGNATURE.as_bytes()) {
+            return None;
+        }
+        let len_at = |offset: usize| u16::from_le_bytes([i[offset], i[offset + 1]]);
+        Some((len_at(28), len_at(30), len_at(32)))
+    }
+
     /// Returns true if the name or comment is not valid UTF-8
     pub fn is_non_utf8(&self) -> bool {
         let (valid1, require1) = detect_utf8(&self.name[..]);
@@ -141,16 +178,30 @@ impl CentralDirectoryFileHeader<'_> {
     /// Converts the directory header into a entry: this involves
     /// parsing the extra fields and converting the timestamps.
     pub fn as_entry(&self, encoding: Encoding, global_offset: u64) -> Result<Entry, Error> {
+        let settings = ExtraFieldSettings {
+            uncompressed_size_u32: self.uncompressed_size,
+            compressed_size_u32: self.compressed_size,
+            header_offset_u32: self.header_offset,
+        };
+
         let mut entry = Entry {
-            name: encoding.decode(&self.name[..])?,
+            name: encoding.decode_lossy(&self.name[..]),
+            name_bytes: self.name.to_vec(),
             method: self.method,
-            comment: encoding.decode(&self.comment[..])?,
+            comment: encoding.decode_lossy(&self.comment[..]),
+            comment_bytes: self.comment.to_vec(),
             modified: self.modified.to_datetime().unwrap_or_else(zero_datetime),
+            modified_local: self
+                .modified
+                .to_naive_datetime()
+                .unwrap_or_else(|| zero_datetime().naive_utc()),
             created: None,
             accessed: None,
-            header_offset: (self.header_offset as u64)
-                .checked_add(global_offset)
-                .ok_or(FormatError::InvalidHeaderOffset)?,
+            // the global offset is added once the zip64 extra field (which
+            // may hold the real header offset) has been parsed
+            header_offset: self.header_offset as u64,
+            disk_nbr_start: self.disk_nbr_start as u32,
+            creator_version: self.creator_version,
             reader_version: self.reader_version,
             flags: self.flags,
             uid: None,
@@ -159,26 +210,19 @@ impl CentralDirectoryFileHeader<'_> {
             compressed_size: self.compressed_size as _,
             uncompressed_size: self.uncompressed_size as _,
             mode: Mode(0),
+            extra: self.extra.to_vec(),
+            extra_settings: settings,
+            extra_is_central: true,
+            local_extra: None,
         };
 
-        entry.mode = match self.creator_version.host_system {
-            HostSystem::Unix | HostSystem::Osx => UnixMode(self.external_attrs >> 16).into(),
-            HostSystem::WindowsNtfs | HostSystem::Vfat | HostSystem::MsDos => {
-                MsdosMode(self.external_attrs).into()
-            }
-            _ => Mode(0),
-        };
+        entry.mode =
+            Mode::from_external_attrs(self.creator_version.host_system(), self.external_attrs);
         if entry.name.ends_with('/') {
             // believe it or not, this is straight from the APPNOTE
             entry.mode |= Mode::DIR
         };
 
-        let settings = ExtraFieldSettings {
-            uncompressed_size_u32: self.uncompressed_size,
-            compressed_size_u32: self.compressed_size,
-            header_offset_u32: self.header_offset,
-        };
-
         let mut slice = Partial::new(&self.extra[..]);
         while !slice.is_empty() {
             match ExtraField::mk_parser(settings).parse_next(&mut slice) {
@@ -186,12 +230,19 @@ impl CentralDirectoryFileHeader<'_> {
                     entry.set_extra_field(&ef);
                 }
                 Err(e) => {
+                    // some writers leave a partial field at the end, that's
+                    // no reason to give up on the whole entry
                     trace!("extra field error: {:#?}", e);
-                    return Err(FormatError::InvalidExtraField.into());
+                    break;
                 }
             }
         }
 
+        entry.header_offset = entry
+            .header_offset
+            .checked_add(global_offset)
+            .ok_or(FormatError::InvalidHeaderOffset)?;
+
         Ok(entry)
     }
 }
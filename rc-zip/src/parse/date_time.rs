@@ -1,6 +1,6 @@
 use chrono::{
     offset::{LocalResult, TimeZone, Utc},
-    DateTime, Timelike,
+    DateTime, Datelike, NaiveDate, NaiveDateTime, Timelike,
 };
 use ownable::{IntoOwned, ToOwned};
 use std::fmt;
@@ -40,23 +40,54 @@ impl MsdosTimestamp {
         .parse_next(i)
     }
 
+    /// Converts a date time to an MS-DOS timestamp, clamping it to the
+    /// range MS-DOS timestamps can represent, and rounding seconds down to
+    /// an even number.
+    pub fn from_naive_datetime(datetime: &NaiveDateTime) -> Self {
+        let min = NaiveDate::from_ymd_opt(1980, 1, 1)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .unwrap();
+        let max = NaiveDate::from_ymd_opt(2107, 12, 31)
+            .and_then(|date| date.and_hms_opt(23, 59, 58))
+            .unwrap();
+        let datetime = (*datetime).clamp(min, max);
+
+        Self {
+            time: ((datetime.hour() << 11) | (datetime.minute() << 5) | (datetime.second() / 2))
+                as u16,
+            date: (((datetime.year() - 1980) as u32) << 9 | datetime.month() << 5 | datetime.day())
+                as u16,
+        }
+    }
+
+    /// Serializes the timestamp, as read by [Self::parser]
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.time.to_le_bytes());
+        out.extend_from_slice(&self.date.to_le_bytes());
+    }
+
     /// Attempts to convert to a chrono UTC date time
+    ///
+    /// MS-DOS timestamps are in local time, but don't say which time zone,
+    /// so this assumes UTC. See [Self::to_naive_datetime] for the wall-clock
+    /// time as stored.
     pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
+        self.to_naive_datetime()
+            .map(|naive| Utc.from_utc_datetime(&naive))
+    }
+
+    /// Attempts to convert to a chrono naive date time, as stored: in the
+    /// (unknown) local time zone of whoever created the archive.
+    pub fn to_naive_datetime(&self) -> Option<NaiveDateTime> {
         // see https://docs.microsoft.com/en-us/windows/win32/api/winbase/nf-winbase-dosdatetimetofiletime
-        let res = {
-            // bits 0-4: day of the month (1-31)
-            let d = (self.date & 0b1_1111) as u32;
-            // bits 5-8: month (1 = january, 2 = february and so on)
-            let m = ((self.date >> 5) & 0b1111) as u32;
-            // bits 9-15: year offset from 1980
-            let y = ((self.date >> 9) + 1980) as i32;
-            Utc.with_ymd_and_hms(y, m, d, 0, 0, 0)
-        };
-
-        let date = match res {
-            LocalResult::Single(date) => date,
-            _ => return None,
-        };
+
+        // bits 0-4: day of the month (1-31)
+        let d = (self.date & 0b1_1111) as u32;
+        // bits 5-8: month (1 = january, 2 = february and so on)
+        let m = ((self.date >> 5) & 0b1111) as u32;
+        // bits 9-15: year offset from 1980
+        let y = ((self.date >> 9) + 1980) as i32;
+        let date = NaiveDate::from_ymd_opt(y, m, d)?;
 
         // bits 0-4: second divided by 2
         let s = (self.time & 0b1_1111) as u32 * 2;
@@ -64,7 +95,7 @@ impl MsdosTimestamp {
         let m = (self.time >> 5 & 0b11_1111) as u32;
         // bits 11-15: hour (0-23 on a 24-hour clock)
         let h = (self.time >> 11) as u32;
-        date.with_hour(h)?.with_minute(m)?.with_second(s)
+        date.and_hms_opt(h, m, s)
     }
 }
 
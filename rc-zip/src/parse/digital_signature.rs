@@ -0,0 +1,33 @@
+use std::borrow::Cow;
+
+use ownable::{IntoOwned, ToOwned};
+use winnow::{
+    binary::{le_u16, length_take},
+    seq,
+    token::literal,
+    PResult, Parser, Partial,
+};
+
+/// 4.3.13 Digital signature:
+///
+/// Sits right after the last central directory header, and is counted in
+/// the size of the central directory. Its contents are left to the writer.
+#[derive(Debug, ToOwned, IntoOwned, Clone)]
+pub struct DigitalSignature<'a> {
+    /// signature data
+    pub data: Cow<'a, [u8]>,
+}
+
+impl<'a> DigitalSignature<'a> {
+    /// The signature of the digital signature record
+    pub const SIGNATURE: &'static str = "PK\x05\x05";
+
+    /// Parser for the digital signature record
+    pub fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
+        _ = literal(Self::SIGNATURE).parse_next(i)?;
+        seq! {Self {
+            data: length_take(le_u16).map(Cow::Borrowed),
+        }}
+        .parse_next(i)
+    }
+}
@@ -55,6 +55,21 @@ impl<'a> EndOfCentralDirectoryRecord<'a> {
         None
     }
 
+    /// Stands in for the end of central directory record of zip64 archives
+    /// whose writer left it out: every field defers to the zip64 end of
+    /// central directory record.
+    pub(crate) fn zip64_placeholder() -> Self {
+        Self {
+            disk_nbr: 0,
+            dir_disk_nbr: 0,
+            dir_records_this_disk: u16::MAX,
+            directory_records: u16::MAX,
+            directory_size: u32::MAX,
+            directory_offset: u32::MAX,
+            comment: Cow::Borrowed(&[]),
+        }
+    }
+
     /// Parser for the end of central directory record
     pub fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
         let _ = literal(Self::SIGNATURE).parse_next(i)?;
@@ -69,6 +84,19 @@ impl<'a> EndOfCentralDirectoryRecord<'a> {
         }}
         .parse_next(i)
     }
+
+    /// Serializes the record, as read by [Self::parser]
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(Self::SIGNATURE.as_bytes());
+        out.extend_from_slice(&self.disk_nbr.to_le_bytes());
+        out.extend_from_slice(&self.dir_disk_nbr.to_le_bytes());
+        out.extend_from_slice(&self.dir_records_this_disk.to_le_bytes());
+        out.extend_from_slice(&self.directory_records.to_le_bytes());
+        out.extend_from_slice(&self.directory_size.to_le_bytes());
+        out.extend_from_slice(&self.directory_offset.to_le_bytes());
+        out.extend_from_slice(&(self.comment.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.comment);
+    }
 }
 
 /// 4.3.15 Zip64 end of central directory locator
@@ -87,6 +115,20 @@ impl EndOfCentralDirectory64Locator {
     pub const LENGTH: usize = 20;
     const SIGNATURE: &'static str = "PK\x06\x07";
 
+    /// Find the zip64 end of central directory locator in a block of data
+    pub fn find_in_block(b: &[u8]) -> Option<Located<Self>> {
+        for i in (0..(b.len().saturating_sub(Self::LENGTH - 1))).rev() {
+            let mut input = Partial::new(&b[i..]);
+            if let Ok(locator) = Self::parser.parse_next(&mut input) {
+                return Some(Located {
+                    offset: i as u64,
+                    inner: locator,
+                });
+            }
+        }
+        None
+    }
+
     /// Parser for the zip64 end of central directory locator
     pub fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
         _ = literal(Self::SIGNATURE).parse_next(i)?;
@@ -97,6 +139,14 @@ impl EndOfCentralDirectory64Locator {
         }}
         .parse_next(i)
     }
+
+    /// Serializes the locator, as read by [Self::parser]
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(Self::SIGNATURE.as_bytes());
+        out.extend_from_slice(&self.dir_disk_number.to_le_bytes());
+        out.extend_from_slice(&self.directory_offset.to_le_bytes());
+        out.extend_from_slice(&self.total_disks.to_le_bytes());
+    }
 }
 
 /// 4.3.14  Zip64 end of central directory record
@@ -134,6 +184,23 @@ pub struct EndOfCentralDirectory64Record {
 impl EndOfCentralDirectory64Record {
     const SIGNATURE: &'static str = "PK\x06\x06";
 
+    /// Length of the record, not counting the extensible data sector
+    pub(crate) const MIN_LENGTH: usize = 56;
+
+    /// Find the zip64 end of central directory record in a block of data
+    pub fn find_in_block(b: &[u8]) -> Option<Located<Self>> {
+        for i in (0..(b.len().saturating_sub(Self::MIN_LENGTH - 1))).rev() {
+            let mut input = Partial::new(&b[i..]);
+            if let Ok(record) = Self::parser.parse_next(&mut input) {
+                return Some(Located {
+                    offset: i as u64,
+                    inner: record,
+                });
+            }
+        }
+        None
+    }
+
     /// Parser for the zip64 end of central directory record
     pub fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
         _ = literal(Self::SIGNATURE).parse_next(i)?;
@@ -150,6 +217,22 @@ impl EndOfCentralDirectory64Record {
         }}
         .parse_next(i)
     }
+
+    /// Serializes the record, as read by [Self::parser]. The extensible data
+    /// sector isn't written, so `record_size` should be
+    /// [Self::MIN_LENGTH] minus 12.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(Self::SIGNATURE.as_bytes());
+        out.extend_from_slice(&self.record_size.to_le_bytes());
+        out.extend_from_slice(&self.creator_version.to_le_bytes());
+        out.extend_from_slice(&self.reader_version.to_le_bytes());
+        out.extend_from_slice(&self.disk_nbr.to_le_bytes());
+        out.extend_from_slice(&self.dir_disk_nbr.to_le_bytes());
+        out.extend_from_slice(&self.dir_records_this_disk.to_le_bytes());
+        out.extend_from_slice(&self.directory_records.to_le_bytes());
+        out.extend_from_slice(&self.directory_size.to_le_bytes());
+        out.extend_from_slice(&self.directory_offset.to_le_bytes());
+    }
 }
 
 /// A zip structure and its location in the input file
@@ -202,6 +285,14 @@ pub struct EndOfCentralDirectory<'a> {
     /// Zip files may be prepended by arbitrary data, this is how much
     /// data is at the beginning of the file that isn't part of the zip
     pub global_offset: i64,
+
+    /// Whether `dir` was read from the archive. Some writers leave it out
+    /// of zip64 archives, it's then a placeholder that defers to `dir64`.
+    pub dir_found: bool,
+
+    /// Whether the zip64 end of central directory locator was read from
+    /// the archive, rather than `dir64` being found without it
+    pub locator_found: bool,
 }
 
 impl<'a> EndOfCentralDirectory<'a> {
@@ -210,10 +301,21 @@ impl<'a> EndOfCentralDirectory<'a> {
         dir: Located<EndOfCentralDirectoryRecord<'a>>,
         dir64: Option<Located<EndOfCentralDirectory64Record>>,
     ) -> Result<Self, Error> {
+        if dir64.is_none()
+            && (dir.inner.directory_size == u32::MAX || dir.inner.directory_offset == u32::MAX)
+        {
+            // these are placeholders for the real values, which are in the
+            // zip64 end of central directory record we didn't find. doing
+            // arithmetic on them would send us looking in the wrong place.
+            return Err(FormatError::MissingZip64Eocd.into());
+        }
+
         let mut res = Self {
+            locator_found: dir64.is_some(),
             dir,
             dir64,
             global_offset: 0,
+            dir_found: true,
         };
 
         //
@@ -245,18 +347,26 @@ impl<'a> EndOfCentralDirectory<'a> {
         // 0                   directory_offset - woops!                   directory_end_offset
         // ~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~
 
-        let computed_directory_offset = res
-            .located_directory_offset()
-            .checked_sub(res.directory_size())
-            .ok_or(FormatError::DirectoryOffsetPointsOutsideFile)?;
+        let directory_end = res.located_directory_offset();
+        let computed_directory_offset = directory_end.checked_sub(res.directory_size()).ok_or(
+            FormatError::InconsistentDirectorySize {
+                directory_size: res.directory_size(),
+                directory_end,
+            },
+        )?;
 
         // did we find a valid offset?
         if (0..size).contains(&computed_directory_offset) {
             // that's different from the recorded one?
             if computed_directory_offset != res.directory_offset() {
                 // then assume the whole file is offset
-                res.global_offset =
-                    computed_directory_offset as i64 - res.directory_offset() as i64;
+                // both offsets come straight from the file, the recorded
+                // one may not even fit in an i64
+                res.global_offset = i64::try_from(computed_directory_offset)
+                    .ok()
+                    .zip(i64::try_from(res.directory_offset()).ok())
+                    .and_then(|(computed, recorded)| computed.checked_sub(recorded))
+                    .ok_or(FormatError::DirectoryOffsetPointsOutsideFile)?;
                 res.set_directory_offset(computed_directory_offset);
             }
         }
@@ -274,6 +384,30 @@ impl<'a> EndOfCentralDirectory<'a> {
         Ok(res)
     }
 
+    /// Stands in for the end of central directory of an archive of `size`
+    /// bytes whose own couldn't be found: an empty central directory at the
+    /// very end of the file.
+    pub(crate) fn placeholder(size: u64) -> Self {
+        Self {
+            dir: Located {
+                offset: size,
+                inner: EndOfCentralDirectoryRecord {
+                    disk_nbr: 0,
+                    dir_disk_nbr: 0,
+                    dir_records_this_disk: 0,
+                    directory_records: 0,
+                    directory_size: 0,
+                    directory_offset: size.min(u32::MAX as u64 - 1) as u32,
+                    comment: Cow::Borrowed(&[]),
+                },
+            },
+            dir64: None,
+            global_offset: 0,
+            dir_found: false,
+            locator_found: false,
+        }
+    }
+
     #[inline]
     pub(crate) fn located_directory_offset(&self) -> u64 {
         match self.dir64.as_ref() {
@@ -282,16 +416,19 @@ impl<'a> EndOfCentralDirectory<'a> {
         }
     }
 
+    /// Offset of the start of the central directory, adjusted for any data
+    /// preceding the zip archive.
     #[inline]
-    pub(crate) fn directory_offset(&self) -> u64 {
+    pub fn directory_offset(&self) -> u64 {
         match self.dir64.as_ref() {
             Some(d64) => d64.inner.directory_offset,
             None => self.dir.inner.directory_offset as u64,
         }
     }
 
+    /// Size of the central directory, in bytes
     #[inline]
-    pub(crate) fn directory_size(&self) -> u64 {
+    pub fn directory_size(&self) -> u64 {
         match self.dir64.as_ref() {
             Some(d64) => d64.inner.directory_size,
             None => self.dir.inner.directory_size as u64,
@@ -306,8 +443,19 @@ impl<'a> EndOfCentralDirectory<'a> {
         };
     }
 
+    /// Number of the disk the central directory starts on
     #[inline]
-    pub(crate) fn directory_records(&self) -> u64 {
+    pub(crate) fn dir_disk_nbr(&self) -> u32 {
+        match self.dir64.as_ref() {
+            Some(d64) => d64.inner.dir_disk_nbr,
+            None => self.dir.inner.dir_disk_nbr as u32,
+        }
+    }
+
+    /// Number of records in the central directory, as announced by the
+    /// (zip64) end of central directory record
+    #[inline]
+    pub fn directory_records(&self) -> u64 {
         match self.dir64.as_ref() {
             Some(d64) => d64.inner.directory_records,
             None => self.dir.inner.directory_records as u64,
@@ -318,4 +466,9 @@ impl<'a> EndOfCentralDirectory<'a> {
     pub(crate) fn comment(&self) -> &[u8] {
         &self.dir.inner.comment
     }
+
+    #[inline]
+    pub(crate) fn set_comment(&mut self, comment: Vec<u8>) {
+        self.dir.inner.comment = Cow::Owned(comment);
+    }
 }
@@ -3,14 +3,15 @@ use std::borrow::Cow;
 use ownable::{IntoOwned, ToOwned};
 use winnow::{
     binary::{le_u16, le_u32, le_u64, le_u8, length_take},
-    combinator::{opt, preceded, repeat_till},
+    combinator::{opt, preceded, repeat_till, rest},
     error::{ErrMode, ErrorKind, ParserError, StrContext},
     seq,
+    stream::StreamIsPartial,
     token::{literal, take},
     PResult, Parser, Partial,
 };
 
-use crate::parse::NtfsTimestamp;
+use crate::parse::{Method, NtfsTimestamp};
 
 /// 4.4.28 extra field: (Variable)
 pub(crate) struct ExtraFieldRecord<'a> {
@@ -65,16 +66,26 @@ pub enum ExtraField<'a> {
     Zip64(ExtraZip64Field),
     /// Extended timestamp
     Timestamp(ExtraTimestampField),
-    /// UNIX & Info-Zip UNIX
+    /// UNIX
     Unix(ExtraUnixField<'a>),
+    /// Info-ZIP UNIX (original, also called "Unix1")
+    InfoZipUnix(ExtraInfoZipUnixField),
     /// New UNIX extra field
     NewUnix(ExtraNewUnixField),
     /// NTFS (Win9x/WinNT FileTimes)
     Ntfs(ExtraNtfsField),
-    /// Unknown extra field, with tag
+    /// Info-ZIP Unicode Path
+    UnicodePath(ExtraUnicodePathField<'a>),
+    /// Info-ZIP Unicode Comment
+    UnicodeComment(ExtraUnicodeCommentField<'a>),
+    /// WinZip AES encryption
+    WinZipAes(ExtraWinZipAesField),
+    /// Unknown extra field (or a known one that failed to parse), with tag
     Unknown {
         /// tag of the extra field
         tag: u16,
+        /// payload of the extra field, as stored
+        data: &'a [u8],
     },
 }
 
@@ -88,6 +99,9 @@ impl<'a> ExtraField<'a> {
             use ExtraField as EF;
             let rec = ExtraFieldRecord::parser.parse_next(i)?;
             let payload = &mut Partial::new(rec.payload);
+            // the payload is all there: running out of it means the field
+            // is malformed, and it's reported as unknown
+            let _ = payload.complete();
 
             let variant = match rec.tag {
                 ExtraZip64Field::TAG => opt(ExtraZip64Field::mk_parser(settings).map(EF::Zip64))
@@ -99,15 +113,31 @@ impl<'a> ExtraField<'a> {
                 ExtraNtfsField::TAG => {
                     opt(ExtraNtfsField::parser.map(EF::Ntfs)).parse_next(payload)?
                 }
-                ExtraUnixField::TAG | ExtraUnixField::TAG_INFOZIP => {
+                ExtraUnixField::TAG => {
                     opt(ExtraUnixField::parser.map(EF::Unix)).parse_next(payload)?
                 }
+                ExtraInfoZipUnixField::TAG => {
+                    opt(ExtraInfoZipUnixField::parser.map(EF::InfoZipUnix)).parse_next(payload)?
+                }
                 ExtraNewUnixField::TAG => {
                     opt(ExtraNewUnixField::parser.map(EF::NewUnix)).parse_next(payload)?
                 }
+                ExtraUnicodePathField::TAG => {
+                    opt(ExtraUnicodePathField::parser.map(EF::UnicodePath)).parse_next(payload)?
+                }
+                ExtraUnicodeCommentField::TAG => {
+                    opt(ExtraUnicodeCommentField::parser.map(EF::UnicodeComment))
+                        .parse_next(payload)?
+                }
+                ExtraWinZipAesField::TAG => {
+                    opt(ExtraWinZipAesField::parser.map(EF::WinZipAes)).parse_next(payload)?
+                }
                 _ => None,
             }
-            .unwrap_or(EF::Unknown { tag: rec.tag });
+            .unwrap_or(EF::Unknown {
+                tag: rec.tag,
+                data: rec.payload,
+            });
 
             Ok(variant)
         }
@@ -185,6 +215,18 @@ impl ExtraTimestampField {
 }
 
 /// 4.5.7 -UNIX Extra Field (0x000d):
+///
+/// ```text
+/// Value         Size        Description
+/// -----         ----        -----------
+/// 0x000d        2 bytes     Tag for this "extra" block type
+/// TSize         2 bytes     Size for the following data block
+/// Atime         4 bytes     File last access time
+/// Mtime         4 bytes     File last modification time
+/// Uid           2 bytes     File user ID
+/// Gid           2 bytes     File group ID
+/// (var)         variable    Variable length data field
+/// ```
 #[derive(Clone, ToOwned, IntoOwned)]
 pub struct ExtraUnixField<'a> {
     /// file last access time
@@ -201,27 +243,64 @@ pub struct ExtraUnixField<'a> {
 
 impl<'a> ExtraUnixField<'a> {
     const TAG: u16 = 0x000d;
-    const TAG_INFOZIP: u16 = 0x5855;
 
     fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
-        let t_size = le_u16.parse_next(i)?;
-
-        // t_size includes the size of the atime .. gid fields, totalling 12 bytes.
-        let t_size = t_size
-            .checked_sub(12)
-            .ok_or(ErrMode::from_error_kind(i, ErrorKind::Verify))?;
-
         seq! {Self {
             atime: le_u32,
             mtime: le_u32,
             uid: le_u16,
             gid: le_u16,
-            data: take(t_size).map(Cow::Borrowed),
+            data: rest.map(Cow::Borrowed),
         }}
         .parse_next(i)
     }
 }
 
+/// Info-ZIP Unix Extra Field (type 1):
+/// ====================================
+///
+/// Superseded by the extended timestamp and new Unix extra fields, but still
+/// found in older archives. The central directory version of this field
+/// only has timestamps.
+///
+/// ```text
+/// Value         Size        Description
+/// -----         ----        -----------
+/// 0x5855        Short       tag for this extra block type ("UX")
+/// TSize         Short       total data size for this block
+/// AcTime        Long        time of last access (GMT/UTC)
+/// ModTime       Long        time of last modification (GMT/UTC)
+/// UID           Short       Unix user ID (optional)
+/// GID           Short       Unix group ID (optional)
+/// ```
+#[derive(Clone)]
+pub struct ExtraInfoZipUnixField {
+    /// file last access time
+    pub atime: u32,
+    /// file last modification time
+    pub mtime: u32,
+    /// file user id, if present
+    pub uid: Option<u16>,
+    /// file group id, if present
+    pub gid: Option<u16>,
+}
+
+impl ExtraInfoZipUnixField {
+    const TAG: u16 = 0x5855;
+
+    fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
+        let atime = le_u32.parse_next(i)?;
+        let mtime = le_u32.parse_next(i)?;
+        let ids = opt((le_u16, le_u16).complete_err()).parse_next(i)?;
+        Ok(Self {
+            atime,
+            mtime,
+            uid: ids.map(|(uid, _)| uid),
+            gid: ids.map(|(_, gid)| gid),
+        })
+    }
+}
+
 /// Info-ZIP New Unix Extra Field:
 /// ====================================
 ///
@@ -276,6 +355,169 @@ impl ExtraNewUnixField {
     }
 }
 
+/// 4.6.9 -Info-ZIP Unicode Path Extra Field (0x7075):
+///
+/// Stores the UTF-8 version of the entry's name, for archivers that write the
+/// main name field in a legacy encoding.
+///
+/// ```text
+/// Value         Size        Description
+/// -----         ----        -----------
+/// 0x7075        Short       tag for this extra block type ("up")
+/// TSize         Short       total data size for this block
+/// Version       1 byte      version of this extra field, currently 1
+/// NameCRC32     4 bytes     File Name Field CRC32 Checksum
+/// UnicodeName   Variable    UTF-8 version of the entry File Name
+/// ```
+#[derive(Clone, ToOwned, IntoOwned)]
+pub struct ExtraUnicodePathField<'a> {
+    /// version of this extra field, currently 1
+    pub version: u8,
+
+    /// CRC-32 checksum of the name field this was made from
+    pub name_crc32: u32,
+
+    /// UTF-8 version of the name
+    pub name: Cow<'a, [u8]>,
+}
+
+impl<'a> ExtraUnicodePathField<'a> {
+    const TAG: u16 = 0x7075;
+
+    fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
+        seq! {Self {
+            version: le_u8,
+            name_crc32: le_u32,
+            name: rest.map(Cow::Borrowed),
+        }}
+        .parse_next(i)
+    }
+
+    /// Returns the UTF-8 name stored in this field, if it's valid UTF-8 and
+    /// was made from `name` (the entry's name field, as stored).
+    ///
+    /// Archivers that rename an entry without updating this field leave
+    /// behind a checksum that doesn't match, in which case this returns
+    /// `None`, and the name field should be used instead.
+    pub fn unicode_name(&self, name: &[u8]) -> Option<&str> {
+        if self.version != 1 || crc32fast::hash(name) != self.name_crc32 {
+            return None;
+        }
+        std::str::from_utf8(&self.name).ok()
+    }
+}
+
+/// 4.6.8 -Info-ZIP Unicode Comment Extra Field (0x6375):
+///
+/// Stores the UTF-8 version of the entry's comment, for archivers that write
+/// the main comment field in a legacy encoding.
+///
+/// ```text
+/// Value         Size        Description
+/// -----         ----        -----------
+/// 0x6375        Short       tag for this extra block type ("uc")
+/// TSize         Short       total data size for this block
+/// Version       1 byte      version of this extra field, currently 1
+/// ComCRC32      4 bytes     Comment Field CRC32 Checksum
+/// UnicodeCom    Variable    UTF-8 version of the entry comment
+/// ```
+#[derive(Clone, ToOwned, IntoOwned)]
+pub struct ExtraUnicodeCommentField<'a> {
+    /// version of this extra field, currently 1
+    pub version: u8,
+
+    /// CRC-32 checksum of the comment field this was made from
+    pub comment_crc32: u32,
+
+    /// UTF-8 version of the comment
+    pub comment: Cow<'a, [u8]>,
+}
+
+impl<'a> ExtraUnicodeCommentField<'a> {
+    const TAG: u16 = 0x6375;
+
+    fn parser(i: &mut Partial<&'a [u8]>) -> PResult<Self> {
+        seq! {Self {
+            version: le_u8,
+            comment_crc32: le_u32,
+            comment: rest.map(Cow::Borrowed),
+        }}
+        .parse_next(i)
+    }
+
+    /// Returns the UTF-8 comment stored in this field, if it's valid UTF-8
+    /// and was made from `comment` (the entry's comment field, as stored).
+    ///
+    /// See [ExtraUnicodePathField::unicode_name] for why this may be `None`.
+    pub fn unicode_comment(&self, comment: &[u8]) -> Option<&str> {
+        if self.version != 1 || crc32fast::hash(comment) != self.comment_crc32 {
+            return None;
+        }
+        std::str::from_utf8(&self.comment).ok()
+    }
+}
+
+/// WinZip AES extra field (0x9901), see Appendix E of the APPNOTE
+///
+/// Entries encrypted with AES have their method set to [Method::Aex], and
+/// this field holds the method their data was compressed with.
+///
+/// ```text
+/// Value         Size        Description
+/// -----         ----        -----------
+/// 0x9901        Short       tag for this extra block type
+/// TSize         Short       total data size for this block (7)
+/// Version       Short       1 for AE-1, 2 for AE-2
+/// VendorID      2 bytes     "AE"
+/// Strength      1 byte      1 for AES-128, 2 for AES-192, 3 for AES-256
+/// Method        Short       actual compression method
+/// ```
+#[derive(Clone)]
+pub struct ExtraWinZipAesField {
+    /// 1 for AE-1 (CRC-32 is stored), 2 for AE-2 (CRC-32 is zero)
+    pub version: u16,
+
+    /// Strength of the encryption
+    pub strength: AesStrength,
+
+    /// Method the data was compressed with, before being encrypted
+    pub method: Method,
+}
+
+impl ExtraWinZipAesField {
+    const TAG: u16 = 0x9901;
+
+    fn parser(i: &mut Partial<&'_ [u8]>) -> PResult<Self> {
+        let version = le_u16.parse_next(i)?;
+        let _ = literal("AE").parse_next(i)?;
+        let strength = le_u8
+            .verify_map(|strength| match strength {
+                1 => Some(AesStrength::Aes128),
+                2 => Some(AesStrength::Aes192),
+                3 => Some(AesStrength::Aes256),
+                _ => None,
+            })
+            .parse_next(i)?;
+        let method = Method::parser.parse_next(i)?;
+        Ok(Self {
+            version,
+            strength,
+            method,
+        })
+    }
+}
+
+/// Key size of WinZip AES encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AesStrength {
+    /// 128-bit key
+    Aes128,
+    /// 192-bit key
+    Aes192,
+    /// 256-bit key
+    Aes256,
+}
+
 /// 4.5.5 -NTFS Extra Field (0x000a):
 #[derive(Clone)]
 pub struct ExtraNtfsField {
@@ -2,7 +2,7 @@ use std::borrow::Cow;
 
 use crate::{
     encoding::{detect_utf8, Encoding},
-    error::{Error, FormatError, UnsupportedError},
+    error::{Error, UnsupportedError},
     parse::{Method, MsdosTimestamp, Version},
 };
 
@@ -114,6 +114,23 @@ impl<'a> LocalFileHeader<'a> {
         })
     }
 
+    /// Serializes the header, as read by [Self::parser]. Method-specific
+    /// properties are part of the entry's data, and aren't written.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(Self::SIGNATURE.as_bytes());
+        self.reader_version.write(out);
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        out.extend_from_slice(&u16::from(self.method).to_le_bytes());
+        self.modified.write(out);
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        out.extend_from_slice(&self.compressed_size.to_le_bytes());
+        out.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        out.extend_from_slice(&(self.name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(self.extra.len() as u16).to_le_bytes());
+        out.extend_from_slice(&self.name);
+        out.extend_from_slice(&self.extra);
+    }
+
     /// Check for the presence of the bit flag that indicates a data descriptor
     /// is present after the file data.
     pub fn has_data_descriptor(&self) -> bool {
@@ -122,6 +139,15 @@ impl<'a> LocalFileHeader<'a> {
         self.flags & 0b1000 != 0
     }
 
+    /// Which fields the zip64 extra field of this header holds
+    pub(crate) fn extra_settings(&self) -> ExtraFieldSettings {
+        ExtraFieldSettings {
+            compressed_size_u32: self.compressed_size,
+            uncompressed_size_u32: self.uncompressed_size,
+            header_offset_u32: 0,
+        }
+    }
+
     /// Converts the local file header into an entry.
     pub fn as_entry(&self) -> Result<Entry, Error> {
         // see APPNOTE 4.4.4: Bit 11 is the language encoding flag (EFS)
@@ -131,16 +157,27 @@ impl<'a> LocalFileHeader<'a> {
         } else {
             Encoding::Cp437
         };
-        let name = encoding.decode(&self.name[..])?;
+        let name = encoding.decode_lossy(&self.name[..]);
+
+        let settings = self.extra_settings();
 
         let mut entry = Entry {
             name,
+            name_bytes: self.name.to_vec(),
             method: self.method,
             comment: Default::default(),
+            comment_bytes: Default::default(),
             modified: self.modified.to_datetime().unwrap_or_else(zero_datetime),
+            modified_local: self
+                .modified
+                .to_naive_datetime()
+                .unwrap_or_else(|| zero_datetime().naive_utc()),
             created: None,
             accessed: None,
             header_offset: 0,
+            disk_nbr_start: 0,
+            // the local header doesn't say
+            creator_version: self.reader_version,
             reader_version: self.reader_version,
             flags: self.flags,
             uid: None,
@@ -149,6 +186,10 @@ impl<'a> LocalFileHeader<'a> {
             compressed_size: self.compressed_size as _,
             uncompressed_size: self.uncompressed_size as _,
             mode: Mode(0),
+            extra: self.extra.to_vec(),
+            extra_settings: settings,
+            extra_is_central: false,
+            local_extra: Some((self.extra.to_vec(), settings)),
         };
 
         if entry.name.ends_with('/') {
@@ -157,11 +198,6 @@ impl<'a> LocalFileHeader<'a> {
         };
 
         let mut slice = Partial::new(&self.extra[..]);
-        let settings = ExtraFieldSettings {
-            compressed_size_u32: self.compressed_size,
-            uncompressed_size_u32: self.uncompressed_size,
-            header_offset_u32: 0,
-        };
 
         while !slice.is_empty() {
             match ExtraField::mk_parser(settings).parse_next(&mut slice) {
@@ -169,8 +205,10 @@ impl<'a> LocalFileHeader<'a> {
                     entry.set_extra_field(&ef);
                 }
                 Err(e) => {
+                    // some writers leave a partial field at the end, that's
+                    // no reason to give up on the whole entry
                     trace!("extra field error: {:#?}", e);
-                    return Err(FormatError::InvalidExtraField.into());
+                    break;
                 }
             }
         }
@@ -223,6 +261,15 @@ impl DataDescriptorRecord {
             }
         }
     }
+
+    /// Serializes the record, with its signature, as read by
+    /// [Self::mk_parser] with `is_zip64` false.
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(Self::SIGNATURE.as_bytes());
+        out.extend_from_slice(&self.crc32.to_le_bytes());
+        out.extend_from_slice(&(self.compressed_size as u32).to_le_bytes());
+        out.extend_from_slice(&(self.uncompressed_size as u32).to_le_bytes());
+    }
 }
 
 /// 5.8.5 LZMA Properties header
@@ -25,8 +25,14 @@ pub use date_time::*;
 mod central_directory_file_header;
 pub use central_directory_file_header::*;
 
+mod digital_signature;
+pub use digital_signature::*;
+
 mod eocd;
 pub use eocd::*;
 
 mod local_headers;
 pub use local_headers::*;
+
+mod raw;
+pub use raw::*;
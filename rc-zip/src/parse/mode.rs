@@ -1,5 +1,7 @@
 use std::fmt;
 
+use super::HostSystem;
+
 /// Mode represents a file's mode and permission bits.
 /// The bits have the same definition on all systems,
 /// but not all bits apply to all systems.
@@ -37,6 +39,48 @@ impl Mode {
     pub const IRREGULAR: Self = Self(1 << 19);
 }
 
+impl Mode {
+    /// Interprets the external attributes of a central directory header,
+    /// written on `host_system`.
+    ///
+    /// Unix-like hosts store Unix mode bits in the upper 16 bits, others
+    /// only have MS-DOS attributes in the lower 8 bits. Unix hosts that left
+    /// the upper bits empty, and unknown hosts that didn't fill them, are
+    /// read as MS-DOS attributes too, rather than as mode `0o000`.
+    ///
+    /// Some writers only mark directories in one of the two: either the
+    /// Unix `S_IFDIR` file type or the MS-DOS directory attribute is enough
+    /// for the entry to be a directory, unless the Unix mode says it's
+    /// something else.
+    pub fn from_external_attrs(host_system: HostSystem, external_attrs: u32) -> Self {
+        let unix_mode = external_attrs >> 16;
+        let mut mode = match host_system {
+            HostSystem::Unix | HostSystem::Osx if unix_mode != 0 => UnixMode(unix_mode).into(),
+            // Info-ZIP and Go write NTFS as 11, which the APPNOTE assigns to MVS
+            HostSystem::MsDos
+            | HostSystem::WindowsNtfs
+            | HostSystem::Mvs
+            | HostSystem::Vfat
+            | HostSystem::Unix
+            | HostSystem::Osx => MsdosMode(external_attrs).into(),
+            _ if unix_mode != 0 => UnixMode(unix_mode).into(),
+            _ => MsdosMode(external_attrs).into(),
+        };
+
+        let unix_type = UnixMode(unix_mode) & UnixMode::IFMT;
+        let is_dir = match unix_type {
+            UnixMode::IFDIR => true,
+            // no file type recorded, only permissions (if anything)
+            UnixMode(0) => MsdosMode(external_attrs).has(MsdosMode::DIR),
+            _ => false,
+        };
+        if is_dir {
+            mode |= Mode::DIR;
+        }
+        mode
+    }
+}
+
 impl fmt::Display for Mode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut w = 0;
@@ -138,15 +182,52 @@ impl From<UnixMode> for Mode {
     }
 }
 
+impl From<Mode> for UnixMode {
+    fn from(m: Mode) -> Self {
+        let mut mode = UnixMode(m.0 & 0o777);
+
+        mode |= if m.has(Mode::DIR) {
+            UnixMode::IFDIR
+        } else if m.has(Mode::SYMLINK) {
+            UnixMode::IFLNK
+        } else if m.has(Mode::NAMED_PIPE) {
+            UnixMode::IFIFO
+        } else if m.has(Mode::SOCKET) {
+            UnixMode::IFSOCK
+        } else if m.has(Mode::CHAR_DEVICE) {
+            UnixMode::IFCHR
+        } else if m.has(Mode::DEVICE) {
+            UnixMode::IFBLK
+        } else {
+            UnixMode::IFREG
+        };
+
+        if m.has(Mode::SETGID) {
+            mode |= UnixMode::ISGID
+        }
+        if m.has(Mode::SETUID) {
+            mode |= UnixMode::ISUID
+        }
+        if m.has(Mode::STICKY) {
+            mode |= UnixMode::ISVTX
+        }
+
+        mode
+    }
+}
+
 impl From<MsdosMode> for Mode {
+    /// MS-DOS attributes have no permission bits: this picks the usual
+    /// `0o644` for files and `0o755` for directories, minus the write bits
+    /// for read-only ones.
     fn from(m: MsdosMode) -> Self {
         let mut mode = if m.has(MsdosMode::DIR) {
-            Mode::DIR | Mode(0o777)
+            Mode::DIR | Mode(0o755)
         } else {
-            Mode(0o666)
+            Mode(0o644)
         };
         if m.has(MsdosMode::READ_ONLY) {
-            mode &= Mode(0o222);
+            mode &= Mode(!0o222);
         }
 
         mode
@@ -0,0 +1,214 @@
+use winnow::{error::ErrMode, stream::Offset, PResult, Parser, Partial};
+
+use crate::error::{Error, FormatError};
+
+use super::{
+    CentralDirectoryFileHeader, DataDescriptorRecord, EndOfCentralDirectory64Locator,
+    EndOfCentralDirectory64Record, EndOfCentralDirectoryRecord, LocalFileHeader,
+};
+
+/// A low-level view of a zip archive: every structural record found in the
+/// file, in physical order, with its offset and length.
+///
+/// Unlike [Archive](crate::parse::Archive), nothing is normalized or
+/// hidden: local file headers, file data and data descriptors are listed
+/// alongside the central directory and the end of central directory records.
+///
+/// It is built by higher-level crates (see `ArchiveHandle::raw_archive` in
+/// [rc-zip-sync](https://crates.io/crates/rc-zip-sync)), since it requires
+/// reading from arbitrary offsets in the file.
+pub struct RawArchive {
+    /// All records, sorted by offset
+    pub records: Vec<RawRecord>,
+}
+
+impl RawArchive {
+    /// Builds a raw archive from a list of records, in any order.
+    pub fn new(mut records: Vec<RawRecord>) -> Self {
+        records.sort_by_key(|r| r.offset);
+        Self { records }
+    }
+
+    /// Iterate over all records, in physical file order.
+    pub fn records(&self) -> impl Iterator<Item = &RawRecord> {
+        self.records.iter()
+    }
+}
+
+/// A single structural record of a zip file, and where it was found.
+pub struct RawRecord {
+    /// Absolute offset of the record, from the start of the file
+    pub offset: u64,
+
+    /// Length of the record, in bytes
+    pub length: u64,
+
+    /// The record itself, as parsed
+    pub kind: RawRecordKind,
+}
+
+impl RawRecord {
+    /// Parses a local file header found at `offset`. `data` must start with
+    /// the header. Returns `Ok(None)` if `data` is too short to contain the
+    /// whole header.
+    pub fn local_file_header(offset: u64, data: &[u8]) -> Result<Option<Self>, Error> {
+        let res = parse_record(
+            data,
+            LocalFileHeader::parser,
+            FormatError::InvalidLocalHeader,
+        )?;
+        Ok(res.map(|(header, _)| {
+            // the header parser also consumes the LZMA properties, but those
+            // are part of the file data as far as the APPNOTE is concerned.
+            let length = 30 + header.name.len() as u64 + header.extra.len() as u64;
+            Self {
+                offset,
+                length,
+                kind: RawRecordKind::LocalFileHeader(header.into_owned()),
+            }
+        }))
+    }
+
+    /// Parses a data descriptor found at `offset`. `data` must start with the
+    /// descriptor. Returns `Ok(None)` if `data` is too short.
+    pub fn data_descriptor(
+        offset: u64,
+        data: &[u8],
+        is_zip64: bool,
+    ) -> Result<Option<Self>, Error> {
+        let res = parse_record(
+            data,
+            DataDescriptorRecord::mk_parser(is_zip64),
+            FormatError::InvalidDataDescriptor,
+        )?;
+        Ok(res.map(|(descriptor, length)| Self {
+            offset,
+            length,
+            kind: RawRecordKind::DataDescriptor(descriptor),
+        }))
+    }
+
+    /// Parses a zip64 end of central directory locator found at `offset`.
+    /// Returns `Ok(None)` if `data` is too short.
+    pub fn eocd64_locator(offset: u64, data: &[u8]) -> Result<Option<Self>, Error> {
+        let res = parse_record(
+            data,
+            EndOfCentralDirectory64Locator::parser,
+            FormatError::Directory64EndRecordInvalid,
+        )?;
+        Ok(res.map(|(locator, length)| Self {
+            offset,
+            length,
+            kind: RawRecordKind::EndOfCentralDirectory64Locator(locator),
+        }))
+    }
+
+    /// Parses a zip64 end of central directory record found at `offset`.
+    /// Returns `Ok(None)` if `data` is too short.
+    pub fn eocd64(offset: u64, data: &[u8]) -> Result<Option<Self>, Error> {
+        let res = parse_record(
+            data,
+            EndOfCentralDirectory64Record::parser,
+            FormatError::Directory64EndRecordInvalid,
+        )?;
+        Ok(res.map(|(record, _)| Self {
+            offset,
+            // the record size doesn't include the leading 12 bytes, and
+            // there may be an extensible data sector we don't parse.
+            length: 12 + record.record_size,
+            kind: RawRecordKind::EndOfCentralDirectory64Record(record),
+        }))
+    }
+
+    /// Parses an end of central directory record found at `offset`.
+    /// Returns `Ok(None)` if `data` is too short.
+    pub fn eocd(offset: u64, data: &[u8]) -> Result<Option<Self>, Error> {
+        let res = parse_record(
+            data,
+            EndOfCentralDirectoryRecord::parser,
+            FormatError::DirectoryEndSignatureNotFound,
+        )?;
+        Ok(res.map(|(record, length)| Self {
+            offset,
+            length,
+            kind: RawRecordKind::EndOfCentralDirectoryRecord(record.into_owned()),
+        }))
+    }
+
+    /// Parses all central directory file headers from `data`, which must
+    /// contain the whole central directory, found at `offset`.
+    /// `directory_records` is the number of records announced by the end of
+    /// central directory record, used for error reporting.
+    pub fn central_directory(
+        offset: u64,
+        data: &[u8],
+        directory_records: u64,
+    ) -> Result<Vec<Self>, Error> {
+        let mut records = Vec::new();
+        let mut input = Partial::new(data);
+        while !input.is_empty() {
+            let start = input.offset_from(&Partial::new(data)) as u64;
+            match CentralDirectoryFileHeader::parser.parse_next(&mut input) {
+                Ok(header) => {
+                    let end = input.offset_from(&Partial::new(data)) as u64;
+                    records.push(Self {
+                        offset: offset + start,
+                        length: end - start,
+                        kind: RawRecordKind::CentralDirectoryFileHeader(header.into_owned()),
+                    });
+                }
+                Err(_) => {
+                    return Err(FormatError::InvalidCentralRecord {
+                        expected: records.len() as u16,
+                        actual: directory_records as u16,
+                    }
+                    .into())
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Runs `parser` on `data`, returning the parsed value and how many bytes it
+/// consumed, or `None` if more data is needed.
+fn parse_record<'a, T>(
+    data: &'a [u8],
+    mut parser: impl FnMut(&mut Partial<&'a [u8]>) -> PResult<T>,
+    err: FormatError,
+) -> Result<Option<(T, u64)>, Error> {
+    let mut input = Partial::new(data);
+    match parser(&mut input) {
+        Ok(value) => Ok(Some((value, input.offset_from(&Partial::new(data)) as u64))),
+        Err(ErrMode::Incomplete(_)) => Ok(None),
+        Err(_) => Err(err.into()),
+    }
+}
+
+/// The different kinds of records that make up a zip file.
+pub enum RawRecordKind {
+    /// 4.3.7 Local file header
+    LocalFileHeader(LocalFileHeader<'static>),
+
+    /// 4.3.8 File data (compressed, possibly encrypted), for the
+    /// entry at the given index in the central directory
+    FileData {
+        /// index of the entry in the central directory
+        entry_index: usize,
+    },
+
+    /// 4.3.9 Data descriptor
+    DataDescriptor(DataDescriptorRecord),
+
+    /// 4.3.12 Central directory file header
+    CentralDirectoryFileHeader(CentralDirectoryFileHeader<'static>),
+
+    /// 4.3.14 Zip64 end of central directory record
+    EndOfCentralDirectory64Record(EndOfCentralDirectory64Record),
+
+    /// 4.3.15 Zip64 end of central directory locator
+    EndOfCentralDirectory64Locator(EndOfCentralDirectory64Locator),
+
+    /// 4.3.16 End of central directory record
+    EndOfCentralDirectoryRecord(EndOfCentralDirectoryRecord<'static>),
+}
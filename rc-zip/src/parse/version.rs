@@ -34,6 +34,20 @@ impl Version {
         }}
         .parse_next(i)
     }
+
+    /// Returns the system the entry was created on, which tells how to
+    /// interpret its external attributes: as Unix mode bits, or as MS-DOS
+    /// attributes (see [Mode::from_external_attrs](crate::parse::Mode::from_external_attrs)).
+    #[inline(always)]
+    pub fn host_system(&self) -> HostSystem {
+        self.host_system
+    }
+
+    /// Serializes the version, as read by [Self::parser]
+    pub(crate) fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.version);
+        out.push(self.host_system.into());
+    }
 }
 
 /// System on which an archive was created, as encoded into a version u16.
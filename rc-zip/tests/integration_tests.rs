@@ -1,8 +1,16 @@
 use std::cmp;
 
+use chrono::NaiveDate;
+
 use rc_zip::{
-    corpus,
-    fsm::{ArchiveFsm, FsmResult},
+    corpus::{self, zips_dir},
+    encoding::Encoding,
+    error::{Error, FormatError, UnsupportedError},
+    fsm::{ArchiveFsm, ArchiveLimits, EntryFsm, FsmResult, RecoveryMode, Validation},
+    parse::{
+        AesStrength, Archive, Encryption, Entry, EntryBuilder, EntryKind, ExtraField, HostSystem,
+        Method, Mode, MsdosMode, SortKey,
+    },
 };
 
 #[test]
@@ -49,3 +57,1151 @@ fn state_machine() {
     // cool, we have the archive
     let _ = archive;
 }
+
+/// Drives an [ArchiveFsm] to completion over an in-memory zip file.
+fn read_archive(mut fsm: ArchiveFsm, bytes: &[u8]) -> Result<Archive, Error> {
+    loop {
+        if let Some(offset) = fsm.wants_read() {
+            let offset = offset as usize;
+            if offset >= bytes.len() {
+                return Err(Error::IO(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            let slice = &bytes[offset..];
+            let len = cmp::min(slice.len(), fsm.space().len());
+            fsm.space()[..len].copy_from_slice(&slice[..len]);
+            fsm.fill(len);
+        }
+
+        fsm = match fsm.process()? {
+            FsmResult::Continue(fsm) => fsm,
+            FsmResult::Done(archive) => return Ok(archive),
+        }
+    }
+}
+
+#[test]
+fn oversized_name_is_rejected() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+
+    // make the first central directory header claim a 65535-byte name
+    let header_offset = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[header_offset + 28..header_offset + 30].copy_from_slice(&u16::MAX.to_le_bytes());
+
+    let limits = ArchiveLimits {
+        max_name_len: 1024,
+        ..Default::default()
+    };
+    let fsm = ArchiveFsm::new(bytes.len() as u64).with_limits(limits);
+    match read_archive(fsm, &bytes) {
+        Err(Error::Format(FormatError::FieldTooLong { field, len, max })) => {
+            assert_eq!(field, "name");
+            assert_eq!(len, u16::MAX as usize);
+            assert_eq!(max, 1024);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+
+    // the untouched file is fine with the same limits
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64).with_limits(limits);
+    assert_eq!(read_archive(fsm, &bytes).unwrap().entries().count(), 2);
+}
+
+#[test]
+fn into_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+
+    let borrowed: Vec<_> = archive
+        .entries()
+        .map(|e| (e.name.clone(), e.header_offset, e.crc32))
+        .collect();
+    let owned: Vec<_> = archive
+        .into_entries()
+        .into_iter()
+        .map(|e| (e.name, e.header_offset, e.crc32))
+        .collect();
+    assert_eq!(borrowed.len(), 2);
+    assert_eq!(borrowed, owned);
+}
+
+#[test]
+fn entry_builder() {
+    corpus::install_test_subscriber();
+
+    let entry = EntryBuilder::new("hello.txt").build();
+    assert_eq!(entry.name, "hello.txt");
+    assert_eq!(entry.name_bytes(), b"hello.txt");
+    assert_eq!(entry.method, Method::Deflate);
+    assert_eq!(entry.mode, Mode(0o644));
+    assert!(matches!(entry.kind(), EntryKind::File));
+    assert_eq!(entry.flags & 0x800, 0);
+
+    let dir = EntryBuilder::new("subdir/").build();
+    assert!(matches!(dir.kind(), EntryKind::Directory));
+
+    let modified = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+    let extra = [0x55, 0x54, 0x05, 0x00, 0x01, 0x00, 0xf1, 0x53, 0x65];
+    let entry = EntryBuilder::new("français.txt")
+        .method(Method::Store)
+        .modified(modified)
+        .mode(Mode::SYMLINK | Mode(0o777))
+        .comment("a comment")
+        .extra(extra)
+        .build();
+    assert_eq!(entry.method, Method::Store);
+    assert_eq!(entry.modified, modified);
+    assert!(matches!(entry.kind(), EntryKind::Symlink));
+    assert_eq!(entry.comment, "a comment");
+    assert_eq!(entry.extra(), &extra);
+    assert_ne!(entry.flags & 0x800, 0, "non-ASCII names get the UTF-8 flag");
+}
+
+#[test]
+fn huge_record_count_is_not_trusted() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("zip64.zip")).unwrap();
+
+    // make the zip64 end of central directory record claim an absurd number
+    // of records
+    let eocd64_offset = bytes.windows(4).position(|w| w == b"PK\x06\x06").unwrap();
+    for field in [24, 32] {
+        let field = eocd64_offset + field;
+        bytes[field..field + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    }
+
+    // reserving room for that many headers would abort: this must fail
+    // gracefully instead, after reading the one header that's there.
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    match read_archive(fsm, &bytes) {
+        Err(Error::Format(FormatError::InvalidCentralRecord { expected, actual })) => {
+            assert_eq!(expected, 1);
+            assert_eq!(actual, u16::MAX);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[test]
+fn max_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let limits = ArchiveLimits {
+        max_entries: Some(1),
+        ..Default::default()
+    };
+
+    let expect_too_many = |bytes: &[u8]| {
+        let fsm = ArchiveFsm::new(bytes.len() as u64).with_limits(limits);
+        match read_archive(fsm, bytes) {
+            Err(Error::Format(FormatError::ImpossibleNumberOfFiles {
+                claimed_records_count,
+                zip_size,
+            })) => {
+                assert_eq!(claimed_records_count, 2);
+                assert_eq!(zip_size, bytes.len() as u64);
+            }
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("should have failed"),
+        }
+    };
+
+    // test.zip has two entries, as announced by its end of central directory
+    expect_too_many(&bytes);
+
+    // the actual number of headers counts too, whatever the record says
+    let mut lying = bytes.clone();
+    let eocd_offset = lying.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    for field in [8, 10] {
+        lying[eocd_offset + field..eocd_offset + field + 2].copy_from_slice(&1u16.to_le_bytes());
+    }
+    expect_too_many(&lying);
+
+    let fsm = ArchiveFsm::new(bytes.len() as u64).with_limits(ArchiveLimits {
+        max_entries: Some(2),
+        ..Default::default()
+    });
+    assert!(read_archive(fsm, &bytes).is_ok());
+}
+
+#[test]
+fn digest() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let digest = |bytes: &[u8]| {
+        let fsm = ArchiveFsm::new(bytes.len() as u64);
+        read_archive(fsm, bytes).unwrap().digest()
+    };
+
+    // stable across reopens
+    assert_eq!(digest(&bytes), digest(&bytes));
+
+    // a different CRC-32 in the central directory is a different archive
+    let mut modified = bytes.clone();
+    let header_offset = modified
+        .windows(4)
+        .position(|w| w == b"PK\x01\x02")
+        .unwrap();
+    modified[header_offset + 16] ^= 0xff;
+    assert_ne!(digest(&bytes), digest(&modified));
+
+    // other zips have other digests
+    let other = std::fs::read(zips_dir().join("zip64.zip")).unwrap();
+    assert_ne!(digest(&bytes), digest(&other));
+}
+
+#[test]
+fn redecode_names() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("shift-jis.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let mut archive = read_archive(fsm, &bytes).unwrap();
+    let names =
+        |archive: &Archive| -> Vec<String> { archive.entries().map(|e| e.name.clone()).collect() };
+    let expected = vec![
+        "should-be-jis/ot_運命のワルツﾈぞなぞ小さな楽しみ遊びま.longboi",
+        "should-be-jis/",
+    ];
+
+    archive.redecode_names(Encoding::Cp437);
+    assert_eq!(archive.encoding(), Encoding::Cp437);
+    assert_ne!(names(&archive), expected);
+
+    archive.redecode_names(Encoding::ShiftJis);
+    assert_eq!(archive.encoding(), Encoding::ShiftJis);
+    assert_eq!(names(&archive), expected);
+}
+
+#[test]
+fn by_name() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("duplicate-names.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let mut archive = read_archive(fsm, &bytes).unwrap();
+
+    // entries stay in central directory order
+    let names = archive
+        .entries()
+        .map(|e| e.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a.txt", "b.txt", "a.txt"]);
+
+    // the first entry with a given name wins
+    assert_eq!(archive.by_name("a.txt").unwrap().uncompressed_size, 6);
+    assert_eq!(archive.by_name("b.txt").unwrap().uncompressed_size, 6);
+    assert!(archive.by_name("c.txt").is_none());
+
+    // the index follows names around when they're decoded again
+    archive.redecode_names(Encoding::Cp437);
+    assert_eq!(archive.by_name("a.txt").unwrap().uncompressed_size, 6);
+}
+
+#[test]
+fn entries_under() {
+    corpus::install_test_subscriber();
+
+    let read = |name: &str| {
+        let bytes = std::fs::read(zips_dir().join(name)).unwrap();
+        let fsm = ArchiveFsm::new(bytes.len() as u64);
+        read_archive(fsm, &bytes).unwrap()
+    };
+
+    let archive = read("meta.zip");
+    let names = |prefix| {
+        archive
+            .entries_under(prefix)
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names("rc-zip/src/parse"),
+        names("rc-zip/src/parse/"),
+        "trailing slashes don't matter"
+    );
+    assert_eq!(names("rc-zip/src/fsm/entry").len(), 7);
+    assert!(names("rc-zip/src/fsm")
+        .iter()
+        .all(|name| name.starts_with("rc-zip/src/fsm/") && *name != "rc-zip/src/fsm/"));
+    assert!(names("rc-zip/sr").is_empty(), "only whole components match");
+    assert_eq!(names("").len(), archive.len());
+
+    assert_eq!(
+        archive.immediate_children("rc-zip"),
+        ["CHANGELOG.md", "Cargo.toml", "README.md", "src/", "tests/"]
+    );
+    assert_eq!(
+        archive.immediate_children("rc-zip/src/"),
+        [
+            "corpus/",
+            "encoding.rs",
+            "error.rs",
+            "fsm/",
+            "lib.rs",
+            "parse/"
+        ]
+    );
+    assert_eq!(archive.immediate_children(""), ["rc-zip/"]);
+    assert!(archive.immediate_children("rc-zip/README.md").is_empty());
+
+    // names and prefixes are normalized, and directories without entries of
+    // their own are listed too
+    let archive = read("noisy-paths.zip");
+    assert_eq!(archive.immediate_children("./"), ["a/", "d/", "plain.txt"]);
+    assert_eq!(archive.immediate_children("d//"), ["e/"]);
+    let names = archive
+        .entries_under("a")
+        .map(|e| e.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["./a//b.txt", "a/./c.txt"]);
+}
+
+#[test]
+fn find_first() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+
+    let png = archive.find_first(|e| e.name.ends_with(".png")).unwrap();
+    assert_eq!(png.name, "gophercolor16x16.png");
+
+    let big = archive.find_first(|e| e.uncompressed_size > 100).unwrap();
+    assert_eq!(big.name, "gophercolor16x16.png");
+
+    assert!(archive.find_first(|e| e.name.ends_with(".toml")).is_none());
+}
+
+#[test]
+fn spanned_zip64_archive() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("zip64.zip")).unwrap();
+
+    // make the zip64 end of central directory locator claim 3 disks
+    let locator_offset = bytes.windows(4).position(|w| w == b"PK\x06\x07").unwrap();
+    bytes[locator_offset + 16..locator_offset + 20].copy_from_slice(&3u32.to_le_bytes());
+
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    match read_archive(fsm, &bytes) {
+        Err(Error::Unsupported(UnsupportedError::SpannedArchiveUnsupported { total_disks })) => {
+            assert_eq!(total_disks, 3);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[test]
+fn extra_fields() {
+    corpus::install_test_subscriber();
+
+    let read = |name: &str| {
+        let bytes = std::fs::read(zips_dir().join(name)).unwrap();
+        let fsm = ArchiveFsm::new(bytes.len() as u64);
+        read_archive(fsm, &bytes).unwrap()
+    };
+
+    let archive = read("unix.zip");
+    let hello = archive.by_name("hello").unwrap();
+    let fields = hello.extra_fields().collect::<Vec<_>>();
+    assert_eq!(fields.len(), 2);
+    assert!(matches!(fields[0], ExtraField::Timestamp(_)));
+    match &fields[1] {
+        ExtraField::NewUnix(unix) => {
+            assert_eq!(unix.uid, 1000);
+            assert_eq!(unix.gid, 1000);
+        }
+        _ => panic!("expected a new unix extra field"),
+    }
+
+    let archive = read("zip64.zip");
+    let readme = archive.by_name("README").unwrap();
+    let fields = readme.extra_fields().collect::<Vec<_>>();
+    assert_eq!(fields.len(), 1);
+    match &fields[0] {
+        ExtraField::Zip64(z64) => {
+            assert_eq!(z64.uncompressed_size, 36);
+            assert_eq!(z64.compressed_size, 36);
+        }
+        _ => panic!("expected a zip64 extra field"),
+    }
+
+    // fields that aren't understood come with their payload
+    let entry = EntryBuilder::new("unknown.txt")
+        .extra(b"\x34\x12\x03\x00abc".to_vec())
+        .build();
+    match entry.extra_fields().collect::<Vec<_>>()[..] {
+        [ExtraField::Unknown { tag, data }] => {
+            assert_eq!(tag, 0x1234);
+            assert_eq!(data, b"abc");
+        }
+        _ => panic!("expected a single unknown extra field"),
+    }
+}
+
+#[test]
+fn set_comment() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let mut archive = read_archive(fsm, &bytes).unwrap();
+    assert_eq!(archive.comment(), "This is a zipfile comment.");
+
+    match archive.set_comment("a".repeat(70000)) {
+        Err(FormatError::CommentTooLong { len, max }) => {
+            assert_eq!(len, 70000);
+            assert_eq!(max, u16::MAX as usize);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+    assert_eq!(archive.comment(), "This is a zipfile comment.");
+
+    let longest = "a".repeat(u16::MAX as usize);
+    archive.set_comment(longest.as_str()).unwrap();
+    assert_eq!(archive.comment(), longest);
+
+    archive.set_comment("r\u{e9}sum\u{e9}").unwrap();
+    archive.redecode_names(Encoding::Utf8);
+    assert_eq!(archive.comment(), "r\u{e9}sum\u{e9}");
+}
+
+#[test]
+fn missing_zip64_eocd() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+
+    // claim the central directory size is in a zip64 record that isn't there
+    let eocd_offset = bytes.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    bytes[eocd_offset + 12..eocd_offset + 16].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    match read_archive(fsm, &bytes) {
+        Err(Error::Format(FormatError::MissingZip64Eocd)) => {}
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[test]
+fn inconsistent_directory_size() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+
+    // claim the central directory is larger than everything before the
+    // end of central directory record
+    let eocd_offset = bytes.windows(4).rposition(|w| w == b"PK\x05\x06").unwrap();
+    let directory_size = eocd_offset as u32 + 1;
+    bytes[eocd_offset + 12..eocd_offset + 16].copy_from_slice(&directory_size.to_le_bytes());
+
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    match read_archive(fsm, &bytes) {
+        Err(Error::Format(FormatError::InconsistentDirectorySize {
+            directory_size: size,
+            directory_end,
+        })) => {
+            assert_eq!(size, directory_size as u64);
+            assert_eq!(directory_end, eocd_offset as u64);
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+}
+
+#[test]
+fn damaged_central_directory() {
+    corpus::install_test_subscriber();
+
+    let mut bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+
+    // garble the second (and last) central directory header
+    let header_offset = bytes.windows(4).rposition(|w| w == b"PK\x01\x02").unwrap();
+    bytes[header_offset..header_offset + 4].copy_from_slice(b"junk");
+
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    match read_archive(fsm, &bytes) {
+        Err(Error::Format(FormatError::InvalidCentralRecord { expected, actual })) => {
+            assert_eq!((expected, actual), (1, 2));
+        }
+        Err(e) => panic!("unexpected error: {e}"),
+        Ok(_) => panic!("should have failed"),
+    }
+
+    let fsm = ArchiveFsm::new(bytes.len() as u64).with_recovery(RecoveryMode::BestEffort);
+    let archive = read_archive(fsm, &bytes).unwrap();
+    assert!(archive.truncated());
+    let names: Vec<_> = archive.entries().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, ["test.txt"]);
+
+    // intact archives aren't flagged
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64).with_recovery(RecoveryMode::BestEffort);
+    let archive = read_archive(fsm, &bytes).unwrap();
+    assert!(!archive.truncated());
+    assert_eq!(archive.entries().count(), 2);
+}
+
+#[test]
+fn fuzz_regressions() {
+    corpus::install_test_subscriber();
+
+    for artifact in corpus::fuzz_artifacts() {
+        let replay = corpus::replay_fuzz_artifact(&artifact.bytes());
+        assert!(
+            replay.archive.is_err(),
+            "{}: reading the archive should have failed",
+            artifact.name
+        );
+        assert!(
+            replay.streaming.is_err(),
+            "{}: streaming the first entry should have failed",
+            artifact.name
+        );
+    }
+}
+
+#[test]
+fn normalized_entries() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("noisy-paths.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+
+    let names = archive
+        .normalized_entries()
+        .map(|(normalized, entry)| (normalized.into_owned(), entry.name.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            ("a/b.txt".to_string(), "./a//b.txt"),
+            ("a/c.txt".to_string(), "a/./c.txt"),
+            ("d/".to_string(), ".//d/"),
+            ("d/e/f.txt".to_string(), "d///e/./f.txt"),
+            ("plain.txt".to_string(), "plain.txt"),
+        ]
+    );
+
+    // clean names aren't copied
+    let plain = archive.by_name("plain.txt").unwrap();
+    assert!(matches!(
+        plain.normalized_name(),
+        std::borrow::Cow::Borrowed(_)
+    ));
+
+    // leading slashes and parent components are someone else's problem
+    let entry = EntryBuilder::new("/x/.//../y").build();
+    assert_eq!(entry.normalized_name(), "/x/../y");
+}
+
+#[test]
+fn modified_local() {
+    corpus::install_test_subscriber();
+
+    let read = |name: &str| {
+        let bytes = std::fs::read(zips_dir().join(name)).unwrap();
+        let fsm = ArchiveFsm::new(bytes.len() as u64);
+        read_archive(fsm, &bytes).unwrap()
+    };
+    let wall_clock = NaiveDate::from_ymd_opt(2017, 10, 31)
+        .unwrap()
+        .and_hms_opt(21, 11, 58)
+        .unwrap();
+
+    // only has an MS-DOS timestamp, which `modified` takes as UTC
+    let archive = read("time-win7.zip");
+    let entry = archive.by_name("test.txt").unwrap();
+    assert_eq!(entry.modified_local(), wall_clock);
+    assert_eq!(entry.modified, wall_clock.and_utc());
+
+    // also has an extended timestamp, which is actually UTC (and has
+    // one-second precision)
+    let archive = read("time-infozip.zip");
+    let entry = archive.by_name("test.txt").unwrap();
+    assert_eq!(entry.modified_local(), wall_clock);
+    assert_eq!(
+        entry.modified,
+        NaiveDate::from_ymd_opt(2017, 11, 1)
+            .unwrap()
+            .and_hms_opt(4, 11, 57)
+            .unwrap()
+            .and_utc()
+    );
+}
+
+#[test]
+fn unicode_path() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("unicode-path.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let mut archive = read_archive(fsm, &bytes).unwrap();
+
+    let entry = archive.by_name("日本語.txt").unwrap();
+    assert_eq!(entry.name_bytes(), b"????.txt");
+    match &entry.extra_fields().collect::<Vec<_>>()[..] {
+        [ExtraField::UnicodePath(up)] => {
+            assert_eq!(up.version, 1);
+            assert_eq!(up.unicode_name(b"????.txt"), Some("日本語.txt"));
+            assert_eq!(up.unicode_name(b"other.txt"), None);
+        }
+        _ => panic!("expected a unicode path extra field"),
+    }
+
+    // the recovered name doesn't depend on the encoding
+    archive.redecode_names(Encoding::ShiftJis);
+    assert!(archive.by_name("日本語.txt").is_some());
+    assert!(archive.by_name("stale.txt").is_some());
+}
+
+#[test]
+fn unicode_comment() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("unicode-comment.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let mut archive = read_archive(fsm, &bytes).unwrap();
+
+    let check = |archive: &Archive| {
+        let entry = archive.by_name("commented.txt").unwrap();
+        assert_eq!(entry.comment, "コメント");
+
+        // the unicode comment extra field was made for another comment
+        let entry = archive.by_name("stale.txt").unwrap();
+        assert_eq!(entry.comment, "legacy comment");
+    };
+    check(&archive);
+
+    archive.redecode_names(Encoding::Cp437);
+    check(&archive);
+}
+
+#[test]
+fn ntfs_timestamps() {
+    corpus::install_test_subscriber();
+
+    let read = |name: &str| {
+        let bytes = std::fs::read(zips_dir().join(name)).unwrap();
+        let fsm = ArchiveFsm::new(bytes.len() as u64);
+        read_archive(fsm, &bytes).unwrap()
+    };
+    let utc = |(h, m, s), nanos| {
+        NaiveDate::from_ymd_opt(2017, 11, 1)
+            .unwrap()
+            .and_hms_nano_opt(h, m, s, nanos)
+            .unwrap()
+            .and_utc()
+    };
+
+    // 100ns precision, rather than the 2s of MS-DOS timestamps
+    let archive = read("time-7zip.zip");
+    let entry = archive.by_name("test.txt").unwrap();
+    assert_eq!(entry.modified, utc((4, 11, 57), 244_817_900));
+    assert_eq!(entry.created, Some(entry.modified));
+    assert_eq!(entry.accessed, Some(utc((4, 13, 19), 623_782_200)));
+
+    // zeroed out timestamps weren't recorded
+    let archive = read("found-me-bzip2.zip");
+    let entry = archive.by_name("found-me.txt").unwrap();
+    assert_eq!(entry.created, None);
+    assert_eq!(entry.accessed, None);
+}
+
+#[test]
+fn encryption() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("encrypted.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+
+    let entry = archive.by_name("plain.txt").unwrap();
+    assert!(!entry.is_encrypted());
+    assert_eq!(entry.encryption(), Encryption::None);
+
+    let entry = archive.by_name("zipcrypto.txt").unwrap();
+    assert!(entry.is_encrypted());
+    assert_eq!(entry.encryption(), Encryption::ZipCrypto);
+
+    let entry = archive.by_name("aes.txt").unwrap();
+    assert!(entry.is_encrypted());
+    assert_eq!(entry.method, Method::Aex);
+    assert_eq!(
+        entry.encryption(),
+        Encryption::WinZipAes {
+            strength: AesStrength::Aes256
+        }
+    );
+    let aes = entry
+        .extra_fields()
+        .find_map(|ef| match ef {
+            ExtraField::WinZipAes(aes) => Some(aes),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(aes.version, 2);
+    assert_eq!(aes.method, Method::Store);
+}
+
+#[test]
+fn validation() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("zeroes.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+
+    // the central directory says the entry is half as large as it is
+    let mut entry = archive.entries().next().unwrap().clone();
+    let actual_size = entry.uncompressed_size;
+    entry.uncompressed_size /= 2;
+
+    // returns how many bytes were read before failing (if at all)
+    let read = |validation: Validation| -> (u64, Result<(), Error>) {
+        let mut fsm = EntryFsm::new(Some(entry.clone()), None).with_validation(validation);
+        let mut data = &bytes[entry.header_offset as usize..];
+        let mut out = vec![0u8; 16 * 1024];
+        let mut total = 0;
+        loop {
+            if fsm.wants_read() {
+                let n = data.len().min(fsm.space().len());
+                fsm.space()[..n].copy_from_slice(&data[..n]);
+                fsm.fill(n);
+                data = &data[n..];
+            }
+            match fsm.process(&mut out) {
+                Ok(FsmResult::Continue((next, outcome))) => {
+                    total += outcome.bytes_written as u64;
+                    fsm = next;
+                }
+                Ok(FsmResult::Done(_)) => return (total, Ok(())),
+                Err(e) => return (total, Err(e)),
+            }
+        }
+    };
+
+    let (total, res) = read(Validation::Skip);
+    assert_eq!(total, actual_size);
+    assert!(res.is_ok());
+
+    let (total, res) = read(Validation::OnFullRead);
+    assert_eq!(total, actual_size);
+    assert!(matches!(
+        res,
+        Err(Error::Format(FormatError::WrongSize { actual, .. })) if actual == actual_size
+    ));
+
+    // fails as soon as it goes over the expected size
+    let (total, res) = read(Validation::Always);
+    assert!(total <= entry.uncompressed_size);
+    assert!(matches!(
+        res,
+        Err(Error::Format(FormatError::WrongSize { expected, .. })) if expected == entry.uncompressed_size
+    ));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel() {
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let bytes = case.bytes();
+        let sequential = read_archive(ArchiveFsm::new(bytes.len() as u64), &bytes);
+        let parallel = read_archive(
+            ArchiveFsm::new(bytes.len() as u64).with_parallel(true),
+            &bytes,
+        );
+        corpus::check_case(&case, parallel.as_ref());
+
+        if let (Ok(sequential), Ok(parallel)) = (sequential, parallel) {
+            assert_eq!(sequential.digest(), parallel.digest(), "{}", case.name);
+            assert_eq!(sequential.encoding(), parallel.encoding(), "{}", case.name);
+            let names = |archive: &Archive| {
+                archive
+                    .entries()
+                    .map(|entry| entry.name.clone())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(names(&sequential), names(&parallel), "{}", case.name);
+        }
+    }
+}
+
+#[test]
+fn streaming_entries() {
+    corpus::install_test_subscriber();
+
+    for case in corpus::test_cases() {
+        let bytes = case.bytes();
+        let Ok(archive) = read_archive(ArchiveFsm::new(bytes.len() as u64), &bytes) else {
+            continue;
+        };
+
+        let mut fsm = ArchiveFsm::new(bytes.len() as u64)
+            .with_encoding(archive.encoding())
+            .with_streaming(true);
+        let mut streamed = Vec::new();
+        let rest = loop {
+            if let Some(offset) = fsm.wants_read() {
+                // feed chunks shorter than a header, so that entries get
+                // handed out before the end of the central directory is found
+                let slice = &bytes[offset as usize..];
+                let len = cmp::min(cmp::min(slice.len(), 32), fsm.space().len());
+                fsm.space()[..len].copy_from_slice(&slice[..len]);
+                fsm.fill(len);
+            }
+
+            fsm = match fsm.process().unwrap() {
+                FsmResult::Continue(mut fsm) => {
+                    streamed.extend(std::iter::from_fn(|| fsm.next_entry()));
+                    fsm
+                }
+                FsmResult::Done(archive) => break archive,
+            }
+        };
+        if archive.len() > 1 {
+            assert!(!streamed.is_empty(), "{}", case.name);
+        }
+        streamed.extend(rest.into_entries());
+
+        let names = |entries: &[Entry]| {
+            entries
+                .iter()
+                .map(|entry| (entry.name.clone(), entry.header_offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&archive.into_entries()),
+            names(&streamed),
+            "{}",
+            case.name
+        );
+    }
+}
+
+#[test]
+fn prefix_len() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("sfx-elf.zip")).unwrap();
+    let archive = read_archive(ArchiveFsm::new(bytes.len() as u64), &bytes).unwrap();
+    assert_eq!(archive.prefix_len(), 1024);
+    assert!(bytes.starts_with(b"\x7fELF"));
+
+    // stripping the stub leaves a regular zip file
+    let stripped = &bytes[archive.prefix_len() as usize..];
+    let stripped_archive = read_archive(ArchiveFsm::new(stripped.len() as u64), stripped).unwrap();
+    assert_eq!(stripped_archive.prefix_len(), 0);
+    for (entry, stripped_entry) in archive.entries().zip(stripped_archive.entries()) {
+        assert_eq!(entry.name, stripped_entry.name);
+        assert_eq!(
+            entry.header_offset,
+            stripped_entry.header_offset + archive.prefix_len()
+        );
+    }
+}
+
+#[test]
+fn host_system_modes() {
+    corpus::install_test_subscriber();
+
+    // made on Windows: only MS-DOS attributes, and NTFS recorded as 11
+    let bytes = std::fs::read(zips_dir().join("winxp.zip")).unwrap();
+    let archive = read_archive(ArchiveFsm::new(bytes.len() as u64), &bytes).unwrap();
+    let entry = archive.by_name("hello").unwrap();
+    assert_eq!(entry.creator_version.host_system(), HostSystem::Mvs);
+    assert!(matches!(entry.kind(), EntryKind::File));
+    assert_eq!(entry.mode, Mode(0o644));
+    let entry = archive.by_name("dir/empty/").unwrap();
+    assert!(matches!(entry.kind(), EntryKind::Directory));
+    assert_eq!(entry.mode, Mode::DIR | Mode(0o755));
+
+    // unix hosts that don't fill in the mode bits
+    assert_eq!(
+        Mode::from_external_attrs(HostSystem::Unix, 0x10),
+        Mode::from_external_attrs(HostSystem::MsDos, 0x10)
+    );
+    assert!(Mode::from_external_attrs(HostSystem::Unix, 0x10).has(Mode::DIR));
+    assert_eq!(
+        Mode::from_external_attrs(HostSystem::Unix, 0o100644 << 16),
+        Mode(0o644)
+    );
+}
+
+#[test]
+fn directory_attributes() {
+    corpus::install_test_subscriber();
+
+    // directories without a trailing slash, marked in only one of the MS-DOS
+    // attributes and the Unix mode
+    let bytes = std::fs::read(zips_dir().join("dir-attributes.zip")).unwrap();
+    let archive = read_archive(ArchiveFsm::new(bytes.len() as u64), &bytes).unwrap();
+    let kind = |name| archive.by_name(name).unwrap().kind();
+
+    assert!(matches!(kind("unix-perms-dos-dir"), EntryKind::Directory));
+    assert_eq!(
+        archive.by_name("unix-perms-dos-dir").unwrap().mode,
+        Mode::DIR | Mode(0o755)
+    );
+    assert!(matches!(kind("dos-host-unix-dir"), EntryKind::Directory));
+    assert!(matches!(kind("unix-perms-file"), EntryKind::File));
+    // the Unix file type wins over the MS-DOS attributes
+    assert!(matches!(kind("unix-file-dos-dir"), EntryKind::File));
+}
+
+#[test]
+fn msdos_modes() {
+    assert_eq!(Mode::from(MsdosMode(0x20)), Mode(0o644));
+    assert_eq!(Mode::from(MsdosMode(0x10)), Mode::DIR | Mode(0o755));
+    assert_eq!(
+        Mode::from(MsdosMode::READ_ONLY | MsdosMode(0x20)),
+        Mode(0o444)
+    );
+    assert_eq!(
+        Mode::from(MsdosMode::READ_ONLY | MsdosMode::DIR),
+        Mode::DIR | Mode(0o555)
+    );
+    assert_eq!(Mode::from(MsdosMode(0x20)).to_string(), "-rw-r--r--");
+}
+
+#[test]
+fn method_codes() {
+    for code in [0, 8, 9, 12, 14, 93, 95, 98, 99, 42] {
+        assert_eq!(Method::from_u16(code).as_u16(), code);
+    }
+    assert_eq!(Method::from_u16(8), Method::Deflate);
+    assert_eq!(Method::from_u16(42), Method::Unrecognized(42));
+
+    assert_eq!(Method::Deflate.to_string(), "Deflate");
+    assert_eq!(Method::Lzma.to_string(), "LZMA");
+    assert_eq!(Method::Zstd.to_string(), "Zstd");
+    assert_eq!(Method::Unrecognized(42).to_string(), "method 42");
+}
+
+#[test]
+fn unsupported_method_errors() {
+    assert_eq!(
+        Error::method_not_supported(Method::Jpeg).to_string(),
+        "unsupported: compression method not supported: JPEG (method 96)"
+    );
+    assert_eq!(
+        Error::method_not_supported(Method::from_u16(4242)).to_string(),
+        "unsupported: compression method not supported: unrecognized method 4242"
+    );
+    assert_eq!(
+        Error::method_not_enabled(Method::Zstd).to_string(),
+        "unsupported: compression method supported, but not enabled in this build: Zstd (method 93)"
+    );
+}
+
+#[test]
+fn digital_signature() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("signed.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+    assert_eq!(
+        archive.digital_signature(),
+        Some(&b"not a real signature, but it will do"[..])
+    );
+    let names: Vec<_> = archive.entries().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["signed.txt", "also-signed.txt"]);
+
+    #[cfg(feature = "rayon")]
+    {
+        let fsm = ArchiveFsm::new(bytes.len() as u64).with_parallel(true);
+        let parallel = read_archive(fsm, &bytes).unwrap();
+        assert_eq!(parallel.digital_signature(), archive.digital_signature());
+        assert_eq!(parallel.entries().count(), 2);
+    }
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    assert_eq!(read_archive(fsm, &bytes).unwrap().digital_signature(), None);
+}
+
+#[test]
+fn comment_raw() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64).with_encoding(Encoding::Cp437);
+    let mut archive = read_archive(fsm, &bytes).unwrap();
+    assert_eq!(archive.comment_raw(), b"This is a zipfile comment.");
+    assert_eq!(archive.comment_encoding(), Encoding::Cp437);
+
+    archive.set_comment("r\u{e9}sum\u{e9}").unwrap();
+    assert_eq!(archive.comment_raw(), "r\u{e9}sum\u{e9}".as_bytes());
+    assert_eq!(archive.comment_encoding(), Encoding::Utf8);
+    assert_eq!(archive.encoding(), Encoding::Cp437);
+
+    // the UTF-8 bytes come out garbled when decoded as cp-437
+    archive.redecode_names(Encoding::Cp437);
+    assert_eq!(archive.comment_encoding(), Encoding::Cp437);
+    assert_eq!(archive.comment(), "r\u{251c}\u{2310}sum\u{251c}\u{2310}");
+}
+
+#[test]
+fn entries_sorted_by() {
+    corpus::install_test_subscriber();
+
+    let bytes = std::fs::read(zips_dir().join("unix.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+
+    let names = |key| {
+        archive
+            .entries_sorted_by(key)
+            .into_iter()
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names(SortKey::Name),
+        ["dir/bar", "dir/empty/", "hello", "readonly"]
+    );
+    assert_eq!(
+        names(SortKey::Size),
+        ["dir/empty/", "dir/bar", "hello", "readonly"]
+    );
+    assert_eq!(
+        names(SortKey::Modified),
+        ["hello", "dir/bar", "readonly", "dir/empty/"]
+    );
+    assert_eq!(
+        names(SortKey::Offset),
+        ["hello", "dir/bar", "dir/empty/", "readonly"]
+    );
+
+    // the central directory order is left alone
+    let physical: Vec<_> = archive.entries().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(physical, names(SortKey::Offset));
+
+    let bytes = std::fs::read(zips_dir().join("test.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+    let names = archive
+        .entries_sorted_by(SortKey::Method)
+        .into_iter()
+        .map(|entry| entry.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["gophercolor16x16.png", "test.txt"]);
+}
+
+#[test]
+fn sanitized_name() {
+    let sanitized = |name: &str| EntryBuilder::new(name).build().sanitized_name();
+
+    for (name, expected) in [
+        ("a/b.txt", "a/b.txt"),
+        ("/etc/passwd", "etc/passwd"),
+        ("\\\\server\\share\\file", "server/share/file"),
+        ("a\\b\\c.txt", "a/b/c.txt"),
+        ("./a//b/./c", "a/b/c"),
+        ("dir/", "dir"),
+        ("a..b/..c", "a..b/..c"),
+        ("console.log", "console.log"),
+        ("ab:c/d", "ab:c/d"),
+    ] {
+        let expected: std::path::PathBuf = expected.split('/').collect();
+        assert_eq!(sanitized(name), Some(expected), "{name}");
+    }
+
+    for name in [
+        "../evil.txt",
+        "a/../../evil.txt",
+        "a/b/..",
+        "..\\evil.txt",
+        "a\\..\\..\\evil.txt",
+        "/../evil.txt",
+        "a/.. /evil.txt",
+        "a/.../evil.txt",
+        "C:\\Windows\\System32\\evil.dll",
+        "c:evil.txt",
+        "/C:/evil.txt",
+        "src/c:/evil.txt",
+        "a:b/evil.txt",
+        "\\\\?\\C:\\evil.txt",
+        "CON",
+        "dir/nul",
+        "aux.txt",
+        "Com1.tar.gz",
+        "lpt9 .txt",
+        "/",
+        "./",
+        "",
+    ] {
+        assert_eq!(sanitized(name), None, "{name}");
+    }
+}
+
+#[test]
+fn local_and_central_extra_fields() {
+    corpus::install_test_subscriber();
+
+    fn unknown_payloads<'a>(fields: impl Iterator<Item = ExtraField<'a>>) -> Vec<&'a [u8]> {
+        fields
+            .map(|field| match field {
+                ExtraField::Unknown { tag: 0xcafe, data } => data,
+                _ => panic!("unexpected extra field"),
+            })
+            .collect()
+    }
+
+    let bytes = std::fs::read(zips_dir().join("local-central-extra.zip")).unwrap();
+    let fsm = ArchiveFsm::new(bytes.len() as u64);
+    let archive = read_archive(fsm, &bytes).unwrap();
+    let entry = archive.by_name("both.txt").unwrap();
+    assert_eq!(
+        unknown_payloads(entry.central_extra_fields().unwrap()),
+        [&b"cntrl"[..]]
+    );
+    assert!(entry.local_extra_fields().is_none());
+
+    // the local header is only read along with the data
+    let mut fsm = EntryFsm::new(Some(entry.clone()), None);
+    let data = &bytes[entry.header_offset as usize..];
+    let n = data.len().min(fsm.space().len());
+    fsm.space()[..n].copy_from_slice(&data[..n]);
+    fsm.fill(n);
+    let entry = fsm.process_till_header().unwrap().unwrap();
+    assert_eq!(
+        unknown_payloads(entry.local_extra_fields().unwrap()),
+        [&b"local"[..]]
+    );
+    assert_eq!(
+        unknown_payloads(entry.central_extra_fields().unwrap()),
+        [&b"cntrl"[..]]
+    );
+
+    // streaming, there's only the local header to go by
+    let mut fsm = EntryFsm::new(None, None);
+    let n = bytes.len().min(fsm.space().len());
+    fsm.space()[..n].copy_from_slice(&bytes[..n]);
+    fsm.fill(n);
+    let entry = fsm.process_till_header().unwrap().unwrap();
+    assert!(entry.central_extra_fields().is_none());
+    assert_eq!(
+        unknown_payloads(entry.local_extra_fields().unwrap()),
+        [&b"local"[..]]
+    );
+}